@@ -0,0 +1,124 @@
+use crate::font;
+
+/// Space in pixels between the edge of the window and the text
+pub const TEXT_MARGIN: usize = 2;
+
+/// Size and scaling of the GPU's text screen, chosen when the machine is built and changeable at
+/// runtime through the text mode OpCode
+#[derive(Debug, Clone)]
+pub struct GpuConfig {
+    /// Number of collumns of the frame buffer
+    pub columns: usize,
+    /// Number of lines of the frame buffer
+    pub rows: usize,
+    /// Width of a character cell in pixels
+    pub cell_width: usize,
+    /// Height of a character cell in pixels
+    pub cell_height: usize,
+    /// Integer factor every pixel is scaled up by
+    pub scale: usize,
+    /// PSF or BDF font loaded from the host instead of the built-in one
+    pub font_path: Option<String>,
+}
+
+impl Default for GpuConfig {
+    fn default() -> Self {
+        Self {
+            columns: 136,
+            rows: 45,
+            cell_width: 7,
+            cell_height: font::GLYPH_HEIGHT,
+            scale: 1,
+            font_path: None,
+        }
+    }
+}
+
+impl GpuConfig {
+    /// Builds a configuration from command line arguments, falling back to the defaults for
+    /// everything not given:
+    /// `--text <collumns>x<lines>`, `--cell <width>x<height>`, `--scale <factor>` and
+    /// `--font <path>`
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut config = Self::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--text" => (config.columns, config.rows) = parse_size(value()?)?,
+                "--cell" => (config.cell_width, config.cell_height) = parse_size(value()?)?,
+                "--scale" => {
+                    let value = value()?;
+                    config.scale = value.parse().map_err(|_| format!("Invalid scale: {}", value))?;
+                }
+                "--font" => config.font_path = Some(value()?.clone()),
+                _ => {}
+            }
+        }
+        Ok(config.clamped())
+    }
+
+    /// Returns the configuration with every value limited to what the GPU supports
+    pub fn clamped(mut self) -> Self {
+        self.columns = self.columns.clamp(1, 255);
+        self.rows = self.rows.clamp(1, 255);
+        self.cell_width = self.cell_width.clamp(4, 16);
+        self.cell_height = self.cell_height.clamp(6, 24);
+        self.scale = self.scale.clamp(1, 8);
+        self
+    }
+
+    /// Returns the unscaled size of the screen in pixels
+    pub fn screen_size(&self) -> (usize, usize) {
+        (
+            self.columns * self.cell_width + 2 * TEXT_MARGIN,
+            self.rows * self.cell_height + 2 * TEXT_MARGIN,
+        )
+    }
+
+    /// Returns the size of the window in pixels
+    pub fn window_size(&self) -> (usize, usize) {
+        let (width, height) = self.screen_size();
+        (width * self.scale, height * self.scale)
+    }
+}
+
+/// Parses a size written as `<width>x<height>`
+fn parse_size(value: &str) -> Result<(usize, usize), String> {
+    value
+        .split_once('x')
+        .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+        .ok_or(format!("Invalid size: {}, expected <width>x<height>", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn arguments_override_the_defaults() {
+        let config = GpuConfig::from_args(&args("--text 80x25 --scale 2 --font a.psf")).unwrap();
+        assert_eq!((config.columns, config.rows, config.scale), (80, 25, 2));
+        assert_eq!((config.cell_width, config.cell_height), (7, font::GLYPH_HEIGHT));
+        assert_eq!(config.font_path.as_deref(), Some("a.psf"));
+        assert_eq!(config.window_size(), (2 * (80 * 7 + 4), 2 * (25 * 12 + 4)));
+    }
+
+    #[test]
+    fn values_are_limited_to_what_the_gpu_supports() {
+        let config = GpuConfig::from_args(&args("--text 0x1000 --cell 1x99 --scale 20")).unwrap();
+        assert_eq!((config.columns, config.rows), (1, 255));
+        assert_eq!((config.cell_width, config.cell_height, config.scale), (4, 24, 8));
+    }
+
+    #[test]
+    fn invalid_arguments_are_rejected() {
+        assert!(GpuConfig::from_args(&args("--text 80")).is_err());
+        assert!(GpuConfig::from_args(&args("--scale big")).is_err());
+        assert!(GpuConfig::from_args(&args("--cell")).is_err());
+    }
+}
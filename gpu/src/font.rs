@@ -0,0 +1,347 @@
+// NOTE: GLYPH FORMAT
+// A glyph is 8x12 pixels, one byte per row with the leftmost pixel in the highest bit. Text cells
+// are 7 pixels wide, so the lowest bit of every row is cut off on screen. In the command ring a
+// glyph takes up 6 16-bit values, two rows per value with the upper row in the high byte.
+
+/// Width of a glyph in pixels
+pub const GLYPH_WIDTH: usize = 8;
+/// Height of a glyph in pixels
+pub const GLYPH_HEIGHT: usize = 12;
+/// Number of glyphs in the glyph table, one for every character byte
+pub const GLYPH_COUNT: usize = 256;
+/// Size of a glyph in words
+pub const GLYPH_WORDS: usize = GLYPH_HEIGHT / 2;
+
+/// The rows of a glyph, top to bottom
+pub type Glyph = [u8; GLYPH_HEIGHT];
+
+/// The GPU's glyph table, indexed by character byte
+#[derive(Debug, Clone)]
+pub struct Font {
+    pub glyphs: Vec<Glyph>,
+}
+
+impl Default for Font {
+    /// The built-in font. Printable ASCII characters come from ProggyClean, everything else is
+    /// blank until a program defines it.
+    fn default() -> Self {
+        let mut glyphs = vec![[0; GLYPH_HEIGHT]; GLYPH_COUNT];
+        glyphs[0x20..0x7F].copy_from_slice(&DEFAULT_GLYPHS);
+        Self { glyphs }
+    }
+}
+
+impl Font {
+    /// Loads a PSF (version 1 or 2) or BDF font from the host. Glyphs wider than 8 pixels are cut
+    /// off on the right with a warning, glyphs of a different height are centered vertically.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|error| format!("Could not read {}: {}", path, error))?;
+        if bytes.starts_with(&PSF1_MAGIC) || bytes.starts_with(&PSF2_MAGIC) {
+            Self::from_psf(&bytes)
+        } else if bytes.starts_with(b"STARTFONT") {
+            Self::from_bdf(&String::from_utf8_lossy(&bytes))
+        } else {
+            Err(format!("{} is neither a PSF nor a BDF font", path))
+        }
+    }
+
+    /// Parses a PSF font. Glyph n becomes character byte n. A header with empty glyphs or glyphs
+    /// too small for their rows is an error, glyphs wider than 8 pixels are cut off with a warning.
+    pub fn from_psf(bytes: &[u8]) -> Result<Self, String> {
+        let (offset, count, glyph_size, height, width) = if bytes.starts_with(&PSF1_MAGIC) {
+            if bytes.len() < 4 {
+                return Err("Truncated PSF1 header".to_string());
+            }
+            let count = if bytes[2] & 0x01 != 0 { 512 } else { 256 };
+            (4, count, bytes[3] as usize, bytes[3] as usize, GLYPH_WIDTH)
+        } else if bytes.starts_with(&PSF2_MAGIC) {
+            if bytes.len() < 32 {
+                return Err("Truncated PSF2 header".to_string());
+            }
+            let field = |index: usize| {
+                u32::from_le_bytes(bytes[index * 4..index * 4 + 4].try_into().unwrap()) as usize
+            };
+            (field(2), field(4), field(5), field(6), field(7))
+        } else {
+            return Err("Missing PSF magic number".to_string());
+        };
+
+        let row_size = width.div_ceil(8);
+        if width == 0 || height == 0 || glyph_size == 0 {
+            return Err(format!(
+                "Invalid PSF glyphs of {}x{} pixels in {} bytes",
+                width, height, glyph_size
+            ));
+        }
+        if height * row_size > glyph_size {
+            return Err(format!(
+                "PSF glyphs of {} bytes can't hold {} rows of {} bytes",
+                glyph_size, height, row_size
+            ));
+        }
+        if width > GLYPH_WIDTH {
+            use colored::Colorize;
+            println!(
+                "{}: The font's glyphs are {} pixels wide, only the leftmost {} are drawn",
+                "[GPU WARNING]".yellow(),
+                width,
+                GLYPH_WIDTH
+            );
+        }
+
+        let mut font = Self { glyphs: vec![[0; GLYPH_HEIGHT]; GLYPH_COUNT] };
+        for index in 0..count.min(GLYPH_COUNT) {
+            let start = offset + index * glyph_size;
+            let Some(data) = bytes.get(start..start + height * row_size) else {
+                return Err(format!("Truncated glyph data at glyph {}", index));
+            };
+            let rows: Vec<u8> = data.chunks(row_size).map(|row| row[0]).collect();
+            font.glyphs[index] = fit_rows(&rows);
+        }
+        Ok(font)
+    }
+
+    /// Parses a BDF font. Glyphs with an encoding outside of 0 - 255 are skipped.
+    pub fn from_bdf(source: &str) -> Result<Self, String> {
+        let mut font = Self { glyphs: vec![[0; GLYPH_HEIGHT]; GLYPH_COUNT] };
+        let mut font_box = (8, GLYPH_HEIGHT as i32, 0, 0);
+        let mut encoding = None;
+        let mut glyph_box = font_box;
+        let mut bitmap: Option<Vec<u8>> = None;
+
+        for (index, line) in source.lines().enumerate() {
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap_or("");
+            let numbers: Vec<i32> = words.filter_map(|word| word.parse().ok()).collect();
+            let parse_box = || match numbers[..] {
+                [width, height, x_offset, y_offset, ..] => Ok((width, height, x_offset, y_offset)),
+                _ => Err(format!("Line {}: {} needs four numbers", index + 1, keyword)),
+            };
+
+            match keyword {
+                "FONTBOUNDINGBOX" => font_box = parse_box()?,
+                "STARTCHAR" => {
+                    encoding = None;
+                    glyph_box = font_box;
+                }
+                "ENCODING" => encoding = numbers.first().copied(),
+                "BBX" => glyph_box = parse_box()?,
+                "BITMAP" => bitmap = Some(Vec::new()),
+                "ENDCHAR" => {
+                    let rows = bitmap.take().unwrap_or_default();
+                    if let Some(code @ 0..=255) = encoding {
+                        // Place the glyph relative to the font's bounding box so all glyphs share
+                        // the same baseline
+                        let (_, height, x_offset, y_offset) = glyph_box;
+                        let shift = x_offset - font_box.2;
+                        let top = (font_box.1 + font_box.3) - (height + y_offset);
+                        let mut placed = vec![0u8; font_box.1.max(0) as usize];
+                        for (row, bits) in rows.iter().enumerate() {
+                            let y = top + row as i32;
+                            if y >= 0 && (y as usize) < placed.len() {
+                                placed[y as usize] = if shift >= 0 {
+                                    bits.checked_shr(shift as u32).unwrap_or(0)
+                                } else {
+                                    bits.checked_shl(-shift as u32).unwrap_or(0)
+                                };
+                            }
+                        }
+                        font.glyphs[code as usize] = fit_rows(&placed);
+                    }
+                }
+                _ => {
+                    if let Some(rows) = bitmap.as_mut() {
+                        let bits = u8::from_str_radix(line.trim().get(..2).unwrap_or(""), 16)
+                            .map_err(|_| format!("Line {}: Invalid bitmap row {:?}", index + 1, line))?;
+                        rows.push(bits);
+                    }
+                }
+            }
+        }
+        Ok(font)
+    }
+
+    /// Replaces a glyph with the rows packed into the given words
+    pub fn set_glyph(&mut self, index: u8, words: &[u16]) {
+        let glyph = &mut self.glyphs[index as usize];
+        for (pair, word) in words.iter().take(GLYPH_WORDS).enumerate() {
+            glyph[pair * 2] = (word >> 8) as u8;
+            glyph[pair * 2 + 1] = *word as u8;
+        }
+    }
+}
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xB5, 0x4A, 0x86];
+
+/// Centers rows of any count vertically in a glyph, cutting off what doesn't fit
+fn fit_rows(rows: &[u8]) -> Glyph {
+    let mut glyph = [0; GLYPH_HEIGHT];
+    let offset = (GLYPH_HEIGHT as i32 - rows.len() as i32) / 2;
+    for (row, bits) in rows.iter().enumerate() {
+        let y = row as i32 + offset;
+        if (0..GLYPH_HEIGHT as i32).contains(&y) {
+            glyph[y as usize] = *bits;
+        }
+    }
+    glyph
+}
+
+/// Glyphs for the characters 0x20 - 0x7E, rasterized from ProggyClean by Tristan Grimmer (MIT)
+#[rustfmt::skip]
+const DEFAULT_GLYPHS: [Glyph; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x00, 0x10, 0x00, 0x00, 0x00], // '!'
+    [0x28, 0x28, 0x28, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x00, 0x14, 0x14, 0x7E, 0x28, 0x28, 0xFC, 0x50, 0x50, 0x00, 0x00, 0x00], // '#'
+    [0x00, 0x10, 0x3C, 0x50, 0x50, 0x38, 0x14, 0x14, 0x78, 0x10, 0x00, 0x00], // '$'
+    [0x00, 0x44, 0xA8, 0xA8, 0x50, 0x14, 0x2A, 0x2A, 0x44, 0x00, 0x00, 0x00], // '%'
+    [0x00, 0x30, 0x48, 0x48, 0x32, 0x4A, 0x44, 0x44, 0x3A, 0x00, 0x00, 0x00], // '&'
+    [0x10, 0x10, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '\''
+    [0x08, 0x10, 0x10, 0x20, 0x20, 0x20, 0x20, 0x20, 0x10, 0x10, 0x08, 0x00], // '('
+    [0x20, 0x10, 0x10, 0x08, 0x08, 0x08, 0x08, 0x08, 0x10, 0x10, 0x20, 0x00], // ')'
+    [0x00, 0x00, 0x00, 0x10, 0x54, 0x38, 0x54, 0x10, 0x00, 0x00, 0x00, 0x00], // '*'
+    [0x00, 0x00, 0x00, 0x10, 0x10, 0x7C, 0x10, 0x10, 0x00, 0x00, 0x00, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x20, 0x20, 0x40, 0x00], // ','
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7C, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x20, 0x00, 0x00, 0x00], // '.'
+    [0x04, 0x04, 0x08, 0x08, 0x10, 0x10, 0x20, 0x20, 0x40, 0x40, 0x00, 0x00], // '/'
+    [0x00, 0x38, 0x44, 0x44, 0x54, 0x54, 0x44, 0x44, 0x38, 0x00, 0x00, 0x00], // '0'
+    [0x00, 0x10, 0x30, 0x50, 0x10, 0x10, 0x10, 0x10, 0x7C, 0x00, 0x00, 0x00], // '1'
+    [0x00, 0x38, 0x44, 0x04, 0x08, 0x10, 0x20, 0x40, 0x7C, 0x00, 0x00, 0x00], // '2'
+    [0x00, 0x38, 0x44, 0x04, 0x18, 0x04, 0x04, 0x44, 0x38, 0x00, 0x00, 0x00], // '3'
+    [0x00, 0x04, 0x0C, 0x14, 0x24, 0x44, 0x7E, 0x04, 0x04, 0x00, 0x00, 0x00], // '4'
+    [0x00, 0x7C, 0x40, 0x40, 0x78, 0x04, 0x04, 0x44, 0x38, 0x00, 0x00, 0x00], // '5'
+    [0x00, 0x18, 0x20, 0x40, 0x78, 0x44, 0x44, 0x44, 0x38, 0x00, 0x00, 0x00], // '6'
+    [0x00, 0x7C, 0x04, 0x08, 0x08, 0x10, 0x10, 0x20, 0x20, 0x00, 0x00, 0x00], // '7'
+    [0x00, 0x38, 0x44, 0x44, 0x38, 0x44, 0x44, 0x44, 0x38, 0x00, 0x00, 0x00], // '8'
+    [0x00, 0x38, 0x44, 0x44, 0x44, 0x3C, 0x04, 0x08, 0x30, 0x00, 0x00, 0x00], // '9'
+    [0x00, 0x00, 0x00, 0x10, 0x10, 0x00, 0x00, 0x10, 0x10, 0x00, 0x00, 0x00], // ':'
+    [0x00, 0x00, 0x00, 0x20, 0x20, 0x00, 0x00, 0x20, 0x20, 0x20, 0x40, 0x00], // ';'
+    [0x00, 0x00, 0x00, 0x0C, 0x30, 0xC0, 0x30, 0x0C, 0x00, 0x00, 0x00, 0x00], // '<'
+    [0x00, 0x00, 0x00, 0x00, 0x7E, 0x00, 0x7E, 0x00, 0x00, 0x00, 0x00, 0x00], // '='
+    [0x00, 0x00, 0x00, 0x60, 0x18, 0x06, 0x18, 0x60, 0x00, 0x00, 0x00, 0x00], // '>'
+    [0x00, 0x38, 0x44, 0x04, 0x08, 0x10, 0x10, 0x00, 0x10, 0x00, 0x00, 0x00], // '?'
+    [0x00, 0x38, 0x44, 0x9A, 0xAA, 0xAA, 0x9C, 0x40, 0x3C, 0x00, 0x00, 0x00], // '@'
+    [0x00, 0x18, 0x18, 0x24, 0x24, 0x3C, 0x42, 0x42, 0x42, 0x00, 0x00, 0x00], // 'A'
+    [0x00, 0x78, 0x44, 0x44, 0x7C, 0x42, 0x42, 0x42, 0x7C, 0x00, 0x00, 0x00], // 'B'
+    [0x00, 0x1C, 0x22, 0x40, 0x40, 0x40, 0x40, 0x22, 0x1C, 0x00, 0x00, 0x00], // 'C'
+    [0x00, 0x78, 0x44, 0x42, 0x42, 0x42, 0x42, 0x44, 0x78, 0x00, 0x00, 0x00], // 'D'
+    [0x00, 0x7C, 0x40, 0x40, 0x78, 0x40, 0x40, 0x40, 0x7C, 0x00, 0x00, 0x00], // 'E'
+    [0x00, 0x7C, 0x40, 0x40, 0x78, 0x40, 0x40, 0x40, 0x40, 0x00, 0x00, 0x00], // 'F'
+    [0x00, 0x1C, 0x22, 0x40, 0x40, 0x4E, 0x42, 0x22, 0x1C, 0x00, 0x00, 0x00], // 'G'
+    [0x00, 0x42, 0x42, 0x42, 0x7E, 0x42, 0x42, 0x42, 0x42, 0x00, 0x00, 0x00], // 'H'
+    [0x00, 0x38, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x38, 0x00, 0x00, 0x00], // 'I'
+    [0x00, 0x38, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x70, 0x00, 0x00, 0x00], // 'J'
+    [0x00, 0x42, 0x44, 0x48, 0x50, 0x70, 0x48, 0x44, 0x42, 0x00, 0x00, 0x00], // 'K'
+    [0x00, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x7C, 0x00, 0x00, 0x00], // 'L'
+    [0x00, 0xC6, 0xC6, 0xAA, 0xAA, 0x92, 0x92, 0x82, 0x82, 0x00, 0x00, 0x00], // 'M'
+    [0x00, 0x62, 0x62, 0x52, 0x52, 0x4A, 0x4A, 0x46, 0x46, 0x00, 0x00, 0x00], // 'N'
+    [0x00, 0x18, 0x24, 0x42, 0x42, 0x42, 0x42, 0x24, 0x18, 0x00, 0x00, 0x00], // 'O'
+    [0x00, 0x78, 0x44, 0x44, 0x44, 0x78, 0x40, 0x40, 0x40, 0x00, 0x00, 0x00], // 'P'
+    [0x00, 0x18, 0x24, 0x42, 0x42, 0x42, 0x42, 0x24, 0x1A, 0x02, 0x00, 0x00], // 'Q'
+    [0x00, 0x78, 0x44, 0x44, 0x44, 0x78, 0x48, 0x44, 0x42, 0x00, 0x00, 0x00], // 'R'
+    [0x00, 0x3C, 0x42, 0x40, 0x30, 0x0C, 0x02, 0x42, 0x3C, 0x00, 0x00, 0x00], // 'S'
+    [0x00, 0xFE, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x00, 0x00, 0x00], // 'T'
+    [0x00, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x3C, 0x00, 0x00, 0x00], // 'U'
+    [0x00, 0x82, 0x82, 0x44, 0x44, 0x28, 0x28, 0x10, 0x10, 0x00, 0x00, 0x00], // 'V'
+    [0x00, 0x82, 0x92, 0x92, 0xAA, 0xAA, 0x6C, 0x44, 0x44, 0x00, 0x00, 0x00], // 'W'
+    [0x00, 0x42, 0x42, 0x24, 0x18, 0x18, 0x24, 0x42, 0x42, 0x00, 0x00, 0x00], // 'X'
+    [0x00, 0x82, 0x82, 0x44, 0x28, 0x10, 0x10, 0x10, 0x10, 0x00, 0x00, 0x00], // 'Y'
+    [0x00, 0x7E, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x7E, 0x00, 0x00, 0x00], // 'Z'
+    [0x38, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x38, 0x00], // '['
+    [0x40, 0x40, 0x20, 0x20, 0x10, 0x10, 0x08, 0x08, 0x04, 0x04, 0x00, 0x00], // '\\'
+    [0x38, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x38, 0x00], // ']'
+    [0x10, 0x10, 0x28, 0x28, 0x44, 0x44, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFE, 0x00, 0x00], // '_'
+    [0x20, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '`'
+    [0x00, 0x00, 0x00, 0x38, 0x04, 0x3C, 0x44, 0x44, 0x3C, 0x00, 0x00, 0x00], // 'a'
+    [0x40, 0x40, 0x40, 0x78, 0x44, 0x44, 0x44, 0x44, 0x78, 0x00, 0x00, 0x00], // 'b'
+    [0x00, 0x00, 0x00, 0x38, 0x44, 0x40, 0x40, 0x44, 0x38, 0x00, 0x00, 0x00], // 'c'
+    [0x04, 0x04, 0x04, 0x3C, 0x44, 0x44, 0x44, 0x44, 0x3C, 0x00, 0x00, 0x00], // 'd'
+    [0x00, 0x00, 0x00, 0x38, 0x44, 0x7C, 0x40, 0x44, 0x38, 0x00, 0x00, 0x00], // 'e'
+    [0x1C, 0x20, 0x20, 0x78, 0x20, 0x20, 0x20, 0x20, 0x20, 0x00, 0x00, 0x00], // 'f'
+    [0x00, 0x00, 0x00, 0x3C, 0x44, 0x44, 0x44, 0x44, 0x3C, 0x04, 0x04, 0x38], // 'g'
+    [0x40, 0x40, 0x40, 0x78, 0x44, 0x44, 0x44, 0x44, 0x44, 0x00, 0x00, 0x00], // 'h'
+    [0x10, 0x00, 0x00, 0x30, 0x10, 0x10, 0x10, 0x10, 0x10, 0x00, 0x00, 0x00], // 'i'
+    [0x08, 0x00, 0x00, 0x18, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x70, 0x00], // 'j'
+    [0x40, 0x40, 0x40, 0x44, 0x48, 0x50, 0x70, 0x48, 0x44, 0x00, 0x00, 0x00], // 'k'
+    [0x30, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x00, 0x00, 0x00], // 'l'
+    [0x00, 0x00, 0x00, 0xEC, 0x92, 0x92, 0x92, 0x92, 0x92, 0x00, 0x00, 0x00], // 'm'
+    [0x00, 0x00, 0x00, 0x78, 0x44, 0x44, 0x44, 0x44, 0x44, 0x00, 0x00, 0x00], // 'n'
+    [0x00, 0x00, 0x00, 0x38, 0x44, 0x44, 0x44, 0x44, 0x38, 0x00, 0x00, 0x00], // 'o'
+    [0x00, 0x00, 0x00, 0x78, 0x44, 0x44, 0x44, 0x44, 0x78, 0x40, 0x40, 0x40], // 'p'
+    [0x00, 0x00, 0x00, 0x3C, 0x44, 0x44, 0x44, 0x44, 0x3C, 0x04, 0x04, 0x04], // 'q'
+    [0x00, 0x00, 0x00, 0x58, 0x64, 0x40, 0x40, 0x40, 0x40, 0x00, 0x00, 0x00], // 'r'
+    [0x00, 0x00, 0x00, 0x3C, 0x40, 0x30, 0x08, 0x04, 0x78, 0x00, 0x00, 0x00], // 's'
+    [0x00, 0x20, 0x20, 0x3C, 0x20, 0x20, 0x20, 0x20, 0x1C, 0x00, 0x00, 0x00], // 't'
+    [0x00, 0x00, 0x00, 0x44, 0x44, 0x44, 0x44, 0x44, 0x3C, 0x00, 0x00, 0x00], // 'u'
+    [0x00, 0x00, 0x00, 0x44, 0x44, 0x28, 0x28, 0x10, 0x10, 0x00, 0x00, 0x00], // 'v'
+    [0x00, 0x00, 0x00, 0x82, 0x92, 0x92, 0xAA, 0x6C, 0x44, 0x00, 0x00, 0x00], // 'w'
+    [0x00, 0x00, 0x00, 0x44, 0x28, 0x10, 0x10, 0x28, 0x44, 0x00, 0x00, 0x00], // 'x'
+    [0x00, 0x00, 0x00, 0x44, 0x44, 0x44, 0x44, 0x44, 0x3C, 0x04, 0x04, 0x38], // 'y'
+    [0x00, 0x00, 0x00, 0x7C, 0x04, 0x08, 0x10, 0x20, 0x7C, 0x00, 0x00, 0x00], // 'z'
+    [0x0C, 0x10, 0x10, 0x10, 0x10, 0x60, 0x10, 0x10, 0x10, 0x10, 0x0C, 0x00], // '{'
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x00], // '|'
+    [0x60, 0x10, 0x10, 0x10, 0x10, 0x0C, 0x10, 0x10, 0x10, 0x10, 0x60, 0x00], // '}'
+    [0x00, 0x00, 0x00, 0x00, 0x72, 0x9C, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '~'
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn psf1_glyphs_are_centered() {
+        let mut bytes = vec![0x36, 0x04, 0x00, 8];
+        bytes.resize(4 + 256 * 8, 0);
+        bytes[4 + 0x41 * 8..4 + 0x42 * 8].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        let font = Font::from_psf(&bytes).unwrap();
+        assert_eq!(font.glyphs[0x41], [0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 0, 0]);
+    }
+
+    #[test]
+    fn truncated_psf_fonts_are_rejected() {
+        assert!(Font::from_psf(&[0x36, 0x04, 0x00, 8, 0xFF]).is_err());
+        assert!(Font::from_psf(&[0x36, 0x04]).is_err());
+    }
+
+    #[test]
+    fn bdf_glyphs_share_a_baseline() {
+        let source = "STARTFONT 2.1\nFONTBOUNDINGBOX 8 12 0 -2\n\
+            STARTCHAR dot\nENCODING 46\nBBX 2 2 1 0\nBITMAP\nC0\nC0\nENDCHAR\n\
+            STARTCHAR big\nENCODING 300\nBBX 8 12 0 -2\nBITMAP\nFF\nENDCHAR\nENDFONT\n";
+        let font = Font::from_bdf(source).unwrap();
+        assert_eq!(font.glyphs[46], [0, 0, 0, 0, 0, 0, 0, 0, 0x60, 0x60, 0, 0]);
+        assert_eq!(font.glyphs[44], [0; GLYPH_HEIGHT]);
+    }
+
+    #[test]
+    fn invalid_bdf_rows_are_rejected() {
+        let source = "STARTCHAR a\nENCODING 65\nBITMAP\nZZ\nENDCHAR\n";
+        assert!(Font::from_bdf(source).is_err());
+    }
+
+    /// A PSF2 font of one glyph with the header fields given
+    fn psf2(glyph_size: u32, height: u32, width: u32) -> Vec<u8> {
+        let mut bytes = PSF2_MAGIC.to_vec();
+        for field in [0, 32, 0, 1, glyph_size, height, width] {
+            bytes.extend_from_slice(&u32::to_le_bytes(field));
+        }
+        bytes.resize(32 + glyph_size as usize, 0xFF);
+        bytes
+    }
+
+    #[test]
+    fn empty_psf_glyphs_are_rejected() {
+        assert!(Font::from_psf(&psf2(16, 16, 0)).is_err());
+        assert!(Font::from_psf(&psf2(16, 0, 8)).is_err());
+        assert!(Font::from_psf(&psf2(0, 16, 8)).is_err());
+    }
+
+    #[test]
+    fn psf_glyphs_too_small_for_their_rows_are_rejected() {
+        assert!(Font::from_psf(&psf2(16, 16, 9)).is_err());
+        assert!(Font::from_psf(&psf2(32, 16, 9)).is_ok());
+    }
+}
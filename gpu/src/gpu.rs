@@ -1,102 +1,610 @@
+use crate::config::{self, GpuConfig};
+use crate::font::{self, Font};
 use crate::opcodes;
+use crate::tiles;
+use crate::status::{self, Status};
 use std::{fs::OpenOptions, os::unix::fs::FileExt};
 use std::io::Read;
 use std::env;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
 
-static mut FONT_SIZE: f32 = 16.0 * 1.0;
+/// Number of lines the scrollback buffer keeps before dropping the oldest ones
+pub const SCROLLBACK_LINES: usize = 1000;
+
+/// Start of the bitmap in the memory. Each word holds four pixels, one palette index per nibble
+/// with the leftmost pixel in the highest nibble.
+pub const BITMAP_BASE: u16 = 0xF000;
+/// Width of the bitmap in pixels
+pub const BITMAP_WIDTH: usize = 160;
+/// Height of the bitmap in pixels
+pub const BITMAP_HEIGHT: usize = 90;
+/// Size of the bitmap in words
+pub const BITMAP_SIZE: usize = BITMAP_WIDTH * BITMAP_HEIGHT / 4;
 
 #[derive(Debug)]
 pub struct GPU {
     pub buf_ptr: u16,
     pub memory: Vec<u16>,
-    pub frame_buffer: [[Character; 45]; 136],
+    pub config: GpuConfig,
+    pub frame_buffer: Vec<Vec<Character>>,
+    pub scrollback: VecDeque<Vec<Character>>,
+    pub scrollback_view: usize,
+    pub scroll_region: (usize, usize),
+    pub video_mode: VideoModes,
+    pub bitmap: Vec<u16>,
+    pub status: Arc<Status>,
+    pub collisions: u16,
+    pub busy: bool,
     pub cursor: Cursor,
     pub cursor_visible: bool,
     pub draw_mode: bool,
     pub draw_color: macroquad::color::Color,
+    pub palette: [macroquad::color::Color; 16],
+    pub attributes: u8,
+    pub blink_visible: bool,
+    pub font: Font,
+    pub glyph_atlas: Option<macroquad::texture::Texture2D>,
     pub clock_speed: usize,
     pub pri_counter: usize,
     pub sec_counter: usize,
-    pub int_flag: bool,
     pub fullscreen: bool,
 }
 
 
 impl GPU {
-    pub fn init() -> Self {
-        #[cfg(not(target_os = "windows"))]
-        let mut img = OpenOptions::new()
-            .read(true)
-            .open(format!("{}/../ROM.bin", env!("CARGO_MANIFEST_DIR")))
-            .expect("Memory image missing");
-
-        #[cfg(target_os = "windows")]
-        let mut img = OpenOptions::new()
-            .read(true)
-            .open(format!("{}\\..\\ROM.bin", env!("CARGO_MANIFEST_DIR")))
-            .expect("Memory image missing");
+    pub fn init(status: Arc<Status>, font: Font, config: GpuConfig) -> Self {
+        let mut img = Self::open_image(false);
 
         let mut buffer = Vec::new();
-        let memory;
 
         img.read_to_end(&mut buffer).unwrap();
 
-        memory = buffer.chunks_exact(2)
+        let memory = buffer.chunks_exact(2)
             .map(|chunk| u16::from_be_bytes([chunk[0], chunk[1]]))
             .collect();
 
         Self {
-            buf_ptr: 0x0300, // 0x0300 - 0x0FFF => 768 - 4096, so 3328 16-bit addresses
+            buf_ptr: status::RING_START, // 0x0300 - 0x0FFF => 768 - 4096, so 3328 16-bit addresses
             memory,
-            frame_buffer: [[Character::new(' '); 45]; 136],
+            frame_buffer: vec![vec![Character::new(' '); config.rows]; config.columns],
+            scrollback: VecDeque::new(),
+            scrollback_view: 0,
+            scroll_region: (0, config.rows - 1),
+            config,
+            video_mode: VideoModes::Text,
+            bitmap: vec![0; BITMAP_SIZE],
+            status,
+            collisions: 0,
+            busy: false,
             cursor: Cursor::new(CursorShapes::Underline),
             cursor_visible: false,
             draw_mode: false,
             draw_color: macroquad::color::WHITE,
+            palette: std::array::from_fn(|index| CharColors::from_u8(index as u8).unwrap().to_color()),
+            attributes: 0,
+            blink_visible: true,
+            font,
+            glyph_atlas: None,
             clock_speed: 10_000, // In Hz
             pri_counter: 0,
             sec_counter: 0,
-            int_flag: false,
             fullscreen: false,
         }
     }
 
     pub fn increase_buf_ptr(&mut self) {
-        if self.buf_ptr + 1 > 0x0FFF {
-            self.buf_ptr = 0x0300;
-        } else {
-            self.buf_ptr += 1;
+        self.buf_ptr = status::next_in_ring(self.buf_ptr);
+        self.status.ring_tail.store(self.buf_ptr, Ordering::Release);
+    }
+
+    /// Opens the memory image, optionally for writing as well
+    fn open_image(write: bool) -> std::fs::File {
+        #[cfg(not(target_os = "windows"))]
+        let img = OpenOptions::new()
+            .read(true)
+            .write(write)
+            .open(format!("{}/../ROM.bin", env!("CARGO_MANIFEST_DIR")))
+            .expect("Memory image missing");
+
+        #[cfg(target_os = "windows")]
+        let img = OpenOptions::new()
+            .read(true)
+            .write(write)
+            .open(format!("{}\\..\\ROM.bin", env!("CARGO_MANIFEST_DIR")))
+            .expect("Memory image missing");
+
+        img
+    }
+
+    /// Reads the word at the given address of the memory image
+    pub fn read_at(&self, address: u16) -> u16 {
+        self.read_words(address, 1)[0]
+    }
+
+    /// Reads a number of consecutive words starting at the given address of the memory image
+    pub fn read_words(&self, address: u16, count: usize) -> Vec<u16> {
+        let mut buffer = vec![0u8; count * 2];
+
+        Self::open_image(false).read_at(&mut buffer, address as u64 * 2).unwrap();
+
+        buffer.chunks_exact(2)
+            .map(|chunk| u16::from_be_bytes([chunk[0], chunk[1]]))
+            .collect()
+    }
+
+    /// Writes consecutive words to the memory image, starting at the given address
+    pub fn write_words(&self, address: u16, words: &[u16]) {
+        let buffer: Vec<u8> = words.iter().flat_map(|word| word.to_be_bytes()).collect();
+
+        _ = Self::open_image(true).write_at(&buffer, address as u64 * 2);
+    }
+
+    /// Reloads the bitmap from the memory, picking up changes the CPU made to it
+    pub fn load_bitmap(&mut self) {
+        self.bitmap = self.read_words(BITMAP_BASE, BITMAP_SIZE);
+    }
+
+    /// Writes the bitmap back to the memory
+    pub fn store_bitmap(&self) {
+        self.write_words(BITMAP_BASE, &self.bitmap);
+    }
+
+    /// Returns the palette index of a pixel in the bitmap
+    pub fn get_pixel(&self, x: usize, y: usize) -> u8 {
+        let pixel = y * BITMAP_WIDTH + x;
+        (self.bitmap[pixel / 4] >> (12 - (pixel % 4) * 4)) as u8 & 0x0F
+    }
+
+    /// Sets a pixel in the bitmap. Pixels outside of the bitmap are ignored.
+    pub fn set_pixel(&mut self, x: i32, y: i32, color: u8) {
+        if x < 0 || y < 0 || x >= BITMAP_WIDTH as i32 || y >= BITMAP_HEIGHT as i32 {
+            return;
         }
+        let pixel = y as usize * BITMAP_WIDTH + x as usize;
+        let shift = 12 - (pixel % 4) * 4;
+        let word = &mut self.bitmap[pixel / 4];
+        *word = (*word & !(0x0F << shift)) | (((color & 0x0F) as u16) << shift);
     }
 
-    pub async fn draw_framebuffer(&mut self) {
-        if self.cursor_visible {
-            let cursor;
-            match self.cursor.shape {
-                CursorShapes::Underline => cursor = "_",
-                CursorShapes::Block => cursor = "█",
-                CursorShapes::VertiBar => cursor = "|"
+    /// Draws a line between two points of the bitmap
+    pub fn draw_line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, color: u8) {
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let step_x = if x0 < x1 { 1 } else { -1 };
+        let step_y = if y0 < y1 { 1 } else { -1 };
+        let mut error = dx + dy;
+        let (mut x, mut y) = (x0, y0);
+
+        loop {
+            self.set_pixel(x, y, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    /// Fills a rectangle of the bitmap with a color. Only the part inside the bitmap is visited.
+    pub fn fill_rect(&mut self, x: i32, y: i32, width: i32, height: i32, color: u8) {
+        let columns = x.max(0)..x.saturating_add(width).min(BITMAP_WIDTH as i32);
+        let rows = y.max(0)..y.saturating_add(height).min(BITMAP_HEIGHT as i32);
+        for row in rows {
+            for column in columns.clone() {
+                self.set_pixel(column, row, color);
+            }
+        }
+    }
+
+    /// Copies an image from the memory into the bitmap. The image uses the bitmap's format with
+    /// every row starting at a new word. Pixels with palette index 0 are transparent. At most as
+    /// many rows and columns as the bitmap has are drawn, and the image ends at the end of the
+    /// memory instead of wrapping around to its start.
+    pub fn blit(&mut self, source: u16, x: i32, y: i32, width: usize, height: usize) {
+        let row_words = width.div_ceil(4);
+        let (width, height) = (width.min(BITMAP_WIDTH), height.min(BITMAP_HEIGHT));
+        let image = self.read_words(source, image_len(source, row_words, height));
+        self.draw_image(&image, row_words, x, y, width, height);
+    }
+
+    /// Draws the words of an image read by ```blit```. Rows past the end of the words are left out.
+    fn draw_image(&mut self, image: &[u16], row_words: usize, x: i32, y: i32, width: usize, height: usize) {
+        for row in 0..height {
+            for column in 0..width {
+                let Some(&word) = image.get(row * row_words + column / 4) else { return };
+                let color = (word >> (12 - (column % 4) * 4)) as u8 & 0x0F;
+                if color != 0 {
+                    self.set_pixel(x + column as i32, y + row as i32, color);
+                }
+            }
+        }
+    }
+
+    /// Moves the buf_ptr to the operand following the current instruction and reads it
+    pub fn read_operand(&mut self) -> u16 {
+        self.increase_buf_ptr();
+        self.read_at(self.buf_ptr)
+    }
+
+    /// Writes a character to the cursor position and moves the cursor one collumn to the right
+    pub fn put_char(&mut self, char: Character) {
+        self.frame_buffer[self.cursor.position.0][self.cursor.position.1] = char;
+        self.move_cursor(CursorMoves::Right);
+    }
+
+    /// Moves the cursor by one cell. Horizontal movement wraps around to the neighbouring line.
+    /// Moving down from the bottom of the scroll region scrolls it up, moving up is clamped.
+    pub fn move_cursor(&mut self, direction: CursorMoves) {
+        let columns = self.frame_buffer.len();
+        let rows = self.frame_buffer[0].len();
+        let (x, y) = self.cursor.position;
+
+        match direction {
+            CursorMoves::Up => self.cursor.position.1 = y.saturating_sub(1),
+            CursorMoves::Down => {
+                if y == self.scroll_region.1 {
+                    self.scroll_up(1);
+                } else if y < rows - 1 {
+                    self.cursor.position.1 += 1;
+                }
+            }
+            CursorMoves::Left => {
+                if x > 0 {
+                    self.cursor.position.0 -= 1;
+                } else if y > 0 {
+                    self.cursor.position = (columns - 1, y - 1);
+                }
+            }
+            CursorMoves::Right => {
+                if x < columns - 1 {
+                    self.cursor.position.0 += 1;
+                } else {
+                    self.cursor.position.0 = 0;
+                    self.move_cursor(CursorMoves::Down);
+                }
+            }
+        }
+    }
+
+    /// Shifts the lines of the scroll region up, clearing the bottom lines. Lines leaving the
+    /// top of the screen are kept in the scrollback buffer.
+    pub fn scroll_up(&mut self, lines: usize) {
+        let (top, bottom) = self.scroll_region;
+        for _ in 0..lines.min(bottom - top + 1) {
+            if top == 0 {
+                self.scrollback.push_back(self.frame_buffer.iter().map(|column| column[0]).collect());
+                if self.scrollback.len() > SCROLLBACK_LINES {
+                    self.scrollback.pop_front();
+                }
+            }
+            for column in self.frame_buffer.iter_mut() {
+                column[top..=bottom].rotate_left(1);
+                column[bottom] = Character::new(' ');
             }
-            macroquad::text::draw_text(
-                cursor,
-                (self.cursor.position.0 as f32 * 7.0 + 2.0) * unsafe { crate::SCALING },
-                (self.cursor.position.1 as f32 * 12.0 + 10.0) * unsafe { crate::SCALING },
-                unsafe { FONT_SIZE },
-                self.draw_color
-            );
         }
-        for y in 0..self.frame_buffer[0].len() {
-            for x in 0..self.frame_buffer.len() {
-                macroquad::text::draw_text(
-                    &format!("{}", self.frame_buffer[x][y].literal) as &str,
-                    (x as f32 * 7.0 + 2.0) * unsafe { crate::SCALING },
-                    (y as f32 * 12.0 + 10.0) * unsafe { crate::SCALING },
-                    unsafe { FONT_SIZE },
-                    self.frame_buffer[x][y].fg_color,
-                );
+    }
+
+    /// Shifts the lines of the scroll region down, clearing the top lines
+    pub fn scroll_down(&mut self, lines: usize) {
+        let (top, bottom) = self.scroll_region;
+        for _ in 0..lines.min(bottom - top + 1) {
+            for column in self.frame_buffer.iter_mut() {
+                column[top..=bottom].rotate_right(1);
+                column[top] = Character::new(' ');
             }
         }
+    }
+
+    /// Sets the lines between which scrolling happens. Invalid regions are ignored.
+    pub fn set_scroll_region(&mut self, top: usize, bottom: usize) {
+        let bottom = bottom.min(self.frame_buffer[0].len() - 1);
+        if top <= bottom {
+            self.scroll_region = (top, bottom);
+        }
+    }
+
+    /// Switches to a new text screen configuration. Values of 0 keep the current setting.
+    /// Resizes the window and clears the frame buffer and the scrollback buffer.
+    pub fn set_text_mode(&mut self, columns: u16, rows: u16, cell_width: u16, cell_height: u16, scale: u16) {
+        let keep = |value: u16, current: usize| if value == 0 { current } else { value as usize };
+        self.config = GpuConfig {
+            columns: keep(columns, self.config.columns),
+            rows: keep(rows, self.config.rows),
+            cell_width: keep(cell_width, self.config.cell_width),
+            cell_height: keep(cell_height, self.config.cell_height),
+            scale: keep(scale, self.config.scale),
+            font_path: self.config.font_path.take(),
+        }
+        .clamped();
+
+        self.frame_buffer = vec![vec![Character::new(' '); self.config.rows]; self.config.columns];
+        self.scrollback.clear();
+        self.scrollback_view = 0;
+        self.scroll_region = (0, self.config.rows - 1);
+        self.cursor.position = (0, 0);
+
+        let (width, height) = self.config.window_size();
+        macroquad::window::request_new_screen_size(width as f32, height as f32);
+    }
+
+    /// Returns the factor every pixel is scaled up by. It's the configured one in a window and
+    /// the largest one that fits the screen in fullscreen.
+    fn scale(&self) -> f32 {
+        let (width, height) = self.config.screen_size();
+        (macroquad::window::screen_width() / width as f32)
+            .min(macroquad::window::screen_height() / height as f32)
+            .floor()
+            .max(1.0)
+    }
+
+    /// Returns the character shown at a screen position, taking the scrollback view into account
+    pub fn visible_char(&self, x: usize, y: usize) -> Character {
+        let line = self.scrollback.len() - self.scrollback_view + y;
+        match self.scrollback.get(line) {
+            Some(row) => row[x],
+            None => self.frame_buffer[x][line - self.scrollback.len()],
+        }
+    }
+
+    /// Sets the cursor to an absolute position, clamped to the frame buffer
+    pub fn set_cursor(&mut self, x: usize, y: usize) {
+        self.cursor.position = (
+            x.min(self.frame_buffer.len() - 1),
+            y.min(self.frame_buffer[0].len() - 1),
+        );
+    }
+
+    /// Creates a character with the current attributes. The color byte holds the background
+    /// palette index in its high and the foreground palette index in its low nibble. A color
+    /// byte of 0x00 would be black on black, so it falls back to the default white on black.
+    pub fn styled_char(&self, literal: char, color_byte: u8) -> Character {
+        let mut char = Character::new(literal);
+        if color_byte != 0x00 {
+            char.fg_color = color_byte & 0x0F;
+            char.bg_color = color_byte >> 4;
+        }
+        char.attributes = self.attributes;
+        char
+    }
+
+    /// Sets a palette entry to a color given in RGB565 format
+    pub fn set_palette(&mut self, index: u16, rgb565: u16) {
+        let red = ((rgb565 >> 11) & 0x1F) as f32 / 31.0;
+        let green = ((rgb565 >> 5) & 0x3F) as f32 / 63.0;
+        let blue = (rgb565 & 0x1F) as f32 / 31.0;
+        self.palette[index as usize & 0x0F] = macroquad::color::Color::new(red, green, blue, 1.0);
+    }
+
+    /// Draws a number at the cursor position. The format word holds the color byte in its high
+    /// byte and the base (2, 10 or 16) in its low byte. Any other base falls back to decimal.
+    pub fn draw_value(&mut self, format: u16, value: u16) {
+        let digits = match format as u8 {
+            2 => format!("{:b}", value),
+            16 => format!("{:X}", value),
+            _ => format!("{}", value),
+        };
+
+        for digit in digits.chars() {
+            let char = self.styled_char(digit, (format >> 8) as u8);
+            self.put_char(char);
+        }
+    }
+
+    pub async fn draw_framebuffer(&mut self) {
+        match self.video_mode {
+            VideoModes::Text => self.draw_text(),
+            VideoModes::Bitmap => self.draw_bitmap(),
+            VideoModes::Tiles => self.draw_tiles(),
+        }
         macroquad::window::next_frame().await;
+        self.publish_status();
+    }
+
+    /// Updates the status shared with the CPU after a frame was drawn and raises the vblank
+    /// interrupt
+    fn publish_status(&self) {
+        let mut flags = ((self.video_mode as u16) << 8) | status::STATUS_VBLANK;
+        if self.collisions != 0 {
+            flags |= status::STATUS_COLLISION;
+        }
+        if self.busy {
+            flags |= status::STATUS_BUSY;
+        }
+
+        self.status.flags.store(flags, Ordering::Relaxed);
+        self.status.collisions.store(self.collisions, Ordering::Relaxed);
+        self.status.frame_counter.fetch_add(1, Ordering::Relaxed);
+        self.status.cursor_x.store(self.cursor.position.0 as u16, Ordering::Relaxed);
+        self.status.cursor_y.store(self.cursor.position.1 as u16, Ordering::Relaxed);
+        self.status.vblank.store(true, Ordering::Release);
+    }
+
+    /// Draws the bitmap
+    fn draw_bitmap(&mut self) {
+        self.load_bitmap();
+
+        let pixels: Vec<u8> = (0..BITMAP_WIDTH * BITMAP_HEIGHT)
+            .map(|pixel| self.get_pixel(pixel % BITMAP_WIDTH, pixel / BITMAP_WIDTH))
+            .collect();
+        self.draw_pixels(&pixels);
+    }
+
+    /// Draws the tile map and the sprites and remembers which sprites collided
+    fn draw_tiles(&mut self) {
+        let patterns = self.read_words(tiles::TILE_PATTERNS, tiles::TILE_COUNT * tiles::TILE_WORDS);
+        let map = self.read_words(tiles::TILE_MAP, tiles::TILE_MAP_WIDTH * tiles::TILE_MAP_HEIGHT);
+        let sprites = self.read_words(tiles::SPRITE_TABLE, tiles::SPRITE_COUNT * tiles::SPRITE_WORDS);
+
+        let (pixels, collisions) = tiles::compose(&patterns, &map, &sprites);
+        self.collisions = collisions;
+
+        self.draw_pixels(&pixels);
+    }
+
+    /// Draws a picture of palette indices the size of the bitmap, scaled up to fill the screen
+    fn draw_pixels(&self, pixels: &[u8]) {
+        let mut image = macroquad::texture::Image::gen_image_color(
+            BITMAP_WIDTH as u16,
+            BITMAP_HEIGHT as u16,
+            macroquad::color::BLACK,
+        );
+        for (pixel, color) in pixels.iter().enumerate() {
+            image.set_pixel(
+                (pixel % BITMAP_WIDTH) as u32,
+                (pixel / BITMAP_WIDTH) as u32,
+                self.palette[*color as usize],
+            );
+        }
+
+        let texture = macroquad::texture::Texture2D::from_image(&image);
+        texture.set_filter(macroquad::texture::FilterMode::Nearest);
+
+        let (width, height) = self.config.screen_size();
+        let pixel_size = (width as f32 / BITMAP_WIDTH as f32).min(height as f32 / BITMAP_HEIGHT as f32)
+            * self.scale();
+        macroquad::texture::draw_texture_ex(
+            &texture,
+            0.0,
+            0.0,
+            macroquad::color::WHITE,
+            macroquad::texture::DrawTextureParams {
+                dest_size: Some(macroquad::math::vec2(
+                    BITMAP_WIDTH as f32 * pixel_size,
+                    BITMAP_HEIGHT as f32 * pixel_size,
+                )),
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Replaces glyphs starting at the given character byte and drops the glyph atlas so it gets
+    /// rebuilt with the new glyphs
+    pub fn define_glyphs(&mut self, first: u16, words: &[u16]) {
+        for (index, glyph) in words.chunks_exact(font::GLYPH_WORDS).enumerate() {
+            self.font.set_glyph((first as usize + index) as u8, glyph);
+        }
+        self.glyph_atlas = None;
+    }
+
+    /// Returns a texture with all glyphs next to each other, white on transparent so they can be
+    /// tinted with any color
+    fn glyph_atlas(&mut self) -> macroquad::texture::Texture2D {
+        if let Some(atlas) = &self.glyph_atlas {
+            return atlas.clone();
+        }
+
+        let mut image = macroquad::texture::Image::gen_image_color(
+            (font::GLYPH_COUNT * font::GLYPH_WIDTH) as u16,
+            font::GLYPH_HEIGHT as u16,
+            macroquad::color::BLANK,
+        );
+        for (index, glyph) in self.font.glyphs.iter().enumerate() {
+            for (y, row) in glyph.iter().enumerate() {
+                for x in 0..font::GLYPH_WIDTH {
+                    if row & (0x80 >> x) != 0 {
+                        image.set_pixel(
+                            (index * font::GLYPH_WIDTH + x) as u32,
+                            y as u32,
+                            macroquad::color::WHITE,
+                        );
+                    }
+                }
+            }
+        }
+
+        let atlas = macroquad::texture::Texture2D::from_image(&image);
+        atlas.set_filter(macroquad::texture::FilterMode::Nearest);
+        self.glyph_atlas = Some(atlas.clone());
+        atlas
+    }
+
+    /// Draws the frame buffer as text. Glyphs are cut off or padded to the cell size.
+    fn draw_text(&mut self) {
+        let atlas = self.glyph_atlas();
+        let scale = self.scale();
+        let margin = config::TEXT_MARGIN as f32;
+        let cell_width = self.config.cell_width as f32;
+        let cell_height = self.config.cell_height as f32;
+        let glyph_width = cell_width.min(font::GLYPH_WIDTH as f32);
+        let glyph_height = cell_height.min(font::GLYPH_HEIGHT as f32);
+        let draw_glyph = |literal: char, x: f32, y: f32, color: macroquad::color::Color| {
+            let index = u8::try_from(literal as u32).unwrap_or(b'?');
+            macroquad::texture::draw_texture_ex(
+                &atlas,
+                x * scale,
+                y * scale,
+                color,
+                macroquad::texture::DrawTextureParams {
+                    dest_size: Some(macroquad::math::vec2(glyph_width * scale, glyph_height * scale)),
+                    source: Some(macroquad::math::Rect::new(
+                        (index as usize * font::GLYPH_WIDTH) as f32,
+                        0.0,
+                        glyph_width,
+                        glyph_height,
+                    )),
+                    ..Default::default()
+                },
+            );
+        };
+
+        for y in 0..self.config.rows {
+            for x in 0..self.config.columns {
+                let char = self.visible_char(x, y);
+                let mut fg_color = self.palette[char.fg_color as usize];
+                let mut bg_color = self.palette[char.bg_color as usize];
+                if char.attributes & ATTR_INVERSE != 0 {
+                    std::mem::swap(&mut fg_color, &mut bg_color);
+                }
+
+                let (cell_x, cell_y) = (x as f32 * cell_width + margin, y as f32 * cell_height + margin);
+                if bg_color != macroquad::color::BLACK {
+                    macroquad::shapes::draw_rectangle(
+                        cell_x * scale,
+                        cell_y * scale,
+                        cell_width * scale,
+                        cell_height * scale,
+                        bg_color,
+                    );
+                }
+
+                if char.attributes & ATTR_BLINK != 0 && !self.blink_visible {
+                    continue;
+                }
+
+                draw_glyph(char.literal, cell_x, cell_y, fg_color);
+                if char.attributes & ATTR_BOLD != 0 {
+                    draw_glyph(char.literal, cell_x + 1.0, cell_y, fg_color);
+                }
+            }
+        }
+
+        if self.cursor_visible && self.scrollback_view == 0 {
+            let (x, y) = (
+                self.cursor.position.0 as f32 * cell_width + margin,
+                self.cursor.position.1 as f32 * cell_height + margin,
+            );
+            let (x, y, width, height) = match self.cursor.shape {
+                CursorShapes::Underline => (x, y + cell_height - 2.0, cell_width, 1.0),
+                CursorShapes::Block => (x, y, cell_width, cell_height),
+                CursorShapes::VertiBar => (x, y, 1.0, cell_height),
+            };
+            macroquad::shapes::draw_rectangle(
+                x * scale,
+                y * scale,
+                width * scale,
+                height * scale,
+                self.draw_color,
+            );
+        }
     }
 
     pub async fn update(&mut self) {
@@ -111,45 +619,38 @@ impl GPU {
                 std::process::exit(0);
             }
             if macroquad::input::is_key_pressed(macroquad::input::KeyCode::F11) {
-                if self.fullscreen {
-                    unsafe { crate::SCALING = 1.0 }
-                    unsafe { FONT_SIZE /= 2.0 };
-                } else {
-                    unsafe { crate::SCALING = 2.0 }
-                    unsafe { FONT_SIZE *= 2.0 };
-                }
                 self.fullscreen = !self.fullscreen;
                 macroquad::window::set_fullscreen(self.fullscreen);
             }
+            if macroquad::input::is_key_pressed(macroquad::input::KeyCode::PageUp) {
+                self.scrollback_view = (self.scrollback_view + 10).min(self.scrollback.len());
+            }
+            if macroquad::input::is_key_pressed(macroquad::input::KeyCode::PageDown) {
+                self.scrollback_view = self.scrollback_view.saturating_sub(10);
+            }
             self.draw_framebuffer().await;
             self.sec_counter += 1;
             if self.sec_counter == 10 {
                 self.cursor_visible = !self.cursor_visible;
+                self.blink_visible = !self.blink_visible;
                 self.sec_counter = 0;
             }
             self.pri_counter = 0;
         }
 
-        #[cfg(not(target_os = "windows"))]
-        let img = OpenOptions::new()
-            .read(true)
-            .open(format!("{}/../ROM.bin", env!("CARGO_MANIFEST_DIR")))
-            .expect("Memory image missing");
-
-        #[cfg(target_os = "windows")]
-        let img = OpenOptions::new()
-            .read(true)
-            .open(format!("{}\\..\\ROM.bin", env!("CARGO_MANIFEST_DIR")))
-            .expect("Memory image missing");
-
-        let mut buffer = [0u8; 2];
-
-        img.read_at(&mut buffer, self.buf_ptr as u64 * 16).unwrap();
+        // --- Wait until the CPU hands over new commands ---
+        self.busy = self.buf_ptr != self.status.doorbell.load(Ordering::Acquire);
+        if !self.busy {
+            std::thread::sleep(std::time::Duration::from_micros(
+                1_000_000 / self.clock_speed as u64,
+            ));
+            return;
+        }
 
-        let instruction = u16::from_be_bytes(buffer);
+        let instruction = self.read_at(self.buf_ptr);
 
         // --- Handle GPU Instructions ---
-        if self.draw_mode == true {
+        if self.draw_mode {
             #[cfg(debug_assertions)]
             crate::debug!(
                 "Appending letter to framebuffer: ",
@@ -158,10 +659,6 @@ impl GPU {
 
             let instr = instruction as u8;
 
-            if self.int_flag {
-
-            }
-
             // --- Handle chars ---
             match char::from(instr) {
                 '`' => {
@@ -171,10 +668,8 @@ impl GPU {
                     self.increase_buf_ptr();
                 }
                 _ => match instruction {
-                    0xA000 => {},
+                    0xA000 => self.increase_buf_ptr(),
                     0x00..=0xFF7A => {
-                        let mut char = Character::new(char::from(instruction as u8));
-
                         let color_byte = (instruction >> 8) as u8;
                         let char_byte = (instruction & 0xFF) as u8;
 
@@ -184,29 +679,12 @@ impl GPU {
                             format!("Character Byte: {:#04X}", char_byte)
                         );
 
-                        match CharColors::from_u8(color_byte).unwrap() {
-                            CharColors::White => char.fg_color = macroquad::color::WHITE,
-                            CharColors::Red => char.fg_color = macroquad::color::RED,
-                            CharColors::Green => char.fg_color = macroquad::color::GREEN,
-                            CharColors::Blue => char.fg_color = macroquad::color::BLUE,
-                            CharColors::Cyan => char.fg_color = macroquad::color::Color::new(0.5,0.9,1.0,1.0),
-                            CharColors::Magenta => char.fg_color = macroquad::color::MAGENTA,
-                        }
-
-                        self.frame_buffer[self.cursor.position.0][self.cursor.position.1] = char;
-                        if self.cursor.position.0 < self.frame_buffer.len() - 1 {
-                            self.cursor.position.0 += 1;
-                        } else {
-                            self.cursor.position.0 = 0;
-                            if self.cursor.position.1 < self.frame_buffer[0].len() - 1 {
-                                self.cursor.position.1 += 1;
-                            } else {
-                                self.cursor.position.1 = 0;
-                            }
-                        }
+                        let char = self.styled_char(char::from(char_byte), color_byte);
+
+                        self.put_char(char);
                         self.increase_buf_ptr();
                     }
-                    _ => {}
+                    _ => self.increase_buf_ptr(),
                 },
             }
         } else {
@@ -216,6 +694,7 @@ impl GPU {
                     #[cfg(debug_assertions)]
                     crate::debug!("NoOp");
                     */
+                    self.increase_buf_ptr();
                 }
                 opcodes::GPU_DRAW_LETT => {
                     #[cfg(debug_assertions)]
@@ -223,17 +702,32 @@ impl GPU {
                     self.increase_buf_ptr();
                     self.draw_mode = true;
                 }
+                opcodes::GPU_DRAW_CHAR => {
+                    let value = self.read_operand();
+                    #[cfg(debug_assertions)]
+                    crate::debug!("Drawing character: ", crate::hex!(value));
+                    let char = self.styled_char(char::from(value as u8), (value >> 8) as u8);
+                    self.put_char(char);
+                    self.increase_buf_ptr();
+                }
                 opcodes::GPU_DRAW_VALU => {
+                    let format = self.read_operand();
+                    let value = self.read_operand();
                     #[cfg(debug_assertions)]
-                    crate::debug!("Entering draw mode");
+                    crate::debug!(
+                        "Drawing value: ",
+                        format!("{:#06X} | Format: {:#06X}", value, format)
+                    );
+                    self.draw_value(format, value);
                     self.increase_buf_ptr();
-                    self.draw_mode = true;
-                    self.int_flag = true;
                 }
                 opcodes::GPU_RESET_PTR => {
                     #[cfg(debug_assertions)]
-                    crate::debug!("Resetting buffer pointer");
-                    self.buf_ptr = 0x0300;
+                    crate::debug!("Discarding the commands handed over so far");
+                    // The head belongs to the CPU, so commands it pushed since ringing the doorbell
+                    // are kept
+                    self.buf_ptr = self.status.doorbell.load(Ordering::Acquire);
+                    self.status.ring_tail.store(self.buf_ptr, Ordering::Release);
                 }
                 opcodes::GPU_UPDATE => {
                     #[cfg(debug_assertions)]
@@ -245,29 +739,231 @@ impl GPU {
                 opcodes::GPU_RES_F_BUF => {
                     #[cfg(debug_assertions)]
                     crate::debug!("Clearing frame buffer");
-                    for y in 0..45 {
-                        for x in 0..136 {
-                            self.frame_buffer[x][y].literal = ' ';
-                            self.frame_buffer[x][y].fg_color = macroquad::color::BLACK;
-                        }
-                    }
+                    self.frame_buffer = vec![vec![Character::new(' '); self.config.rows]; self.config.columns];
                     self.cursor.position = (0,0);
                     self.increase_buf_ptr();
                 }
+                opcodes::GPU_MV_C_UP => {
+                    #[cfg(debug_assertions)]
+                    crate::debug!("Moving cursor up");
+                    self.move_cursor(CursorMoves::Up);
+                    self.increase_buf_ptr();
+                }
                 opcodes::GPU_MV_C_DOWN => {
                     #[cfg(debug_assertions)]
                     crate::debug!("Moving cursor down");
-                    self.cursor.position.1 += 1;
+                    self.move_cursor(CursorMoves::Down);
+                    self.increase_buf_ptr();
+                }
+                opcodes::GPU_MV_C_LEFT => {
+                    #[cfg(debug_assertions)]
+                    crate::debug!("Moving cursor left");
+                    self.move_cursor(CursorMoves::Left);
+                    self.increase_buf_ptr();
+                }
+                opcodes::GPU_MV_C_RIGH => {
+                    #[cfg(debug_assertions)]
+                    crate::debug!("Moving cursor right");
+                    self.move_cursor(CursorMoves::Right);
                     self.increase_buf_ptr();
                 }
                 opcodes::GPU_NEW_LINE => {
                     #[cfg(debug_assertions)]
                     crate::debug!("Inserting new line");
-                    self.cursor.position.1 += 1;
+                    self.move_cursor(CursorMoves::Down);
                     self.cursor.position.0 = 0;
                     self.increase_buf_ptr();
                 }
-                _ => {}
+                opcodes::GPU_SET_ATTR => {
+                    let attributes = self.read_operand();
+                    #[cfg(debug_assertions)]
+                    crate::debug!("Setting character attributes: ", crate::hex!(attributes));
+                    self.attributes = attributes as u8;
+                    self.increase_buf_ptr();
+                }
+                opcodes::GPU_SET_PALT => {
+                    let index = self.read_operand();
+                    let rgb565 = self.read_operand();
+                    #[cfg(debug_assertions)]
+                    crate::debug!(
+                        "Redefining palette entry: ",
+                        format!("{:#04X} | Color: {:#06X}", index, rgb565)
+                    );
+                    self.set_palette(index, rgb565);
+                    self.increase_buf_ptr();
+                }
+                opcodes::GPU_SET_SCRL_R => {
+                    let top = self.read_operand();
+                    let bottom = self.read_operand();
+                    #[cfg(debug_assertions)]
+                    crate::debug!("Setting scroll region: ", format!("{} - {}", top, bottom));
+                    self.set_scroll_region(top as usize, bottom as usize);
+                    self.increase_buf_ptr();
+                }
+                opcodes::GPU_SCROLL_UP => {
+                    let lines = self.read_operand();
+                    #[cfg(debug_assertions)]
+                    crate::debug!("Scrolling up: ", lines);
+                    self.scroll_up(lines as usize);
+                    self.increase_buf_ptr();
+                }
+                opcodes::GPU_SCROLL_DN => {
+                    let lines = self.read_operand();
+                    #[cfg(debug_assertions)]
+                    crate::debug!("Scrolling down: ", lines);
+                    self.scroll_down(lines as usize);
+                    self.increase_buf_ptr();
+                }
+                opcodes::GPU_SET_MODE => {
+                    let mode = self.read_operand();
+                    #[cfg(debug_assertions)]
+                    crate::debug!("Switching video mode: ", mode);
+                    self.video_mode = match mode {
+                        1 => VideoModes::Bitmap,
+                        2 => VideoModes::Tiles,
+                        _ => VideoModes::Text,
+                    };
+                    self.increase_buf_ptr();
+                }
+                opcodes::GPU_SET_TEXT => {
+                    let columns = self.read_operand();
+                    let rows = self.read_operand();
+                    let cell_width = self.read_operand();
+                    let cell_height = self.read_operand();
+                    let scale = self.read_operand();
+                    #[cfg(debug_assertions)]
+                    crate::debug!(
+                        "Switching text mode: ",
+                        format!("{}x{} | Cell: {}x{} | Scale: {}", columns, rows, cell_width, cell_height, scale)
+                    );
+                    self.set_text_mode(columns, rows, cell_width, cell_height, scale);
+                    self.increase_buf_ptr();
+                }
+                opcodes::GPU_SET_PIXL => {
+                    let x = self.read_operand();
+                    let y = self.read_operand();
+                    let color = self.read_operand();
+                    #[cfg(debug_assertions)]
+                    crate::debug!("Setting pixel: ", format!("({}, {}) | Color: {}", x, y, color));
+                    self.load_bitmap();
+                    self.set_pixel(x as i32, y as i32, color as u8);
+                    self.store_bitmap();
+                    self.increase_buf_ptr();
+                }
+                opcodes::GPU_DRAW_LINE => {
+                    let x0 = self.read_operand();
+                    let y0 = self.read_operand();
+                    let x1 = self.read_operand();
+                    let y1 = self.read_operand();
+                    let color = self.read_operand();
+                    #[cfg(debug_assertions)]
+                    crate::debug!(
+                        "Drawing line: ",
+                        format!("({}, {}) - ({}, {}) | Color: {}", x0, y0, x1, y1, color)
+                    );
+                    self.load_bitmap();
+                    self.draw_line(x0 as i32, y0 as i32, x1 as i32, y1 as i32, color as u8);
+                    self.store_bitmap();
+                    self.increase_buf_ptr();
+                }
+                opcodes::GPU_FILL_RECT => {
+                    let x = self.read_operand();
+                    let y = self.read_operand();
+                    let width = self.read_operand();
+                    let height = self.read_operand();
+                    let color = self.read_operand();
+                    #[cfg(debug_assertions)]
+                    crate::debug!(
+                        "Filling rectangle: ",
+                        format!("({}, {}) {}x{} | Color: {}", x, y, width, height, color)
+                    );
+                    self.load_bitmap();
+                    self.fill_rect(x as i32, y as i32, width as i32, height as i32, color as u8);
+                    self.store_bitmap();
+                    self.increase_buf_ptr();
+                }
+                opcodes::GPU_BLIT_BMAP => {
+                    let source = self.read_operand();
+                    let x = self.read_operand();
+                    let y = self.read_operand();
+                    let width = self.read_operand();
+                    let height = self.read_operand();
+                    #[cfg(debug_assertions)]
+                    crate::debug!(
+                        "Blitting image: ",
+                        format!("{:#06X} -> ({}, {}) {}x{}", source, x, y, width, height)
+                    );
+                    self.load_bitmap();
+                    self.blit(source, x as i32, y as i32, width as usize, height as usize);
+                    self.store_bitmap();
+                    self.increase_buf_ptr();
+                }
+                opcodes::GPU_SET_SPRT => {
+                    let index = self.read_operand() as usize % tiles::SPRITE_COUNT;
+                    let x = self.read_operand();
+                    let y = self.read_operand();
+                    let tile = self.read_operand();
+                    let flags = self.read_operand();
+                    #[cfg(debug_assertions)]
+                    crate::debug!(
+                        "Setting sprite: ",
+                        format!("{} -> ({}, {}) | Tile: {} | Flags: {:#06X}", index, x, y, tile, flags)
+                    );
+                    self.write_words(
+                        tiles::SPRITE_TABLE + (index * tiles::SPRITE_WORDS) as u16,
+                        &[x, y, tile, flags],
+                    );
+                    self.increase_buf_ptr();
+                }
+                opcodes::GPU_SET_TILE => {
+                    let x = self.read_operand() as usize % tiles::TILE_MAP_WIDTH;
+                    let y = self.read_operand() as usize % tiles::TILE_MAP_HEIGHT;
+                    let tile = self.read_operand();
+                    #[cfg(debug_assertions)]
+                    crate::debug!("Setting tile: ", format!("({}, {}) -> {}", x, y, tile));
+                    self.write_words(tiles::TILE_MAP + (y * tiles::TILE_MAP_WIDTH + x) as u16, &[tile]);
+                    self.increase_buf_ptr();
+                }
+                opcodes::GPU_DEF_GLYPH => {
+                    let index = self.read_operand();
+                    let rows: Vec<u16> = (0..font::GLYPH_WORDS).map(|_| self.read_operand()).collect();
+                    #[cfg(debug_assertions)]
+                    crate::debug!("Defining glyph: ", crate::hex!(index));
+                    self.define_glyphs(index, &rows);
+                    self.increase_buf_ptr();
+                }
+                opcodes::GPU_LOAD_FONT => {
+                    let address = self.read_operand();
+                    let first = self.read_operand();
+                    let count = self.read_operand() as usize;
+                    #[cfg(debug_assertions)]
+                    crate::debug!(
+                        "Loading glyphs: ",
+                        format!("{:#06X} -> {:#04X} | Count: {}", address, first, count)
+                    );
+                    let words = self.read_words(address, count.min(font::GLYPH_COUNT) * font::GLYPH_WORDS);
+                    self.define_glyphs(first, &words);
+                    self.increase_buf_ptr();
+                }
+                opcodes::GPU_SET_C_X => {
+                    let x = self.read_operand();
+                    #[cfg(debug_assertions)]
+                    crate::debug!("Setting cursor collumn: ", x);
+                    self.set_cursor(x as usize, self.cursor.position.1);
+                    self.increase_buf_ptr();
+                }
+                opcodes::GPU_SET_C_Y => {
+                    let y = self.read_operand();
+                    #[cfg(debug_assertions)]
+                    crate::debug!("Setting cursor line: ", y);
+                    self.set_cursor(self.cursor.position.0, y as usize);
+                    self.increase_buf_ptr();
+                }
+                _ => {
+                    #[cfg(debug_assertions)]
+                    crate::debug!("Skipping unknown instruction: ", crate::hex!(instruction));
+                    self.increase_buf_ptr();
+                }
             }
         }
         std::thread::sleep(std::time::Duration::from_micros(
@@ -276,19 +972,29 @@ impl GPU {
     }
 }
 
+/// Character attribute: Draws the character in bold
+pub const ATTR_BOLD: u8 = 0b0001;
+/// Character attribute: Swaps the character's foreground and background colors
+pub const ATTR_INVERSE: u8 = 0b0010;
+/// Character attribute: Makes the character blink
+pub const ATTR_BLINK: u8 = 0b0100;
+
+/// A cell of the frame buffer. The colors are indices into the GPU's palette.
 #[derive(Debug, Clone, Copy)]
 pub struct Character {
     pub literal: char,
-    pub fg_color: macroquad::color::Color,
-    pub bg_color: macroquad::color::Color,
+    pub fg_color: u8,
+    pub bg_color: u8,
+    pub attributes: u8,
 }
 
 impl Character {
     pub fn new(char: char) -> Self {
         Self {
             literal: char,
-            fg_color: macroquad::color::WHITE,
-            bg_color: macroquad::color::BLACK,
+            fg_color: CharColors::White as u8,
+            bg_color: CharColors::Black as u8,
+            attributes: 0,
         }
     }
 }
@@ -308,6 +1014,21 @@ impl Cursor {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VideoModes {
+    Text = 0,
+    Bitmap = 1,
+    Tiles = 2,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum CursorMoves {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
 #[derive(Debug)]
 pub enum CursorShapes {
     Block,
@@ -315,8 +1036,19 @@ pub enum CursorShapes {
     VertiBar,
 }
 
+/// The GPU's default palette
 #[repr(u8)]
 pub enum CharColors {
+    Black = 0x00,
+    DarkGray = 0x01,
+    Gray = 0x02,
+    Orange = 0x03,
+    Yellow = 0x04,
+    Brown = 0x05,
+    Pink = 0x06,
+    Purple = 0x07,
+    Navy = 0x08,
+    DarkGreen = 0x09,
     White = 0x0A,
     Red = 0x0B,
     Green = 0x0C,
@@ -328,14 +1060,301 @@ pub enum CharColors {
 impl CharColors {
     fn from_u8(value: u8) -> Option<Self> {
         match value {
-            0x00 => Some(CharColors::White),
+            0x00 => Some(CharColors::Black),
+            0x01 => Some(CharColors::DarkGray),
+            0x02 => Some(CharColors::Gray),
+            0x03 => Some(CharColors::Orange),
+            0x04 => Some(CharColors::Yellow),
+            0x05 => Some(CharColors::Brown),
+            0x06 => Some(CharColors::Pink),
+            0x07 => Some(CharColors::Purple),
+            0x08 => Some(CharColors::Navy),
+            0x09 => Some(CharColors::DarkGreen),
             0x0A => Some(CharColors::White),
             0x0B => Some(CharColors::Red),
             0x0C => Some(CharColors::Green),
             0x0D => Some(CharColors::Blue),
             0x0E => Some(CharColors::Cyan),
             0x0F => Some(CharColors::Magenta),
-            _ => Some(CharColors::White),
+            _ => None,
+        }
+    }
+
+    fn to_color(&self) -> macroquad::color::Color {
+        match self {
+            CharColors::Black => macroquad::color::BLACK,
+            CharColors::DarkGray => macroquad::color::DARKGRAY,
+            CharColors::Gray => macroquad::color::GRAY,
+            CharColors::Orange => macroquad::color::ORANGE,
+            CharColors::Yellow => macroquad::color::YELLOW,
+            CharColors::Brown => macroquad::color::BROWN,
+            CharColors::Pink => macroquad::color::PINK,
+            CharColors::Purple => macroquad::color::PURPLE,
+            CharColors::Navy => macroquad::color::DARKBLUE,
+            CharColors::DarkGreen => macroquad::color::DARKGREEN,
+            CharColors::White => macroquad::color::WHITE,
+            CharColors::Red => macroquad::color::RED,
+            CharColors::Green => macroquad::color::GREEN,
+            CharColors::Blue => macroquad::color::BLUE,
+            CharColors::Cyan => macroquad::color::Color::new(0.5,0.9,1.0,1.0),
+            CharColors::Magenta => macroquad::color::MAGENTA,
+        }
+    }
+}
+
+/// Returns the number of words of an image ```blit``` reads, which stop at the end of the memory
+fn image_len(source: u16, row_words: usize, height: usize) -> usize {
+    (row_words * height).min(0x10000 - source as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A GPU that doesn't read the memory image
+    fn gpu() -> GPU {
+        GPU {
+            buf_ptr: 0x0300,
+            memory: Vec::new(),
+            frame_buffer: vec![vec![Character::new(' '); 45]; 136],
+            scrollback: VecDeque::new(),
+            scrollback_view: 0,
+            scroll_region: (0, 44),
+            config: GpuConfig::default(),
+            video_mode: VideoModes::Text,
+            bitmap: vec![0; BITMAP_SIZE],
+            status: Arc::new(Status::default()),
+            collisions: 0,
+            busy: false,
+            font: Font::default(),
+            glyph_atlas: None,
+            cursor: Cursor::new(CursorShapes::Underline),
+            cursor_visible: false,
+            draw_mode: false,
+            draw_color: macroquad::color::WHITE,
+            palette: std::array::from_fn(|index| CharColors::from_u8(index as u8).unwrap().to_color()),
+            attributes: 0,
+            blink_visible: true,
+            clock_speed: 10_000,
+            pri_counter: 0,
+            sec_counter: 0,
+            fullscreen: false,
+        }
+    }
+
+    /// Returns the characters of a line of the frame buffer, without the spaces at its end
+    fn line(gpu: &GPU, y: usize) -> String {
+        let line: String = gpu.frame_buffer.iter().map(|column| column[y].literal).collect();
+        line.trim_end().to_string()
+    }
+
+    /// Writes text at the cursor, starting a new line at every newline
+    fn write(gpu: &mut GPU, text: &str) {
+        for char in text.chars() {
+            match char {
+                '\n' => {
+                    gpu.cursor.position.0 = 0;
+                    gpu.move_cursor(CursorMoves::Down);
+                }
+                _ => gpu.put_char(Character::new(char)),
+            }
         }
     }
+
+    #[test]
+    fn the_cursor_wraps_to_the_neighbouring_line() {
+        let mut gpu = gpu();
+        gpu.move_cursor(CursorMoves::Left);
+        assert_eq!(gpu.cursor.position, (0, 0));
+        gpu.set_cursor(135, 3);
+        gpu.move_cursor(CursorMoves::Right);
+        assert_eq!(gpu.cursor.position, (0, 4));
+        gpu.move_cursor(CursorMoves::Left);
+        assert_eq!(gpu.cursor.position, (135, 3));
+    }
+
+    #[test]
+    fn the_cursor_is_clamped_to_the_screen() {
+        let mut gpu = gpu();
+        gpu.move_cursor(CursorMoves::Up);
+        assert_eq!(gpu.cursor.position, (0, 0));
+        gpu.set_cursor(500, 500);
+        assert_eq!(gpu.cursor.position, (135, 44));
+        gpu.move_cursor(CursorMoves::Down);
+        assert_eq!(gpu.cursor.position, (135, 44));
+    }
+
+    #[test]
+    fn values_are_drawn_in_their_base() {
+        let mut gpu = gpu();
+        gpu.draw_value(0x0B10, 0xBEEF);
+        gpu.draw_value(0x0A02, 5);
+        gpu.draw_value(0x0A07, 42);
+        assert_eq!(line(&gpu, 0), "BEEF10142");
+        assert_eq!(gpu.frame_buffer[0][0].fg_color, CharColors::Red as u8);
+        assert_eq!(gpu.cursor.position, (9, 0));
+    }
+
+    #[test]
+    fn color_bytes_hold_both_colors() {
+        let mut gpu = gpu();
+        gpu.attributes = ATTR_BOLD | ATTR_BLINK;
+        let char = gpu.styled_char('a', 0x3B);
+        assert_eq!((char.fg_color, char.bg_color, char.attributes), (0x0B, 0x03, ATTR_BOLD | ATTR_BLINK));
+        let char = gpu.styled_char('a', 0x00);
+        assert_eq!((char.fg_color, char.bg_color), (CharColors::White as u8, CharColors::Black as u8));
+    }
+
+    #[test]
+    fn palette_entries_are_set_from_rgb565() {
+        let mut gpu = gpu();
+        gpu.set_palette(0x13, 0xF800);
+        assert_eq!(gpu.palette[3], macroquad::color::Color::new(1.0, 0.0, 0.0, 1.0));
+        gpu.set_palette(4, 0x07E0 | 0x001F);
+        assert_eq!(gpu.palette[4], macroquad::color::Color::new(0.0, 1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn the_screen_scrolls_at_its_bottom() {
+        let mut gpu = gpu();
+        write(&mut gpu, "first\nsecond");
+        gpu.set_cursor(0, 44);
+        write(&mut gpu, "last\n");
+        assert_eq!(gpu.cursor.position, (0, 44));
+        assert_eq!(line(&gpu, 0), "second");
+        assert_eq!(line(&gpu, 43), "last");
+        assert_eq!(gpu.scrollback.len(), 1);
+
+        gpu.scrollback_view = 1;
+        assert_eq!(gpu.visible_char(0, 0).literal, 'f');
+        assert_eq!(gpu.visible_char(0, 1).literal, 's');
+    }
+
+    #[test]
+    fn scrolling_stays_inside_the_scroll_region() {
+        let mut gpu = gpu();
+        write(&mut gpu, "a\nb\nc\nd");
+        gpu.set_scroll_region(1, 2);
+        gpu.scroll_up(1);
+        assert_eq!((0..4).map(|y| line(&gpu, y)).collect::<Vec<_>>(), ["a", "c", "", "d"]);
+        gpu.scroll_down(5);
+        assert_eq!((0..4).map(|y| line(&gpu, y)).collect::<Vec<_>>(), ["a", "", "", "d"]);
+        assert!(gpu.scrollback.is_empty());
+
+        gpu.set_scroll_region(3, 2);
+        assert_eq!(gpu.scroll_region, (1, 2));
+    }
+
+    #[test]
+    fn pixels_are_packed_four_to_a_word() {
+        let mut gpu = gpu();
+        gpu.set_pixel(0, 0, 0x1);
+        gpu.set_pixel(3, 0, 0x2F);
+        gpu.set_pixel(5, 1, 0x7);
+        assert_eq!(gpu.bitmap[0], 0x100F);
+        assert_eq!(gpu.bitmap[BITMAP_WIDTH / 4 + 1], 0x0700);
+        assert_eq!(gpu.get_pixel(5, 1), 0x7);
+
+        gpu.set_pixel(-1, 0, 0x3);
+        gpu.set_pixel(BITMAP_WIDTH as i32, 0, 0x3);
+        gpu.set_pixel(0, BITMAP_HEIGHT as i32, 0x3);
+        assert_eq!(gpu.bitmap.iter().filter(|word| **word != 0).count(), 2);
+    }
+
+    #[test]
+    fn lines_include_both_ends() {
+        let mut gpu = gpu();
+        gpu.draw_line(4, 2, 0, 0, 0x5);
+        let pixels: Vec<(usize, usize)> = (0..3)
+            .flat_map(|y| (0..5).map(move |x| (x, y)))
+            .filter(|&(x, y)| gpu.get_pixel(x, y) == 0x5)
+            .collect();
+        assert_eq!(pixels, [(0, 0), (1, 0), (2, 1), (3, 1), (4, 2)]);
+    }
+
+    #[test]
+    fn rectangles_are_cut_off_at_the_edges() {
+        let mut gpu = gpu();
+        gpu.fill_rect(-2, BITMAP_HEIGHT as i32 - 2, 4, 4, 0x9);
+        let filled: Vec<(usize, usize)> = (0..BITMAP_HEIGHT)
+            .flat_map(|y| (0..BITMAP_WIDTH).map(move |x| (x, y)))
+            .filter(|&(x, y)| gpu.get_pixel(x, y) != 0)
+            .collect();
+        assert_eq!(filled, [(0, 88), (1, 88), (0, 89), (1, 89)]);
+    }
+
+    #[test]
+    fn huge_rectangles_only_fill_the_bitmap() {
+        let mut gpu = gpu();
+        gpu.fill_rect(BITMAP_WIDTH as i32 - 1, 0, u16::MAX as i32, u16::MAX as i32, 0x3);
+        gpu.fill_rect(0, i32::MAX, i32::MAX, i32::MAX, 0x4);
+        let filled = (0..BITMAP_HEIGHT).filter(|&y| gpu.get_pixel(BITMAP_WIDTH - 1, y) == 0x3).count();
+        assert_eq!(filled, BITMAP_HEIGHT);
+        assert_eq!(gpu.get_pixel(BITMAP_WIDTH - 2, 0), 0);
+    }
+
+    #[test]
+    fn images_end_with_their_words() {
+        let mut gpu = gpu();
+        // Two rows of 8 pixels, the second one cut short
+        gpu.draw_image(&[0x1200, 0x0034, 0x5600], 2, 1, 1, 8, BITMAP_HEIGHT);
+        let drawn: Vec<(usize, usize, u8)> = (0..4)
+            .flat_map(|y| (0..10).map(move |x| (x, y)))
+            .map(|(x, y)| (x, y, gpu.get_pixel(x, y)))
+            .filter(|&(_, _, color)| color != 0)
+            .collect();
+        assert_eq!(drawn, [(1, 1, 1), (2, 1, 2), (7, 1, 3), (8, 1, 4), (1, 2, 5), (2, 2, 6)]);
+
+        assert_eq!(image_len(0x8000, 2, 8), 16);
+        assert_eq!(image_len(0xFFF0, u16::MAX.div_ceil(4) as usize, BITMAP_HEIGHT), 0x10);
+    }
+
+    #[test]
+    fn frames_publish_the_status() {
+        let mut gpu = gpu();
+        gpu.video_mode = VideoModes::Tiles;
+        gpu.collisions = 0b110;
+        gpu.busy = true;
+        gpu.set_cursor(7, 3);
+        gpu.publish_status();
+        gpu.publish_status();
+
+        let flags = status::STATUS_VBLANK | status::STATUS_COLLISION | status::STATUS_BUSY;
+        assert_eq!(gpu.status.read(status::GPU_STATUS), Some(0x0200 | flags));
+        assert_eq!(gpu.status.read(status::GPU_COLLISIONS), Some(0b110));
+        assert_eq!(gpu.status.read(status::GPU_FRAMES), Some(2));
+        assert_eq!(gpu.status.read(status::GPU_CURSOR_X), Some(7));
+        assert_eq!(gpu.status.read(status::GPU_CURSOR_Y), Some(3));
+        assert!(gpu.status.vblank.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn the_tail_follows_the_buffer_pointer() {
+        let mut gpu = gpu();
+        gpu.buf_ptr = status::RING_END;
+        gpu.increase_buf_ptr();
+        assert_eq!(gpu.buf_ptr, status::RING_START);
+        gpu.increase_buf_ptr();
+        assert_eq!(gpu.status.read(status::RING_TAIL), Some(status::RING_START + 1));
+    }
+
+    #[test]
+    fn glyphs_are_defined_in_a_row() {
+        let mut gpu = gpu();
+        let words: Vec<u16> = (0..12).collect();
+        gpu.define_glyphs(0xFF, &words);
+        assert_eq!(gpu.font.glyphs[0xFF], [0, 0, 0, 1, 0, 2, 0, 3, 0, 4, 0, 5]);
+        assert_eq!(gpu.font.glyphs[0x00], [0, 6, 0, 7, 0, 8, 0, 9, 0, 10, 0, 11]);
+    }
+
+    #[test]
+    fn text_wraps_at_the_configured_width() {
+        let mut gpu = gpu();
+        gpu.config = GpuConfig { columns: 4, rows: 2, ..GpuConfig::default() };
+        gpu.frame_buffer = vec![vec![Character::new(' '); 2]; 4];
+        gpu.scroll_region = (0, 1);
+        write(&mut gpu, "ABCDEFGHI");
+        assert_eq!(line(&gpu, 0), "EFGH");
+        assert_eq!(line(&gpu, 1), "I");
+    }
 }
@@ -1,9 +1,12 @@
+pub mod config;
+pub mod font;
 pub mod gpu;
 pub mod opcodes;
+pub mod status;
+pub mod tiles;
 
 use std::default::Default;
-
-pub static mut SCALING: f32 = 1.0;
+use std::sync::Arc;
 
 // NOTE: MEMORY LAYOUT
 // 0x0000 - 0x01FF | STACK (512 16-bit / 1024B)
@@ -14,22 +17,39 @@ pub static mut SCALING: f32 = 1.0;
 // 0x0240 - 0x0249 | 0 - 9
 // 0x024A - 0x024F | : _ | & ? @
 // 0x0250          | EMPTY CHAR (0x0020)
-// 0x0300 - 0x0FFF | GPU BUFFER (3328 16-bit / 6656B)
+// 0x02F0 - 0x02F7 | GPU STATUS, COMMAND RING POINTERS (MAPPED TO THE GPU)
+// 0x0300 - 0x0FFF | GPU COMMAND RING (3328 16-bit / 6656B)
+// 0xE000 - 0xE53F | TILE PATTERNS, TILE MAP, SPRITE ATTRIBUTES
+// 0xF000 - 0xFE0F | BITMAP (160x90 pixels, 4 per 16-bit address)
 
-fn window_config() -> macroquad::window::Conf {
+fn window_config(config: &config::GpuConfig) -> macroquad::window::Conf {
+    let (width, height) = config.window_size();
     macroquad::window::Conf {
         window_title: "Rusty-VM".to_string(),
-        window_width: 960 * unsafe { SCALING as i32 },
-        window_height: 545 * unsafe { SCALING as i32 },
+        window_width: width as i32,
+        window_height: height as i32,
         window_resizable: false,
         fullscreen: false,
         ..Default::default()
     }
 }
 
-#[macroquad::main(window_config())]
-pub async fn main() {
-    let mut gpu = gpu::GPU::init();
+/// Opens the window and runs the GPU. The status is shared with the CPU. If the configuration
+/// names a font, it is loaded from the host instead of using the built-in one.
+pub fn main(status: Arc<status::Status>, config: config::GpuConfig) {
+    let font = match &config.font_path {
+        Some(path) => font::Font::load(path).unwrap_or_else(|error| {
+            use colored::Colorize;
+            println!("{}: {}, using the built-in font", "[GPU ERROR]".red(), error);
+            font::Font::default()
+        }),
+        None => font::Font::default(),
+    };
+    macroquad::Window::from_config(window_config(&config), run(status, font, config));
+}
+
+async fn run(status: Arc<status::Status>, font: font::Font, config: config::GpuConfig) {
+    let mut gpu = gpu::GPU::init(status, font, config);
     #[cfg(debug_assertions)]
     debug!("GPU initialized");
     macroquad::window::next_frame().await;
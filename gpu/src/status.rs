@@ -0,0 +1,184 @@
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+
+// NOTE: GPU STATUS LAYOUT
+// 0x02F0 | STATUS FLAGS (VIDEO MODE IN THE HIGH BYTE)
+// 0x02F1 | SPRITE COLLISIONS
+// 0x02F2 | FRAME COUNTER
+// 0x02F3 | CURSOR COLLUMN
+// 0x02F4 | CURSOR LINE
+// 0x02F5 | COMMAND RING HEAD (WRITTEN BY THE CPU)
+// 0x02F6 | COMMAND RING TAIL (WRITTEN BY THE GPU)
+// 0x02F7 | DOORBELL
+//
+// NOTE: COMMAND RING
+// The GPU buffer at 0x0300 - 0x0FFF is used as a ring. The CPU pushes commands at the head and
+// rings the doorbell to hand everything up to the head over to the GPU. The GPU executes commands
+// at the tail until it reaches the head it was handed. The ring is full when the head is right
+// behind the tail, so one address always stays unused.
+
+/// Address of the GPU status word
+pub const GPU_STATUS: u16 = 0x02F0;
+/// Address of the sprite collision mask. Bit n is set if sprite n overlapped another sprite.
+pub const GPU_COLLISIONS: u16 = 0x02F1;
+/// Address of the frame counter, which wraps around after 65535 frames
+pub const GPU_FRAMES: u16 = 0x02F2;
+/// Address of the cursor's collumn
+pub const GPU_CURSOR_X: u16 = 0x02F3;
+/// Address of the cursor's line
+pub const GPU_CURSOR_Y: u16 = 0x02F4;
+/// Address of the command ring's head, the address the CPU pushes the next command word to
+pub const RING_HEAD: u16 = 0x02F5;
+/// Address of the command ring's tail, the address of the next command word the GPU executes
+pub const RING_TAIL: u16 = 0x02F6;
+/// Address of the doorbell. Writing any value to it hands the commands up to the head to the GPU.
+pub const RING_DOORBELL: u16 = 0x02F7;
+
+/// First address of the command ring
+pub const RING_START: u16 = 0x0300;
+/// Last address of the command ring
+pub const RING_END: u16 = 0x0FFF;
+
+/// Status flag: At least two sprites overlapped in the last frame
+pub const STATUS_COLLISION: u16 = 0x0001;
+/// Status flag: The GPU was working through commands when the last frame was drawn
+pub const STATUS_BUSY: u16 = 0x0002;
+/// Status flag: A frame was drawn since the status word was last read
+pub const STATUS_VBLANK: u16 = 0x0004;
+/// Status flag: The command ring is full, pushing more commands has to wait for the GPU
+pub const STATUS_RING_FULL: u16 = 0x0008;
+
+/// Returns the address following the given one in the command ring
+pub fn next_in_ring(address: u16) -> u16 {
+    if address >= RING_END { RING_START } else { address + 1 }
+}
+
+/// State the GPU shares with the CPU. The CPU sees it mapped into the memory at
+/// 0x02F0 - 0x02F7 and gets an interrupt after every frame through the vblank flag.
+#[derive(Debug)]
+pub struct Status {
+    pub flags: AtomicU16,
+    pub collisions: AtomicU16,
+    pub frame_counter: AtomicU16,
+    pub cursor_x: AtomicU16,
+    pub cursor_y: AtomicU16,
+    pub vblank: AtomicBool,
+    pub ring_head: AtomicU16,
+    pub ring_tail: AtomicU16,
+    pub doorbell: AtomicU16,
+}
+
+impl Default for Status {
+    fn default() -> Self {
+        Self {
+            flags: Default::default(),
+            collisions: Default::default(),
+            frame_counter: Default::default(),
+            cursor_x: Default::default(),
+            cursor_y: Default::default(),
+            vblank: Default::default(),
+            ring_head: AtomicU16::new(RING_START),
+            ring_tail: AtomicU16::new(RING_START),
+            doorbell: AtomicU16::new(RING_START),
+        }
+    }
+}
+
+impl Status {
+    /// Returns true if the CPU has to wait for the GPU before pushing another command word
+    pub fn ring_full(&self) -> bool {
+        next_in_ring(self.ring_head.load(Ordering::Acquire)) == self.ring_tail.load(Ordering::Acquire)
+    }
+
+    /// Hands all commands up to the ring's head over to the GPU
+    pub fn ring_doorbell(&self) {
+        self.doorbell.store(self.ring_head.load(Ordering::Acquire), Ordering::Release);
+    }
+
+    /// Returns the value mapped to the given address, or None if the address isn't part of the
+    /// GPU status. Reading the status word clears its vblank flag.
+    pub fn read(&self, address: u16) -> Option<u16> {
+        match address {
+            GPU_STATUS => {
+                let mut flags = self.flags.fetch_and(!STATUS_VBLANK, Ordering::Relaxed);
+                if self.ring_full() {
+                    flags |= STATUS_RING_FULL;
+                }
+                Some(flags)
+            }
+            GPU_COLLISIONS => Some(self.collisions.load(Ordering::Relaxed)),
+            GPU_FRAMES => Some(self.frame_counter.load(Ordering::Relaxed)),
+            GPU_CURSOR_X => Some(self.cursor_x.load(Ordering::Relaxed)),
+            GPU_CURSOR_Y => Some(self.cursor_y.load(Ordering::Relaxed)),
+            RING_HEAD => Some(self.ring_head.load(Ordering::Acquire)),
+            RING_TAIL => Some(self.ring_tail.load(Ordering::Acquire)),
+            RING_DOORBELL => Some(self.doorbell.load(Ordering::Acquire)),
+            _ => None,
+        }
+    }
+
+    /// Writes a value to the given address. Returns false if the address isn't part of the GPU
+    /// status. Only the ring's head and the doorbell can be written by the CPU.
+    pub fn write(&self, address: u16, value: u16) -> bool {
+        match address {
+            RING_HEAD => {
+                let head = value.clamp(RING_START, RING_END);
+                self.ring_head.store(head, Ordering::Release);
+            }
+            RING_DOORBELL => self.ring_doorbell(),
+            GPU_STATUS..=RING_TAIL => {}
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reading_the_status_word_clears_vblank() {
+        let status = Status::default();
+        status.flags.store(STATUS_VBLANK | STATUS_BUSY, Ordering::Relaxed);
+        assert_eq!(status.read(GPU_STATUS), Some(STATUS_VBLANK | STATUS_BUSY));
+        assert_eq!(status.read(GPU_STATUS), Some(STATUS_BUSY));
+    }
+
+    #[test]
+    fn only_the_status_block_is_mapped() {
+        let status = Status::default();
+        status.cursor_y.store(12, Ordering::Relaxed);
+        assert_eq!(status.read(GPU_CURSOR_Y), Some(12));
+        assert_eq!(status.read(GPU_STATUS - 1), None);
+        assert_eq!(status.read(RING_DOORBELL + 1), None);
+    }
+
+    #[test]
+    fn the_ring_wraps_to_its_start() {
+        assert_eq!(next_in_ring(RING_START), RING_START + 1);
+        assert_eq!(next_in_ring(RING_END), RING_START);
+    }
+
+    #[test]
+    fn the_ring_is_full_when_the_head_is_behind_the_tail() {
+        let status = Status::default();
+        assert!(!status.ring_full());
+        status.ring_tail.store(0x0500, Ordering::Relaxed);
+        assert!(status.write(RING_HEAD, 0x04FF));
+        assert!(status.ring_full());
+        assert_eq!(status.read(GPU_STATUS), Some(STATUS_RING_FULL));
+    }
+
+    #[test]
+    fn only_the_head_and_the_doorbell_are_written() {
+        let status = Status::default();
+        assert!(status.write(RING_HEAD, 0x0010));
+        assert_eq!(status.read(RING_HEAD), Some(RING_START));
+        assert!(status.write(RING_HEAD, 0x0420));
+        assert!(status.write(RING_TAIL, 0x0600));
+        assert_eq!(status.read(RING_TAIL), Some(RING_START));
+        assert!(status.write(RING_DOORBELL, 0));
+        assert_eq!(status.read(RING_DOORBELL), Some(0x0420));
+        assert!(!status.write(RING_START, 0));
+    }
+}
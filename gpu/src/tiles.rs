@@ -0,0 +1,171 @@
+use crate::gpu::{BITMAP_HEIGHT, BITMAP_WIDTH};
+
+// NOTE: TILE MEMORY LAYOUT
+// 0xE000 - 0xE3FF | TILE PATTERNS (64 tiles, 8x8 pixels, 16 16-bit addresses each)
+// 0xE400 - 0xE4EF | TILE MAP (20x12 tile indices)
+// 0xE500 - 0xE53F | SPRITE ATTRIBUTES (16 sprites, 4 16-bit addresses each)
+
+/// Start of the tile pattern table. Tiles use the bitmap's format, two addresses per row.
+pub const TILE_PATTERNS: u16 = 0xE000;
+/// Number of tiles in the tile pattern table
+pub const TILE_COUNT: usize = 64;
+/// Width and height of a tile in pixels
+pub const TILE_SIZE: usize = 8;
+/// Size of a tile in words
+pub const TILE_WORDS: usize = TILE_SIZE * TILE_SIZE / 4;
+
+/// Start of the tile map. Each word holds the index of the tile drawn at that position.
+pub const TILE_MAP: u16 = 0xE400;
+/// Width of the tile map in tiles
+pub const TILE_MAP_WIDTH: usize = 20;
+/// Height of the tile map in tiles. The last row is only partially visible.
+pub const TILE_MAP_HEIGHT: usize = 12;
+
+/// Start of the sprite attribute table
+pub const SPRITE_TABLE: u16 = 0xE500;
+/// Number of hardware sprites
+pub const SPRITE_COUNT: usize = 16;
+/// Size of a sprite's attributes in words: x, y, tile index and flags
+pub const SPRITE_WORDS: usize = 4;
+
+/// Sprite flag: Adds this offset to the sprite's palette indices
+pub const SPRITE_PALETTE: u16 = 0x000F;
+/// Sprite flag: Mirrors the sprite horizontally
+pub const SPRITE_FLIP_H: u16 = 0x0010;
+/// Sprite flag: Mirrors the sprite vertically
+pub const SPRITE_FLIP_V: u16 = 0x0020;
+/// Sprite flag: Makes the sprite visible
+pub const SPRITE_VISIBLE: u16 = 0x0080;
+
+/// A hardware sprite as stored in the sprite attribute table
+#[derive(Debug, Clone, Copy)]
+pub struct Sprite {
+    pub x: i16,
+    pub y: i16,
+    pub tile: u16,
+    pub flags: u16,
+}
+
+impl Sprite {
+    pub fn from_words(words: &[u16]) -> Self {
+        Self {
+            x: words[0] as i16,
+            y: words[1] as i16,
+            tile: words[2],
+            flags: words[3],
+        }
+    }
+}
+
+/// Returns the palette index of a pixel inside a tile
+fn tile_pixel(patterns: &[u16], tile: u16, x: usize, y: usize) -> u8 {
+    let offset = (tile as usize % TILE_COUNT) * TILE_WORDS;
+    let pixel = y * TILE_SIZE + x;
+    (patterns[offset + pixel / 4] >> (12 - (pixel % 4) * 4)) as u8 & 0x0F
+}
+
+/// Draws the tile map and the sprites into a picture of palette indices the size of the bitmap.
+/// Sprites are drawn in reverse order so sprite 0 ends up in front, palette index 0 is
+/// transparent. Also returns a mask with a bit set for every sprite that overlaps another one.
+pub fn compose(patterns: &[u16], map: &[u16], sprites: &[u16]) -> (Vec<u8>, u16) {
+    let mut pixels = vec![0u8; BITMAP_WIDTH * BITMAP_HEIGHT];
+    let mut owners = vec![None::<usize>; BITMAP_WIDTH * BITMAP_HEIGHT];
+    let mut collisions = 0u16;
+
+    for y in 0..BITMAP_HEIGHT {
+        for x in 0..BITMAP_WIDTH {
+            let tile = map[(y / TILE_SIZE) * TILE_MAP_WIDTH + x / TILE_SIZE];
+            pixels[y * BITMAP_WIDTH + x] = tile_pixel(patterns, tile, x % TILE_SIZE, y % TILE_SIZE);
+        }
+    }
+
+    for index in (0..SPRITE_COUNT).rev() {
+        let sprite = Sprite::from_words(&sprites[index * SPRITE_WORDS..(index + 1) * SPRITE_WORDS]);
+        if sprite.flags & SPRITE_VISIBLE == 0 {
+            continue;
+        }
+
+        for row in 0..TILE_SIZE {
+            for column in 0..TILE_SIZE {
+                let x = sprite.x as i32 + column as i32;
+                let y = sprite.y as i32 + row as i32;
+                if x < 0 || y < 0 || x >= BITMAP_WIDTH as i32 || y >= BITMAP_HEIGHT as i32 {
+                    continue;
+                }
+
+                let source_x = if sprite.flags & SPRITE_FLIP_H != 0 { TILE_SIZE - 1 - column } else { column };
+                let source_y = if sprite.flags & SPRITE_FLIP_V != 0 { TILE_SIZE - 1 - row } else { row };
+                let color = tile_pixel(patterns, sprite.tile, source_x, source_y);
+                if color == 0 {
+                    continue;
+                }
+
+                let pixel = y as usize * BITMAP_WIDTH + x as usize;
+                if let Some(other) = owners[pixel] {
+                    collisions |= (1 << index) | (1 << other);
+                }
+                owners[pixel] = Some(index);
+                pixels[pixel] = (color + (sprite.flags & SPRITE_PALETTE) as u8) & 0x0F;
+            }
+        }
+    }
+
+    (pixels, collisions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tile patterns where tile 1 is filled with color 2 and tile 2 has color 3 in its top left
+    /// pixel only
+    fn patterns() -> Vec<u16> {
+        let mut patterns = vec![0; TILE_COUNT * TILE_WORDS];
+        patterns[TILE_WORDS..2 * TILE_WORDS].fill(0x2222);
+        patterns[2 * TILE_WORDS] = 0x3000;
+        patterns
+    }
+
+    fn sprite(x: i16, y: i16, tile: u16, flags: u16) -> [u16; SPRITE_WORDS] {
+        [x as u16, y as u16, tile, flags | SPRITE_VISIBLE]
+    }
+
+    #[test]
+    fn the_tile_map_fills_the_screen() {
+        let mut map = vec![0; TILE_MAP_WIDTH * TILE_MAP_HEIGHT];
+        map[TILE_MAP_WIDTH + 2] = 1;
+        let (pixels, collisions) = compose(&patterns(), &map, &[0; SPRITE_COUNT * SPRITE_WORDS]);
+        let at = |x: usize, y: usize| pixels[y * BITMAP_WIDTH + x];
+        assert_eq!((at(16, 8), at(23, 15)), (2, 2));
+        assert_eq!((at(15, 8), at(24, 15), at(16, 16)), (0, 0, 0));
+        assert_eq!(collisions, 0);
+    }
+
+    #[test]
+    fn sprites_are_flipped_and_recolored() {
+        let map = vec![0; TILE_MAP_WIDTH * TILE_MAP_HEIGHT];
+        let mut sprites = vec![0; SPRITE_COUNT * SPRITE_WORDS];
+        sprites[..4].copy_from_slice(&sprite(-4, 10, 2, SPRITE_FLIP_H | SPRITE_FLIP_V | 1));
+        sprites[4..8].copy_from_slice(&sprite(20, 20, 2, 0));
+        sprites[8] = 30;
+        let (pixels, _) = compose(&patterns(), &map, &sprites);
+        let at = |x: usize, y: usize| pixels[y * BITMAP_WIDTH + x];
+        assert_eq!(at(3, 17), 4);
+        assert_eq!(at(20, 20), 3);
+        assert_eq!(pixels.iter().filter(|pixel| **pixel != 0).count(), 2);
+    }
+
+    #[test]
+    fn overlapping_sprites_collide() {
+        let map = vec![0; TILE_MAP_WIDTH * TILE_MAP_HEIGHT];
+        let mut sprites = vec![0; SPRITE_COUNT * SPRITE_WORDS];
+        sprites[..4].copy_from_slice(&sprite(0, 0, 1, 0));
+        sprites[8..12].copy_from_slice(&sprite(7, 7, 1, 5));
+        // Sprite 3 only overlaps the others with its transparent pixels
+        sprites[12..16].copy_from_slice(&sprite(-1, -1, 2, 0));
+        let (pixels, collisions) = compose(&patterns(), &map, &sprites);
+        assert_eq!(collisions, 0b0101);
+        assert_eq!(pixels[7 * BITMAP_WIDTH + 7], 2);
+        assert_eq!(pixels[8 * BITMAP_WIDTH + 8], 7);
+    }
+}
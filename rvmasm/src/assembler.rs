@@ -0,0 +1,1716 @@
+use crate::ast::{self, BinaryOp, CompOperand, InstructionKind, Register, Style, UnaryOp, Value, ValueKind};
+use crate::diagnostics::{self, Diagnostic, Diagnostics};
+use crate::layout::{Layout, Placement};
+use crate::object::{self, Object, SectionKind};
+use crate::source::{SourceFile, Span};
+use crate::{debug, fs, opcodes};
+use colored::Colorize;
+use std::collections::HashMap;
+use std::ops::Range;
+
+type Result<T> = std::result::Result<T, Diagnostic>;
+
+pub const ROM_SIZE: usize = 65536; // 64 KiB
+
+/// Address of the jump to the entry routine
+pub const ENTRY_TRAMPOLINE: usize = 0x1000;
+/// Address the first routine or filesystem is placed at
+pub const CODE_START: usize = 0x1002;
+
+/// Addresses of the preprogrammed character codes
+pub const ASCII_TABLE: Range<u16> = 0x0200..0x0252;
+/// Addresses of the GPU's status and the pointers of its command ring
+pub const GPU_STATUS: Range<u16> = 0x02F0..0x02F8;
+/// Addresses of the GPU's command buffer
+pub const GPU_BUFFER: Range<u16> = 0x0300..0x1000;
+/// Addresses of the GPU's tile patterns, tile map and sprites
+pub const TILE_MEMORY: Range<u16> = 0xE000..0xE540;
+/// Addresses of the GPU's bitmap
+pub const BITMAP: Range<u16> = 0xF000..0xFE10;
+
+/// Number of passes after which the addresses of all symbols must have settled
+const MAX_PASSES: usize = 8;
+
+/// The assembled memory image and what was placed in it
+pub struct Image {
+    pub memory: Vec<u16>,
+    pub file_systems: Vec<fs::FileSystem>,
+    /// Every routine, label and constant, in the order they are defined
+    pub symbols: Vec<Symbol>,
+    /// The words of every instruction and data directive with the code they were assembled from
+    pub listing: Vec<Listed>,
+    /// Where the words at every address were assembled from, for the VM
+    pub debug: Vec<debug::DebugEntry>,
+    /// The warnings found while assembling, with the files and macro calls the spans of the
+    /// symbols, the listing and the debug info point into
+    pub diagnostics: Diagnostics,
+}
+
+/// A name defined by the program
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// The address of a routine or label, or the value of a constant
+    pub value: u16,
+    /// Number of addresses a routine or the data after a data label takes up
+    pub size: Option<usize>,
+    /// The routine a local label belongs to
+    pub routine: Option<String>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Routine,
+    /// A label inside a routine
+    Label,
+    /// A label naming data
+    Data,
+    Constant,
+}
+
+impl SymbolKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Routine => "routine",
+            Self::Label => "label",
+            Self::Data => "data",
+            Self::Constant => "constant",
+        }
+    }
+}
+
+/// Words placed at an address, or a label or routine starting there without any words
+#[derive(Debug, Clone)]
+pub struct Listed {
+    pub address: u16,
+    pub words: Vec<u16>,
+    pub span: Span,
+}
+
+/// The words of the instructions assembled so far
+#[derive(Clone, Default)]
+pub struct Routine {
+    pub instructions: Vec<u16>,
+}
+
+impl Routine {
+    /// Pushes each word to the GPU's command ring by passing it through the G register, then
+    /// rings the doorbell so the GPU executes them
+    pub fn push_gpu_words(&mut self, words: &[u16]) {
+        for word in words {
+            self.instructions.push(opcodes::LOAD_GREG);
+            self.instructions.push(*word);
+            self.instructions.push(opcodes::STOR_GREG);
+        }
+        self.instructions.push(opcodes::RING_DBELL);
+    }
+}
+
+/// Addresses of routines and labels with the index of the section they are in. Labels inside a
+/// routine are stored with the index of their routine's section, everything else without one.
+type Addresses<'a> = HashMap<(Option<usize>, &'a str), (u16, usize)>;
+
+/// What an address depends on when assembling an object file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Base<'a> {
+    /// Where the linker places the section with the index
+    Section(usize),
+    /// The address of a symbol another object file defines
+    Import(&'a str),
+}
+
+/// Index of each word holding an address in what was assembled, with what the address depends on
+/// and where it is used
+type Relocations<'a> = Vec<(usize, Base<'a>, Span)>;
+
+/// Everything placed by one pass over the program
+#[derive(Default)]
+struct Pass<'a> {
+    addresses: Addresses<'a>,
+    sections: Vec<object::Section>,
+    /// Where each section is defined, to point at when it overlaps something
+    spans: Vec<Span>,
+    /// Words holding an address in object files, as the index of their section and their index
+    /// in it with what the address depends on and where it is used
+    relocations: Vec<(usize, usize, Base<'a>, Span)>,
+    /// Value of every constant, with what it depends on in object files
+    constants: Vec<(&'a str, u16, Option<Base<'a>>)>,
+    symbols: Vec<Symbol>,
+    listing: Vec<Listed>,
+    /// Lines printed about the placement once the final pass is known
+    log: Vec<String>,
+    diagnostics: Diagnostics,
+    /// Symbols that were used without being defined, with the scope they were looked up in
+    undefined: Vec<(Option<usize>, &'a str, Span)>,
+}
+
+/// Turns a parsed program into a memory image. Routines, filesystems and data are placed one after
+/// another into the first region of the layout that isn't reserved, which starts at 0x1002 without
+/// a layout file, and routines and filesystems are followed by an unused address. ```.section```
+/// continues in another region and ```.org``` continues at an address. Anything placed over
+/// something else or into a reserved region is an error. The jump at 0x1000 leads to the routine
+/// called "entry", or to the last routine if there is none.
+/// Values may use routines, labels and constants defined anywhere in the program. Since their
+/// addresses can depend on values placed before them, the program is placed again with the
+/// addresses of the previous pass until no address changes anymore.
+/// An instruction with an error is left out after adding the error to the diagnostics, so every
+/// error of the program is reported at once. There is no image if there were any errors.
+pub fn assemble(program: &ast::Program, layout: &Layout, diagnostics: &mut Diagnostics) -> Option<Image> {
+    let pass = arrange(program, layout, false, diagnostics)?;
+    let mut image = build_image(&pass.sections);
+    image.debug = debug::debug_info(&pass.listing, &pass.symbols, &diagnostics.expansions);
+    image.symbols = pass.symbols;
+    image.listing = pass.listing;
+    Some(image)
+}
+
+/// Turns a parsed program into an object file for the linker. It is placed like a program of its
+/// own, and every word holding an address is recorded with what the address depends on, so the
+/// linker can move the sections. Names that aren't defined are imported from other objects.
+/// Routines, data labels and constants are exported, except for constants that depend on an
+/// imported symbol. The sources are used to tell where each address is used.
+pub fn assemble_object(
+    program: &ast::Program,
+    sources: &[SourceFile],
+    layout: &Layout,
+    diagnostics: &mut Diagnostics,
+) -> Option<Object> {
+    let pass = arrange(program, layout, true, diagnostics)?;
+    let mut sections = pass.sections;
+    for (section, offset, base, span) in pass.relocations {
+        let target = match base {
+            Base::Section(index) => object::Target::Section(index),
+            Base::Import(name) => object::Target::Symbol(name.to_string()),
+        };
+        let location = match sources.get(span.file) {
+            Some(source) => format!("{}:{}:{}", source.path, span.line, span.column),
+            None => "?".to_string(),
+        };
+        sections[section].relocations.push(object::Relocation { offset, target, location });
+    }
+
+    let mut symbols: Vec<object::Symbol> = pass
+        .addresses
+        .iter()
+        .filter(|((scope, _), _)| scope.is_none())
+        .map(|((_, name), (address, section))| object::Symbol {
+            name: name.to_string(),
+            value: object::SymbolValue::Section(*section, address.wrapping_sub(sections[*section].address)),
+        })
+        .collect();
+    for (name, value, base) in pass.constants {
+        let value = match base {
+            None => object::SymbolValue::Absolute(value),
+            Some(Base::Section(index)) => {
+                object::SymbolValue::Section(index, value.wrapping_sub(sections[index].address))
+            }
+            Some(Base::Import(_)) => continue,
+        };
+        symbols.push(object::Symbol { name: name.to_string(), value });
+    }
+    symbols.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Some(Object { sections, symbols })
+}
+
+/// Places the program until its addresses settle, printing where everything was placed. There is
+/// no result if there were any errors.
+fn arrange<'a>(
+    program: &'a ast::Program,
+    layout: &Layout,
+    object: bool,
+    diagnostics: &mut Diagnostics,
+) -> Option<Pass<'a>> {
+    let constants: HashMap<&str, &ast::Constant> = program
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            ast::Item::Constant(constant) => Some((constant.name.name.as_str(), constant)),
+            _ => None,
+        })
+        .collect();
+
+    let mut previous = Addresses::new();
+    let mut pass = place(program, layout, &previous, &constants, object);
+    let mut passes = 1;
+    while pass.addresses != previous {
+        if passes == MAX_PASSES {
+            let span = unsettled(program);
+            pass.diagnostics.push(
+                Diagnostic::error(
+                    diagnostics::E_CIRCULAR,
+                    format!("The addresses of the program didn't settle after {} passes", MAX_PASSES),
+                    span,
+                )
+                .with_note("a size or address depends on an address that changes with it"),
+            );
+            break;
+        }
+        previous = std::mem::take(&mut pass.addresses);
+        pass = place(program, layout, &previous, &constants, object);
+        passes += 1;
+    }
+
+    diagnostics.log.append(&mut pass.log);
+    let placed: Vec<Placement> = pass
+        .sections
+        .iter()
+        .zip(&pass.spans)
+        .map(|(section, span)| Placement {
+            what: match section.kind {
+                SectionKind::Routine => format!("routine `{}`", section.name),
+                SectionKind::FileSystem => "filesystem".to_string(),
+                SectionKind::Data => format!("data `{}`", section.name),
+            },
+            start: section.address as usize,
+            len: section.used(),
+            span: *span,
+        })
+        .collect();
+    layout.check_overlaps(&placed, &mut pass.diagnostics);
+    report_undefined(&pass, &constants, diagnostics);
+    diagnostics.extend(std::mem::take(&mut pass.diagnostics));
+    if diagnostics.has_errors() {
+        return None;
+    }
+    Some(pass)
+}
+
+/// Writes sections into a memory image. The jump at 0x1000 leads to the routine called "entry",
+/// or to the last routine if there is none.
+pub fn build_image(sections: &[object::Section]) -> Image {
+    let mut memory = vec![0; ROM_SIZE];
+    preprogram(&mut memory);
+
+    let mut routines = sections.iter().filter(|section| section.kind == SectionKind::Routine);
+    let entry = routines.clone().find(|section| section.name == "entry").or(routines.next_back());
+    memory[ENTRY_TRAMPOLINE] = opcodes::JMP_TO_SR;
+    memory[ENTRY_TRAMPOLINE + 1] = entry.map_or(0, |section| section.address);
+
+    let mut file_systems = Vec::new();
+    for section in sections {
+        let start = section.address as usize;
+        memory[start..start + section.words.len()].copy_from_slice(&section.words);
+        if section.kind == SectionKind::FileSystem {
+            let mut filesystem = fs::FileSystem::new(start);
+            filesystem.size = section.size - 1;
+            file_systems.push(filesystem);
+        }
+    }
+
+    Image {
+        memory,
+        file_systems,
+        symbols: Vec::new(),
+        listing: Vec::new(),
+        debug: Vec::new(),
+        diagnostics: Diagnostics::default(),
+    }
+}
+
+/// Places every item of the program, evaluating values with the addresses of the previous pass.
+/// Each routine and filesystem gets a section of its own, data following each other shares one.
+/// An item that doesn't fit is left out after reporting it, so the items after it are still placed.
+fn place<'a>(
+    program: &'a ast::Program,
+    layout: &Layout,
+    previous: &Addresses<'a>,
+    constants: &HashMap<&'a str, &'a ast::Constant>,
+    object: bool,
+) -> Pass<'a> {
+    let mut pass = Pass::default();
+    let mut context = Context {
+        addresses: previous,
+        constants,
+        object,
+        routine: None,
+        evaluating: Vec::new(),
+        undefined: Vec::new(),
+        bases: Vec::new(),
+        shifts: Vec::new(),
+    };
+    // Where the next item goes in every region, and the region it goes into
+    let mut pointers: Vec<usize> = layout.regions.iter().map(|region| region.start as usize).collect();
+    let mut region = layout.default_region();
+    let mut instr_ptr = pointers[region];
+    // Whether the items are placed at an address given by .org
+    let mut fixed = false;
+    let mut definitions = HashMap::<&str, Span>::new();
+    // Whether data placed now continues the last section
+    let mut in_data = false;
+    // The symbol of the data label the data placed now belongs to
+    let mut data_label = None;
+
+    for item in &program.items {
+        match item {
+            ast::Item::Routine(definition) => {
+                in_data = false;
+                pass.log.push(format!(
+                    "{} \"{}\" @ {}",
+                    "Building routine".green(),
+                    definition.name.name.cyan(),
+                    format!("{:#06X}", instr_ptr).yellow()
+                ));
+                define(&mut definitions, &definition.name, "Routine", &mut pass.diagnostics);
+                let section = pass.sections.len();
+                pass.addresses.insert((None, &definition.name.name), (instr_ptr as u16, section));
+                let symbol = pass.symbols.len();
+                pass.symbols.push(Symbol {
+                    name: definition.name.name.clone(),
+                    kind: SymbolKind::Routine,
+                    value: instr_ptr as u16,
+                    size: None,
+                    routine: None,
+                    span: definition.name.span,
+                });
+                let span = definition.name.span;
+                pass.listing.push(Listed { address: instr_ptr as u16, words: Vec::new(), span });
+                context.routine = Some(section);
+                let relocated = pass.relocations.len();
+                let mut words = Vec::new();
+                let mut labels = HashMap::<&str, Span>::new();
+                for statement in &definition.body {
+                    match statement {
+                        ast::Statement::Label(label) => {
+                            define(&mut labels, label, "Label", &mut pass.diagnostics);
+                            let address = (instr_ptr + words.len()) as u16;
+                            pass.addresses.insert((Some(section), &label.name), (address, section));
+                            pass.symbols.push(Symbol {
+                                name: label.name.clone(),
+                                kind: SymbolKind::Label,
+                                value: address,
+                                size: None,
+                                routine: Some(definition.name.name.clone()),
+                                span: label.span,
+                            });
+                            pass.listing.push(Listed { address, words: Vec::new(), span: label.span });
+                        }
+                        ast::Statement::Instruction(instruction) => {
+                            let assembled = context.relocated(&mut pass.diagnostics, |context, diagnostics| {
+                                assemble_instruction(instruction, context, diagnostics)
+                            });
+                            match assembled {
+                                Ok((assembled, relocations)) => {
+                                    pass.relocations.extend(relocations.into_iter().map(
+                                        |(index, base, span)| (section, words.len() + index, base, span),
+                                    ));
+                                    let address = (instr_ptr + words.len()) as u16;
+                                    let span = instruction.span;
+                                    pass.listing.push(Listed { address, words: assembled.clone(), span });
+                                    words.extend(assembled);
+                                }
+                                Err(diagnostic) => pass.diagnostics.push(diagnostic),
+                            }
+                        }
+                    }
+                }
+                context.routine = None;
+
+                if instr_ptr + words.len() > limit(layout, region) {
+                    pass.diagnostics.push(Diagnostic::error(
+                        diagnostics::E_MEMORY_FULL,
+                        format!(
+                            "Routine `{}` doesn't fit into region `{}`",
+                            definition.name.name, layout.regions[region].name
+                        ),
+                        definition.span,
+                    ));
+                    pass.relocations.truncate(relocated);
+                    continue;
+                }
+                pass.symbols[symbol].size = Some(words.len());
+                let size = words.len() + 1;
+                pass.sections.push(object::Section {
+                    kind: SectionKind::Routine,
+                    name: definition.name.name.clone(),
+                    address: instr_ptr as u16,
+                    words,
+                    size,
+                    region: layout.regions[region].name.clone(),
+                    fixed,
+                    relocations: Vec::new(),
+                });
+                pass.spans.push(definition.span);
+                instr_ptr += size;
+            }
+            ast::Item::FileSystem(definition) => {
+                in_data = false;
+                pass.log.push(format!(
+                    "{} \"{}\" @ {}",
+                    "Building filesystem".magenta(),
+                    "Filesystem".cyan(),
+                    format!("{:#06X}", instr_ptr).yellow()
+                ));
+                let mut size = 0;
+                if let Some(value) = &definition.size {
+                    size = context.evaluate_absolute(value).unwrap_or_else(|diagnostic| {
+                        pass.diagnostics.push(diagnostic);
+                        0
+                    }) as usize;
+                    pass.log.push(format!("  -> Preallocating {} Bytes / {} Addresses", size * 2, size));
+                }
+                if instr_ptr + size > limit(layout, region) {
+                    pass.diagnostics.push(Diagnostic::error(
+                        diagnostics::E_MEMORY_FULL,
+                        format!("Filesystem doesn't fit into region `{}`", layout.regions[region].name),
+                        definition.span,
+                    ));
+                    continue;
+                }
+                pass.sections.push(object::Section {
+                    kind: SectionKind::FileSystem,
+                    name: "filesystem".to_string(),
+                    address: instr_ptr as u16,
+                    words: Vec::new(),
+                    size: size + 1,
+                    region: layout.regions[region].name.clone(),
+                    fixed,
+                    relocations: Vec::new(),
+                });
+                pass.spans.push(definition.span);
+                instr_ptr += size + 1;
+            }
+            ast::Item::Label(label) => {
+                pass.log.push(format!(
+                    "{} \"{}\" @ {}",
+                    "Placing data".blue(),
+                    label.name.cyan(),
+                    format!("{:#06X}", instr_ptr).yellow()
+                ));
+                define(&mut definitions, label, "Label", &mut pass.diagnostics);
+                if !in_data {
+                    let region = &layout.regions[region].name;
+                    pass.sections.push(data_section(&label.name, instr_ptr, region, fixed));
+                    pass.spans.push(label.span);
+                    in_data = true;
+                }
+                let section = pass.sections.len() - 1;
+                pass.addresses.insert((None, &label.name), (instr_ptr as u16, section));
+                data_label = Some(pass.symbols.len());
+                pass.symbols.push(Symbol {
+                    name: label.name.clone(),
+                    kind: SymbolKind::Data,
+                    value: instr_ptr as u16,
+                    size: Some(0),
+                    routine: None,
+                    span: label.span,
+                });
+                pass.listing.push(Listed { address: instr_ptr as u16, words: Vec::new(), span: label.span });
+            }
+            ast::Item::Data(definition) => {
+                if !in_data {
+                    let region = &layout.regions[region].name;
+                    pass.sections.push(data_section("data", instr_ptr, region, fixed));
+                    pass.spans.push(definition.span);
+                    in_data = true;
+                    data_label = None;
+                }
+                let section = pass.sections.len() - 1;
+                let assembled = context.relocated(&mut pass.diagnostics, |context, _| {
+                    assemble_data(definition, instr_ptr, context)
+                });
+                let (words, relocations) = assembled.unwrap_or_else(|diagnostic| {
+                    pass.diagnostics.push(diagnostic);
+                    (Vec::new(), Vec::new())
+                });
+                if instr_ptr + words.len() > limit(layout, region) {
+                    pass.diagnostics.push(Diagnostic::error(
+                        diagnostics::E_MEMORY_FULL,
+                        format!(
+                            "`{}` doesn't fit into region `{}`",
+                            definition.directive.name, layout.regions[region].name
+                        ),
+                        definition.span,
+                    ));
+                    continue;
+                }
+                if let Some(size) = data_label.and_then(|symbol| pass.symbols[symbol].size.as_mut()) {
+                    *size += words.len();
+                }
+                let span = definition.span;
+                pass.listing.push(Listed { address: instr_ptr as u16, words: words.clone(), span });
+                let data = &mut pass.sections[section];
+                let start = data.words.len();
+                pass.relocations.extend(
+                    relocations.into_iter().map(|(index, base, span)| (section, start + index, base, span)),
+                );
+                instr_ptr += words.len();
+                data.size += words.len();
+                data.words.extend(words);
+            }
+            ast::Item::Constant(constant) => {
+                define(&mut definitions, &constant.name, "Constant", &mut pass.diagnostics);
+                // Evaluated once here so constants that are never used are checked as well
+                match context.word(&constant.value) {
+                    Ok((value, base)) => {
+                        pass.constants.push((&constant.name.name, value, base));
+                        pass.symbols.push(Symbol {
+                            name: constant.name.name.clone(),
+                            kind: SymbolKind::Constant,
+                            value,
+                            size: None,
+                            routine: None,
+                            span: constant.name.span,
+                        });
+                    }
+                    Err(diagnostic) => pass.diagnostics.push(diagnostic),
+                }
+            }
+            ast::Item::Org(org) => {
+                in_data = false;
+                pointers[region] = instr_ptr;
+                let address = match context.evaluate_absolute(&org.address) {
+                    Ok(address) => address as usize,
+                    Err(diagnostic) => {
+                        pass.diagnostics.push(diagnostic);
+                        continue;
+                    }
+                };
+                match layout.containing(address) {
+                    Some(index) => {
+                        region = index;
+                        instr_ptr = address;
+                        fixed = true;
+                    }
+                    None => {
+                        let regions: Vec<String> = layout
+                            .regions
+                            .iter()
+                            .filter(|region| !region.reserved)
+                            .map(|region| {
+                                format!("`{}` at {:#06X} - {:#06X}", region.name, region.start, region.end)
+                            })
+                            .collect();
+                        pass.diagnostics.push(
+                            Diagnostic::error(
+                                diagnostics::E_LAYOUT,
+                                format!("{:#06X} isn't inside a region code and data can go into", address),
+                                org.span,
+                            )
+                            .with_note(format!("the layout has {}", regions.join(", "))),
+                        );
+                    }
+                }
+            }
+            ast::Item::Section(name) => {
+                in_data = false;
+                pointers[region] = instr_ptr;
+                match layout.find(&name.name) {
+                    Some(index) if !layout.regions[index].reserved => {
+                        region = index;
+                        instr_ptr = pointers[index];
+                        fixed = false;
+                    }
+                    Some(_) => pass.diagnostics.push(Diagnostic::error(
+                        diagnostics::E_LAYOUT,
+                        format!("Region `{}` is reserved, nothing can be placed into it", name.name),
+                        name.span,
+                    )),
+                    None => {
+                        let names: Vec<&str> =
+                            layout.regions.iter().map(|region| region.name.as_str()).collect();
+                        let mut diagnostic = Diagnostic::error(
+                            diagnostics::E_LAYOUT,
+                            format!("The layout has no region called `{}`", name.name),
+                            name.span,
+                        );
+                        if let Some(suggestion) = diagnostics::suggest(&name.name, &names) {
+                            diagnostic = diagnostic.with_note(format!("did you mean `{}`?", suggestion));
+                        }
+                        pass.diagnostics.push(diagnostic);
+                    }
+                }
+            }
+        }
+    }
+
+    pass.undefined = context.undefined;
+    pass
+}
+
+/// Returns the address after the last one of a region
+fn limit(layout: &Layout, region: usize) -> usize {
+    layout.regions[region].end as usize + 1
+}
+
+/// Returns an empty section for data placed at the address
+fn data_section(name: &str, address: usize, region: &str, fixed: bool) -> object::Section {
+    object::Section {
+        kind: SectionKind::Data,
+        name: name.to_string(),
+        address: address as u16,
+        words: Vec::new(),
+        size: 0,
+        region: region.to_string(),
+        fixed,
+        relocations: Vec::new(),
+    }
+}
+
+/// Adds a name to the definitions, reporting it if it was defined before
+fn define<'a>(
+    definitions: &mut HashMap<&'a str, Span>,
+    name: &'a ast::Ident,
+    what: &str,
+    diagnostics: &mut Diagnostics,
+) {
+    if let Some(first) = definitions.insert(&name.name, name.span) {
+        diagnostics.push(
+            Diagnostic::error(
+                diagnostics::E_DUPLICATE,
+                format!("{} `{}` is defined more than once", what, name.name),
+                name.span,
+            )
+            .with_note_at(format!("`{}` first defined here", name.name), first),
+        );
+    }
+}
+
+/// Returns the code that most likely keeps the addresses of a program from settling: the first
+/// size or address depending on a name, else the first alignment or change of the section
+fn unsettled(program: &ast::Program) -> Span {
+    let mut candidates = program.items.iter().filter_map(|item| match item {
+        ast::Item::FileSystem(filesystem) => {
+            filesystem.size.as_ref().map(|size| (uses_symbol(size), size.span))
+        }
+        ast::Item::Org(org) => Some((uses_symbol(&org.address), org.span)),
+        ast::Item::Data(data) => match &data.kind {
+            ast::DataKind::Fill { count, .. } => Some((uses_symbol(count), data.span)),
+            ast::DataKind::Align(_) => Some((false, data.span)),
+            _ => None,
+        },
+        ast::Item::Section(region) => Some((false, region.span)),
+        _ => None,
+    });
+    let first = candidates.clone().next().map(|(_, span)| span);
+    candidates.find(|(uses_symbol, _)| *uses_symbol).map(|(_, span)| span).or(first).unwrap_or_default()
+}
+
+/// Returns true if a value uses a routine, label or constant
+fn uses_symbol(value: &Value) -> bool {
+    match &value.kind {
+        ValueKind::Symbol(_) => true,
+        ValueKind::Unary(_, operand) => uses_symbol(operand),
+        ValueKind::Binary(_, left, right) => uses_symbol(left) || uses_symbol(right),
+        _ => false,
+    }
+}
+
+/// Reports each undefined symbol once, pointing at every place it is used
+fn report_undefined(pass: &Pass, constants: &HashMap<&str, &ast::Constant>, diagnostics: &mut Diagnostics) {
+    let mut undefined = Vec::<(Option<usize>, &str, Vec<Span>)>::new();
+    for (scope, name, span) in &pass.undefined {
+        match undefined.iter_mut().find(|(other_scope, other, _)| other_scope == scope && other == name) {
+            Some((_, _, uses)) if uses.contains(span) => {}
+            Some((_, _, uses)) => uses.push(*span),
+            None => undefined.push((*scope, name, vec![*span])),
+        }
+    }
+
+    for (scope, name, uses) in undefined {
+        let message = match scope {
+            Some(routine) => format!(
+                "Label `{}` is not defined in routine `{}`",
+                name,
+                pass.sections.get(routine).map_or("", |section| &section.name)
+            ),
+            None => format!("Routine, label or constant `{}` is not defined", name),
+        };
+        let mut names: Vec<&str> = pass
+            .addresses
+            .keys()
+            .filter(|(other_scope, _)| *other_scope == scope)
+            .map(|(_, name)| *name)
+            .chain(constants.keys().copied().filter(|_| scope.is_none()))
+            .collect();
+        names.sort_unstable();
+        let mut diagnostic = Diagnostic::error(diagnostics::E_UNDEFINED, message, uses[0]);
+        for span in &uses[1..] {
+            diagnostic = diagnostic.with_note_at(format!("`{}` is also used here", name), *span);
+        }
+        if let Some(suggestion) = diagnostics::suggest(name, &names) {
+            diagnostic = diagnostic.with_note(format!("did you mean `{}`?", suggestion));
+        }
+        diagnostics.push(diagnostic);
+    }
+}
+
+/// Returns the words a data directive places at the address
+fn assemble_data<'a>(
+    data: &'a ast::Data,
+    address: usize,
+    context: &mut Context<'a, '_>,
+) -> Result<Vec<u16>> {
+    match &data.kind {
+        ast::DataKind::Words(values) => context.evaluate_all(values),
+        ast::DataKind::String { text, length_prefixed } => {
+            let mut words = text
+                .value
+                .chars()
+                .map(|char| {
+                    u16::try_from(u32::from(char)).map_err(|_| {
+                        Diagnostic::error(
+                            diagnostics::E_OUT_OF_RANGE,
+                            format!("Character `{}` doesn't fit into a word", char),
+                            text.span,
+                        )
+                    })
+                })
+                .collect::<Result<Vec<u16>>>()?;
+            if *length_prefixed {
+                words.insert(0, words.len() as u16);
+            } else {
+                words.push(0);
+            }
+            Ok(words)
+        }
+        ast::DataKind::Fill { count, value } => {
+            Ok(vec![context.evaluate(value)?; context.evaluate_absolute(count)? as usize])
+        }
+        ast::DataKind::Align(value) => match context.evaluate_absolute(value)? as usize {
+            0 => Err(Diagnostic::error(diagnostics::E_OUT_OF_RANGE, "Alignment must not be 0", value.span)),
+            alignment => Ok(vec![0; (alignment - address % alignment) % alignment]),
+        },
+    }
+}
+
+/// Writes the regions every ROM starts out with
+pub fn preprogram(memory: &mut [u16]) {
+    // NOTE: ASCII
+    // Write A - Z to 0x0200
+    // Write a - z to 0x0220
+    for i in 0..26 {
+        memory[0x0200 + i] = 0x0041 + i as u16;
+        memory[0x0220 + i] = 0x0061 + i as u16;
+    }
+
+    for i in 0..9 {
+        memory[0x0240 + i] = 0x0030 + i as u16;
+    }
+
+    memory[0x021A] = 0x0021; // !
+    memory[0x021B] = 0x0022; // "
+    memory[0x021C] = 0x0023; // #
+    memory[0x021D] = 0x0024; // $
+    memory[0x021E] = 0x005B; // [
+    memory[0x021F] = 0x005C; // ]
+    memory[0x023A] = 0x005D; // /
+    memory[0x023B] = 0x003C; // <
+    memory[0x023C] = 0x003E; // >
+    memory[0x023D] = 0x003D; // =
+    memory[0x023E] = 0x002D; // -
+    memory[0x023F] = 0x007E; // ~
+    memory[0x024A] = 0x003A; // :
+    memory[0x024B] = 0x005F; // _
+    memory[0x024C] = 0x007C; // |
+    memory[0x024D] = 0x0026; // &
+    memory[0x024E] = 0x003F; // ?
+    memory[0x024F] = 0x0040; // @
+    memory[0x0250] = 0x0020; // [SPACE]
+    memory[0x0251] = 0x002E;
+
+    // NOTE: GPU BUFFER
+    // Filling GPU buffer with GPU NoOps
+    for i in 0..3328 {
+        memory[0x0300 + i] = opcodes::GPU_NO_OPERAT;
+    }
+}
+
+/// Evaluates values. Symbols are looked up in the addresses of the previous pass, the ones that
+/// aren't known evaluate to 0 and are remembered so they can be reported after the final pass.
+/// For object files, the ones that aren't known are imported instead, and every value is
+/// evaluated together with the address it depends on.
+struct Context<'a, 'b> {
+    addresses: &'b Addresses<'a>,
+    constants: &'b HashMap<&'a str, &'a ast::Constant>,
+    /// Whether an object file is assembled
+    object: bool,
+    /// Index of the section of the routine labels are looked up in
+    routine: Option<usize>,
+    /// Constants currently being evaluated, to catch the ones that depend on themselves
+    evaluating: Vec<&'a str>,
+    undefined: Vec<(Option<usize>, &'a str, Span)>,
+    /// What the values evaluated so far depend on, with where they are used
+    bases: Vec<(Base<'a>, Span)>,
+    /// Bases whose addresses are moved while assembling again, by one more than their index
+    shifts: Vec<Base<'a>>,
+}
+
+impl<'a> Context<'a, '_> {
+    /// Returns the word a value stands for. Negative values are stored as two's complement.
+    fn evaluate(&mut self, value: &'a Value) -> Result<u16> {
+        let (word, base) = self.word(value)?;
+        self.bases.extend(base.map(|base| (base, value.span)));
+        Ok(word)
+    }
+
+    fn evaluate_all(&mut self, values: &'a [Value]) -> Result<Vec<u16>> {
+        values.iter().map(|value| self.evaluate(value)).collect()
+    }
+
+    /// Returns the word a value stands for, which must not depend on where the linker places
+    /// anything, as it decides how much is placed
+    fn evaluate_absolute(&mut self, value: &'a Value) -> Result<u16> {
+        match self.word(value)? {
+            (word, None) => Ok(word),
+            (_, Some(_)) => Err(Diagnostic::error(
+                diagnostics::E_RELOCATION,
+                "This value decides how much is placed, so it can't depend on an address",
+                value.span,
+            )
+            .with_note("the address is only known once the object files are linked")),
+        }
+    }
+
+    /// Returns the word a value stands for with what it depends on
+    fn word(&mut self, value: &'a Value) -> Result<(u16, Option<Base<'a>>)> {
+        let (number, base) = self.number(value)?;
+        match number {
+            // Moved addresses may leave the range, their words are only compared
+            _ if base.is_some() && !self.shifts.is_empty() => Ok((number as u16, base)),
+            0..=0xFFFF => Ok((number as u16, base)),
+            -0x8000..=-1 => Ok((number as i16 as u16, base)),
+            _ => Err(Diagnostic::error(
+                diagnostics::E_OUT_OF_RANGE,
+                format!("Value {} doesn't fit into a word", number),
+                value.span,
+            )),
+        }
+    }
+
+    /// Returns the number a value stands for, without limiting it to a word, and the address it
+    /// depends on in object files
+    fn number(&mut self, value: &'a Value) -> Result<(i64, Option<Base<'a>>)> {
+        match &value.kind {
+            ValueKind::Hex(text) => Ok((text.chars().next().unwrap_or_default() as i64, None)),
+            ValueKind::Lit(text) => {
+                let digits = text.trim_start_matches("0x");
+                u16::from_str_radix(digits, 16).map(|number| (i64::from(number), None)).map_err(|_| {
+                    Diagnostic::error(
+                        diagnostics::E_INVALID_NUMBER,
+                        format!("`{}` is not a hexadecimal number between 0 and 0xFFFF", text),
+                        value.span,
+                    )
+                })
+            }
+            ValueKind::Num(text) => {
+                let (digits, radix, kind) = if let Some(digits) = text.strip_prefix("0x") {
+                    (digits, 16, "hexadecimal")
+                } else if let Some(digits) = text.strip_prefix("0b") {
+                    (digits, 2, "binary")
+                } else {
+                    (text.as_str(), 10, "decimal")
+                };
+                match u32::from_str_radix(digits, radix) {
+                    Ok(number) if number > 65535 => Err(Diagnostic::error(
+                        diagnostics::E_OUT_OF_RANGE,
+                        "Value too big, must not be bigger than 65535",
+                        value.span,
+                    )),
+                    Ok(number) => Ok((number as i64, None)),
+                    Err(_) => Err(Diagnostic::error(
+                        diagnostics::E_INVALID_NUMBER,
+                        format!("`{}` is not a {} number", text, kind),
+                        value.span,
+                    )),
+                }
+            }
+            ValueKind::Symbol(name) => self.symbol(name, value.span),
+            ValueKind::Unary(op, operand) => {
+                let (operand, base) = self.number(operand)?;
+                if base.is_some() {
+                    return Err(not_relocatable(value.span));
+                }
+                Ok((
+                    match op {
+                        UnaryOp::Negate => -operand,
+                        UnaryOp::Not => !operand & 0xFFFF,
+                        UnaryOp::High => (operand >> 8) & 0xFF,
+                        UnaryOp::Low => operand & 0xFF,
+                    },
+                    None,
+                ))
+            }
+            ValueKind::Binary(op, left, right) => {
+                let ((lhs, left_base), (rhs, right_base)) = (self.number(left)?, self.number(right)?);
+                // An address stays an address when a number is added or subtracted, and the
+                // distance between two addresses in the same section doesn't depend on either
+                let base = match (op, left_base, right_base) {
+                    (_, None, None) => None,
+                    (BinaryOp::Add, Some(base), None)
+                    | (BinaryOp::Add, None, Some(base))
+                    | (BinaryOp::Sub, Some(base), None) => Some(base),
+                    (BinaryOp::Sub, Some(left), Some(right)) if left == right => None,
+                    _ => return Err(not_relocatable(value.span)),
+                };
+                if matches!(op, BinaryOp::Div | BinaryOp::Rem) && rhs == 0 {
+                    let span = right.span;
+                    return Err(Diagnostic::error(diagnostics::E_OUT_OF_RANGE, "Division by zero", span));
+                }
+                let shift = |shift: fn(i64, u32) -> Option<i64>| {
+                    u32::try_from(rhs).ok().and_then(|rhs| shift(lhs, rhs)).ok_or_else(|| {
+                        Diagnostic::error(
+                            diagnostics::E_OUT_OF_RANGE,
+                            format!("Can't shift by {} bits, shifts go from 0 to 63", rhs),
+                            right.span,
+                        )
+                    })
+                };
+                let number = match op {
+                    BinaryOp::Add => lhs.wrapping_add(rhs),
+                    BinaryOp::Sub => lhs.wrapping_sub(rhs),
+                    BinaryOp::Mul => lhs.wrapping_mul(rhs),
+                    BinaryOp::Div => lhs.wrapping_div(rhs),
+                    BinaryOp::Rem => lhs.wrapping_rem(rhs),
+                    BinaryOp::Shl => shift(i64::checked_shl)?,
+                    BinaryOp::Shr => shift(i64::checked_shr)?,
+                    BinaryOp::And => lhs & rhs,
+                    BinaryOp::Or => lhs | rhs,
+                    BinaryOp::Xor => lhs ^ rhs,
+                };
+                Ok((number, base))
+            }
+        }
+    }
+
+    /// Returns the value of a constant or the address of a routine or label
+    fn symbol(&mut self, name: &'a str, span: Span) -> Result<(i64, Option<Base<'a>>)> {
+        // Labels start with a dot and belong to the routine they are defined in
+        let scope = if name.starts_with('.') { self.routine } else { None };
+        if let Some(constant) = self.constants.get(name).copied().filter(|_| scope.is_none()) {
+            if self.evaluating.contains(&name) {
+                return Err(Diagnostic::error(
+                    diagnostics::E_CIRCULAR,
+                    format!("Constant `{}` depends on itself", name),
+                    span,
+                )
+                .with_note_at(format!("`{}` is defined here", name), constant.span));
+            }
+            self.evaluating.push(name);
+            let routine = self.routine.take();
+            let result = self.number(&constant.value);
+            self.routine = routine;
+            self.evaluating.pop();
+            return result;
+        }
+        let base = match self.addresses.get(&(scope, name)) {
+            Some(&(address, _)) if !self.object => return Ok((address as i64, None)),
+            Some(&(address, section)) => (address as i64, Base::Section(section)),
+            None if self.object && scope.is_none() => (0, Base::Import(name)),
+            None => {
+                self.undefined.push((scope, name, span));
+                return Ok((0, None));
+            }
+        };
+        let shift = self.shifts.iter().position(|shifted| *shifted == base.1).map_or(0, |index| index + 1);
+        Ok((base.0 + shift as i64, Some(base.1)))
+    }
+
+    /// Assembles something and finds the words holding addresses the linker has to adjust, by
+    /// assembling it again with each address moved by a different distance. Returns the words and
+    /// the index of each word holding an address, with what the address depends on and where it
+    /// is used. Outside of object files, it's only assembled once.
+    fn relocated(
+        &mut self,
+        diagnostics: &mut Diagnostics,
+        assemble: impl Fn(&mut Self, &mut Diagnostics) -> Result<Vec<u16>>,
+    ) -> Result<(Vec<u16>, Relocations<'a>)> {
+        self.bases.clear();
+        let words = assemble(self, diagnostics)?;
+        let mut bases = Vec::<(Base<'a>, Span)>::new();
+        for (base, span) in std::mem::take(&mut self.bases) {
+            if !bases.iter().any(|(other, _)| *other == base) {
+                bases.push((base, span));
+            }
+        }
+        if bases.is_empty() {
+            return Ok((words, Vec::new()));
+        }
+
+        self.shifts = bases.iter().map(|(base, _)| *base).collect();
+        let shifted = assemble(self, &mut Diagnostics::default());
+        self.shifts.clear();
+        self.bases.clear();
+        let shifted = match shifted {
+            Ok(shifted) if shifted.len() == words.len() => shifted,
+            _ => return Err(not_relocatable(bases[0].1)),
+        };
+
+        let mut relocations = Vec::new();
+        for (index, (word, shifted)) in words.iter().zip(&shifted).enumerate() {
+            match shifted.wrapping_sub(*word) as usize {
+                0 => {}
+                difference => match bases.get(difference - 1) {
+                    Some(&(base, span)) => relocations.push((index, base, span)),
+                    None => return Err(not_relocatable(bases[0].1)),
+                },
+            }
+        }
+        // An address that ended up in no word was changed in a way the linker can't repeat
+        let unused = bases.iter().find(|(base, _)| relocations.iter().all(|(_, other, _)| other != base));
+        if let Some((_, span)) = unused {
+            return Err(not_relocatable(*span));
+        }
+        Ok((words, relocations))
+    }
+}
+
+/// Returns the error for a value the linker can't adjust once it placed the address it uses
+fn not_relocatable(span: Span) -> Diagnostic {
+    Diagnostic::error(
+        diagnostics::E_RELOCATION,
+        "This value uses an address in a way the linker can't adjust",
+        span,
+    )
+    .with_note(
+        "only an address plus or minus a number, or the distance between two addresses in the same \
+         section, can be used in object files",
+    )
+}
+
+/// Returns the palette index of a color, or the default if none was given
+fn color(color: &Option<ast::Ident>, default: u16) -> u16 {
+    color
+        .as_ref()
+        .and_then(|color| ast::color_index(&color.name))
+        .unwrap_or(default)
+}
+
+/// Returns the color byte of a style, the background color in the high and the foreground
+/// color in the low nibble
+fn color_byte(style: &Style) -> u16 {
+    (color(&style.background, 0x00) << 4) | color(&style.color, 0x0A)
+}
+
+/// Wraps GPU commands in setting and resetting the style's attributes if it has any
+fn with_attributes(style: &Style, mut words: Vec<u16>) -> Vec<u16> {
+    if style.attributes != 0 {
+        words.splice(0..0, [opcodes::GPU_SET_ATTR, style.attributes]);
+        words.extend([opcodes::GPU_SET_ATTR, 0]);
+    }
+    words
+}
+
+/// Warns about jumps into memory that holds data instead of code
+fn check_jump_address(address: u16, value: &Value, diagnostics: &mut Diagnostics) {
+    let region = if ASCII_TABLE.contains(&address) {
+        "the character codes at 0x0200 - 0x0251"
+    } else if GPU_BUFFER.contains(&address) {
+        "the GPU's command buffer at 0x0300 - 0x0FFF"
+    } else {
+        return;
+    };
+    diagnostics.push(
+        Diagnostic::warning(
+            diagnostics::W_RESERVED_JUMP,
+            format!("Jump to {:#06X}, which is reserved", address),
+            value.span,
+        )
+        .with_note(format!("the address is inside {}", region)),
+    );
+}
+
+/// Assembles a single instruction into the words it stands for
+fn assemble_instruction<'a>(
+    instruction: &'a ast::Instruction,
+    context: &mut Context<'a, '_>,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<u16>> {
+    let mnemonic = instruction.mnemonic.name.as_str();
+    let mut routine = Routine::default();
+
+    match &instruction.kind {
+        InstructionKind::Load { register, memory, value } => {
+            let instr = match (memory, register) {
+                (false, Register::A) => opcodes::LOAD_AREG,
+                (false, Register::X) => opcodes::LOAD_XREG,
+                (false, Register::Y) => opcodes::LOAD_YREG,
+                (true, Register::A) => opcodes::LOAD_AMEM,
+                (true, Register::X) => opcodes::LOAD_XMEM,
+                (true, Register::Y) => opcodes::LOAD_YMEM,
+            };
+            routine.instructions.extend([instr, context.evaluate(value)?]);
+        }
+        InstructionKind::Stor { register, address } => {
+            let instr = match register {
+                Register::A => opcodes::STOR_AREG,
+                Register::X => opcodes::STOR_XREG,
+                Register::Y => opcodes::STOR_YREG,
+            };
+            routine.instructions.extend([instr, context.evaluate(address)?]);
+        }
+        InstructionKind::Arithmetic { register, value } => {
+            let instr = match mnemonic {
+                "radd" => opcodes::INC_REG_V,
+                "rsub" => opcodes::DEC_REG_V,
+                "rmul" => opcodes::MUL_REG_V,
+                _ => opcodes::DIV_REG_V,
+            };
+            routine.instructions.extend([instr, register.code(), context.evaluate(value)?]);
+        }
+        InstructionKind::Comp { left, right } => {
+            let mut operand = |operand: &'a CompOperand| match operand {
+                CompOperand::Register(register) => Ok(register.code()),
+                CompOperand::Value(value) => context.evaluate(value),
+            };
+            routine.instructions.extend([opcodes::COMP_REGS, operand(left)?, operand(right)?]);
+        }
+        InstructionKind::Jump { target } => {
+            let instr = match mnemonic {
+                "jusr" => opcodes::JMP_TO_SR,
+                "jump" => opcodes::JMP_TO_AD,
+                "juie" => opcodes::JUMP_IFEQ,
+                _ => opcodes::JUMP_INEQ,
+            };
+            let address = context.evaluate(target)?;
+            check_jump_address(address, target, diagnostics);
+            routine.instructions.extend([instr, address]);
+        }
+        InstructionKind::Return => routine.instructions.push(opcodes::RET_TO_OR),
+        InstructionKind::Interrupt { handler } => {
+            let address = match handler {
+                Some(handler) => context.evaluate(handler)?,
+                None => 0x0000,
+            };
+            routine.instructions.extend([opcodes::SET_INT_V, address]);
+        }
+        InstructionKind::ReturnFromInterrupt => routine.instructions.push(opcodes::RET_FR_INT),
+        InstructionKind::SetValue { address, value } => {
+            let (address, value) = (context.evaluate(address)?, context.evaluate(value)?);
+            routine.instructions.extend([opcodes::STOR_IMMV, address, value]);
+        }
+        InstructionKind::Noop => routine.instructions.push(opcodes::NO_OPERAT),
+        InstructionKind::Halt => routine.instructions.push(opcodes::HALT_LOOP),
+        InstructionKind::Control { device, action } => match (device.name.as_str(), action.name.as_str()) {
+            ("gpu", "clear") => routine.push_gpu_words(&[opcodes::GPU_RES_F_BUF]),
+            ("gpu", "reset") => routine.push_gpu_words(&[opcodes::GPU_RESET_PTR]),
+            ("gpu", _) => routine.push_gpu_words(&[opcodes::GPU_UPDATE]),
+            (_, "reset") => routine.instructions.push(opcodes::NO_OPERAT),
+            _ => routine.instructions.push(opcodes::HALT_LOOP),
+        },
+        InstructionKind::DrawStr { text, style } => {
+            let color_byte = color_byte(style);
+            let mut words = Vec::new();
+            // Draw mode ends at a '`', so those and new lines leave it for their own commands
+            let mut draw_mode = false;
+            for char in text.value.chars() {
+                let char_byte = u8::try_from(char).map_err(|_| {
+                    Diagnostic::error(
+                        diagnostics::E_OUT_OF_RANGE,
+                        format!("Character `{}` can't be drawn, it doesn't fit into a byte", char),
+                        text.span,
+                    )
+                })?;
+                if draw_mode && (char == '`' || char == '\n') {
+                    words.push(0x60);
+                    draw_mode = false;
+                }
+                match char {
+                    '`' => words.extend([opcodes::GPU_DRAW_CHAR, (color_byte << 8) | char_byte as u16]),
+                    '\n' => words.push(opcodes::GPU_NEW_LINE),
+                    _ => {
+                        if !draw_mode {
+                            words.push(opcodes::GPU_DRAW_TEXT);
+                            draw_mode = true;
+                        }
+                        words.push((color_byte << 8) | char_byte as u16);
+                    }
+                }
+            }
+            if draw_mode {
+                words.push(0x60);
+            }
+            routine.push_gpu_words(&with_attributes(style, words));
+        }
+        InstructionKind::DrawVal { value, base, style } => {
+            let value = context.evaluate(value)?;
+            let words = vec![opcodes::GPU_DRAW_VALU, (color_byte(style) << 8) | base, value];
+            routine.push_gpu_words(&with_attributes(style, words));
+        }
+        InstructionKind::CursorMove { direction } => {
+            let instr = match direction.name.as_str() {
+                "up" => opcodes::GPU_MV_C_UP,
+                "do" => opcodes::GPU_MV_C_DOWN,
+                "le" => opcodes::GPU_MV_C_LEFT,
+                "ri" => opcodes::GPU_MV_C_RIGH,
+                _ => opcodes::GPU_NEW_LINE,
+            };
+            routine.push_gpu_words(&[instr, opcodes::GPU_UPDATE]);
+        }
+        InstructionKind::Scroll { direction, values } => {
+            let instr = match direction.name.as_str() {
+                "up" => opcodes::GPU_SCROLL_UP,
+                "do" => opcodes::GPU_SCROLL_DN,
+                _ => opcodes::GPU_SET_SCRL_R,
+            };
+            let mut words = vec![instr];
+            words.extend(context.evaluate_all(values)?);
+            routine.push_gpu_words(&words);
+        }
+        InstructionKind::VideoMode { mode } => {
+            let mode = match mode.name.as_str() {
+                "text" => 0,
+                "bmap" => 1,
+                _ => 2,
+            };
+            routine.push_gpu_words(&[opcodes::GPU_SET_MODE, mode]);
+        }
+        InstructionKind::Sprite { values, flags } => {
+            let mut sprite_flags = 0x0080;
+            if flags.flip_h {
+                sprite_flags |= 0x0010;
+            }
+            if flags.flip_v {
+                sprite_flags |= 0x0020;
+            }
+            if flags.hidden {
+                sprite_flags &= !0x0080;
+            }
+            if let Some(palette) = &flags.palette {
+                sprite_flags |= context.evaluate(palette)? & 0x000F;
+            }
+            let mut words = vec![opcodes::GPU_SET_SPRT];
+            words.extend(context.evaluate_all(values)?);
+            words.push(sprite_flags);
+            routine.push_gpu_words(&words);
+        }
+        InstructionKind::Gpu { values, style } => {
+            let mut arguments = context.evaluate_all(values)?;
+            let instr = match mnemonic {
+                "txtm" => opcodes::GPU_SET_TEXT,
+                "pixl" => opcodes::GPU_SET_PIXL,
+                "line" => opcodes::GPU_DRAW_LINE,
+                "rect" => opcodes::GPU_FILL_RECT,
+                "blit" => opcodes::GPU_BLIT_BMAP,
+                "glyf" => opcodes::GPU_DEF_GLYPH,
+                "font" => opcodes::GPU_LOAD_FONT,
+                "tile" => opcodes::GPU_SET_TILE,
+                "spal" => {
+                    if arguments[0] > 0x0F {
+                        return Err(Diagnostic::error(
+                            diagnostics::E_OUT_OF_RANGE,
+                            "Palette index must not be bigger than 15",
+                            values[0].span,
+                        ));
+                    }
+                    opcodes::GPU_SET_PALT
+                }
+                _ => {
+                    // cpos sets the collumn and the line with two separate commands
+                    arguments.insert(1, opcodes::GPU_SET_C_Y);
+                    opcodes::GPU_SET_C_X
+                }
+            };
+            let mut words = vec![instr];
+            words.extend(arguments);
+            if matches!(mnemonic, "pixl" | "line" | "rect") {
+                words.push(color(&style.color, 0x0A));
+            }
+            routine.push_gpu_words(&words);
+        }
+    }
+
+    Ok(routine.instructions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Options;
+    use crate::source::SourceFile;
+    use crate::{lexer, parser};
+
+    fn assemble_text(text: &str) -> (Option<Image>, Diagnostics) {
+        assemble_into(text, &Layout::default())
+    }
+
+    fn assemble_into(text: &str, layout: &Layout) -> (Option<Image>, Diagnostics) {
+        let sources = [SourceFile { path: "main.rvmasm".to_string(), text: text.to_string() }];
+        let mut diagnostics = Diagnostics::default();
+        let program = parser::parse(&lexer::tokenize(text, 0), &sources, &mut diagnostics);
+        (assemble(&program, layout, &mut diagnostics), diagnostics)
+    }
+
+    #[test]
+    fn routines_are_placed_after_the_jump_to_entry() {
+        let (image, diagnostics) = assemble_text(
+            "routine: add\nradd A num 1\nrtor\nend\n\
+            routine: entry\nload A num 10\njusr add\nend\n",
+        );
+        assert!(diagnostics.list.is_empty());
+        let image = image.unwrap();
+        assert_eq!(image.memory[ENTRY_TRAMPOLINE..ENTRY_TRAMPOLINE + 2], [opcodes::JMP_TO_SR, 0x1007]);
+        assert_eq!(image.memory[CODE_START + 3], opcodes::RET_TO_OR);
+        assert_eq!(
+            image.memory[0x1007..0x100C],
+            [opcodes::LOAD_AREG, 10, opcodes::JMP_TO_SR, CODE_START as u16, 0x0000]
+        );
+    }
+
+    #[test]
+    fn every_bad_value_is_reported() {
+        let (image, diagnostics) = assemble_text("routine: entry\nload A num 65536\nload A lit 0xG\nend\n");
+        assert!(image.is_none());
+        let codes: Vec<&str> = diagnostics.list.iter().map(|diagnostic| diagnostic.code).collect();
+        assert_eq!(codes, [diagnostics::E_OUT_OF_RANGE, diagnostics::E_INVALID_NUMBER]);
+        let span = diagnostics.list[0].span;
+        assert_eq!((span.line, span.column), (2, 8));
+    }
+
+    #[test]
+    fn duplicate_routines_point_at_the_first_one() {
+        let (image, diagnostics) = assemble_text("routine: a\nend\nroutine: a\nend\n");
+        assert!(image.is_none());
+        let diagnostic = &diagnostics.list[0];
+        assert_eq!((diagnostic.code, diagnostic.span.line), (diagnostics::E_DUPLICATE, 3));
+        assert_eq!(diagnostic.notes[0].span.map(|span| span.line), Some(1));
+    }
+
+    #[test]
+    fn later_routines_can_be_jumped_to() {
+        let (image, diagnostics) =
+            assemble_text("routine: entry\njump later\nintr later\nend\nroutine: later\nend\n");
+        assert!(diagnostics.list.is_empty());
+        let image = image.unwrap();
+        assert_eq!(image.memory[CODE_START + 1], 0x1007);
+        assert_eq!(image.memory[CODE_START + 2..CODE_START + 4], [opcodes::SET_INT_V, 0x1007]);
+    }
+
+    #[test]
+    fn undefined_routines_are_reported_once() {
+        let (image, diagnostics) =
+            assemble_text("routine: entry\njump lator\njusr lator\nend\nroutine: later\nend\n");
+        assert!(image.is_none());
+        let [diagnostic] = &diagnostics.list[..] else { panic!("{:?}", diagnostics.list) };
+        assert_eq!((diagnostic.code, diagnostic.span.line), (diagnostics::E_UNDEFINED, 2));
+        assert_eq!(diagnostic.notes[0].span.map(|span| span.line), Some(3));
+        assert_eq!(diagnostic.notes[1].message, "did you mean `later`?");
+    }
+
+    #[test]
+    fn labels_belong_to_their_routine() {
+        let (image, diagnostics) = assemble_text(
+            "routine: first\n.loop: rtor\nend\n\
+            routine: entry\nrtor\n.loop:\njump .loop\nend\n",
+        );
+        assert!(diagnostics.list.is_empty());
+        let image = image.unwrap();
+        assert_eq!(image.memory[0x1004..0x1008], [opcodes::RET_TO_OR, opcodes::JMP_TO_AD, 0x1005, 0x0000]);
+    }
+
+    #[test]
+    fn labels_of_other_routines_are_undefined() {
+        let (_, diagnostics) = assemble_text(
+            "routine: first\n.done: rtor\n.done:\nend\n\
+            routine: entry\njump .dome\nend\n",
+        );
+        let [undefined, duplicate] = &diagnostics.list[..] else { panic!("{:?}", diagnostics.list) };
+        assert_eq!((duplicate.code, duplicate.span.line), (diagnostics::E_DUPLICATE, 3));
+        assert_eq!(undefined.message, "Label `.dome` is not defined in routine `entry`");
+        assert!(undefined.notes.is_empty());
+    }
+
+    #[test]
+    fn jumps_into_data_are_warned_about() {
+        let (image, diagnostics) =
+            assemble_text("routine: entry\njump lit 0x0300\njusr lit 0x0251\njuie num 4096\nend\n");
+        let image = image.unwrap();
+        assert_eq!(
+            image.memory[CODE_START..CODE_START + 6],
+            [opcodes::JMP_TO_AD, 0x0300, opcodes::JMP_TO_SR, 0x0251, opcodes::JUMP_IFEQ, 0x1000]
+        );
+        let warnings: Vec<(&str, usize)> =
+            diagnostics.list.iter().map(|diagnostic| (diagnostic.code, diagnostic.span.line)).collect();
+        assert_eq!(warnings, [(diagnostics::W_RESERVED_JUMP, 2), (diagnostics::W_RESERVED_JUMP, 3)]);
+        assert_eq!(
+            diagnostics.list[0].notes[0].message,
+            "the address is inside the GPU's command buffer at 0x0300 - 0x0FFF"
+        );
+    }
+
+    #[test]
+    fn values_are_set_without_a_register() {
+        let (image, diagnostics) = assemble_text("routine: entry\nsetv lit 0x2000 num 42\nnoop\nhalt\nend\n");
+        assert!(diagnostics.list.is_empty());
+        assert_eq!(
+            image.unwrap().memory[CODE_START..CODE_START + 5],
+            [opcodes::STOR_IMMV, 0x2000, 42, opcodes::NO_OPERAT, opcodes::HALT_LOOP]
+        );
+    }
+
+    #[test]
+    fn registers_are_the_ones_of_the_vm() {
+        let (image, diagnostics) = assemble_text(
+            "routine: entry\nload X num 1\nload Y mem lit 0x2000\nstor Y lit 0x2001\nradd X num 2\n\
+            comp reg X reg Y\nend\n",
+        );
+        assert!(diagnostics.list.is_empty());
+        assert_eq!(
+            image.unwrap().memory[CODE_START..CODE_START + 12],
+            [
+                opcodes::LOAD_XREG,
+                1,
+                opcodes::LOAD_YMEM,
+                0x2000,
+                opcodes::STOR_YREG,
+                0x2001,
+                opcodes::INC_REG_V,
+                0x0058,
+                2,
+                opcodes::COMP_REGS,
+                0x0058,
+                0x0059
+            ]
+        );
+        assert_eq!([opcodes::LOAD_XREG, opcodes::LOAD_YREG, opcodes::LOAD_XMEM], [0x00A2, 0x00A3, 0x00A7]);
+        assert_eq!([opcodes::LOAD_YMEM, opcodes::STOR_XREG, opcodes::STOR_YREG], [0x00A8, 0x00B2, 0x00B3]);
+    }
+
+    /// Returns the words a routine starting at the code's start pushes to the GPU's command ring
+    fn gpu_words(memory: &[u16]) -> Vec<u16> {
+        memory[CODE_START..]
+            .chunks(3)
+            .take_while(|chunk| chunk[0] == opcodes::LOAD_GREG && chunk[2] == opcodes::STOR_GREG)
+            .map(|chunk| chunk[1])
+            .collect()
+    }
+
+    #[test]
+    fn backticks_and_new_lines_leave_draw_mode() {
+        let (image, diagnostics) = assemble_text("routine: entry\ndraw str \"a`\\nb\"\nend\n");
+        assert!(diagnostics.list.is_empty());
+        assert_eq!(
+            gpu_words(&image.unwrap().memory),
+            [
+                opcodes::GPU_DRAW_TEXT,
+                0x0A61,
+                0x60,
+                opcodes::GPU_DRAW_CHAR,
+                0x0A60,
+                opcodes::GPU_NEW_LINE,
+                opcodes::GPU_DRAW_TEXT,
+                0x0A62,
+                0x60
+            ]
+        );
+    }
+
+    #[test]
+    fn characters_outside_of_a_byte_are_reported() {
+        let (_, diagnostics) = assemble_text("routine: entry\ndraw str \"\\u{263A}\"\nend\n");
+        assert_eq!(diagnostics.list[0].code, diagnostics::E_OUT_OF_RANGE);
+    }
+
+    #[test]
+    fn data_is_placed_in_order() {
+        let (image, diagnostics) = assemble_text(
+            ".word num 1\ntable: .words num 2 hex A\n.align num 8\nfilled:\n.fill num 2 lit 0xFFFF\n\
+            text: .string \"\\n\\u{E9}\" len\n.string \"a\"\n\
+            routine: entry\nload A mem lit 0x1000\njump filled\nend\n",
+        );
+        assert!(diagnostics.list.is_empty());
+        let memory = &image.unwrap().memory;
+        assert_eq!(memory[CODE_START..CODE_START + 3], [1, 2, 0x41]);
+        assert_eq!(memory[CODE_START + 3..0x1008], [0; 3]);
+        assert_eq!(memory[0x1008..0x100F], [0xFFFF, 0xFFFF, 2, 0x0A, 0xE9, 0x61, 0]);
+        assert_eq!(memory[0x100F..0x1013], [opcodes::LOAD_AMEM, 0x1000, opcodes::JMP_TO_AD, 0x1008]);
+    }
+
+    #[test]
+    fn data_labels_share_names_with_routines() {
+        let (_, diagnostics) = assemble_text("entry: .word num 1\nroutine: entry\nend\n.align num 0\n");
+        let [duplicate, alignment] = &diagnostics.list[..] else { panic!("{:?}", diagnostics.list) };
+        assert_eq!(duplicate.message, "Routine `entry` is defined more than once");
+        assert_eq!(duplicate.notes[0].span.map(|span| span.line), Some(1));
+        assert_eq!(alignment.message, "Alignment must not be 0");
+    }
+
+    /// Assembles code that must assemble and returns the words placed at the start of the code
+    fn words(code: &str, count: usize) -> Vec<u16> {
+        let (image, diagnostics) = assemble_text(code);
+        assert!(diagnostics.list.is_empty(), "{:?}", diagnostics.list);
+        image.unwrap().memory[CODE_START..CODE_START + count].to_vec()
+    }
+
+    #[test]
+    fn operators_bind_by_precedence() {
+        let code = ".words 1 + 2 * 3 (1 + 2) * 3 1 << 2 + 1 6 | 1 & 2 5 ^ 3 & 1 7-2-1 16 / 4 / 2 17 % 5 * 2";
+        assert_eq!(words(code, 8), [7, 9, 8, 6, 4, 4, 2, 4]);
+    }
+
+    #[test]
+    fn unary_operators_bind_tightest() {
+        let code = ".equ HALF 0x1234 / 2\n.words ~0 -2 * 3 -(1 + 1) hi(0x1234) lo(0x1234) HALF + 1";
+        assert_eq!(words(code, 6), [0xFFFF, 0xFFFA, 0xFFFE, 0x12, 0x34, 0x091B]);
+    }
+
+    #[test]
+    fn strings_of_one_character_are_values() {
+        let code = ".equ NEW_LINE \"\\n\"\n.words hex \"A\" hex A NEW_LINE \"\\x41\" + 1 \"\\u{E9}\"";
+        assert_eq!(words(code, 5), [0x41, 0x41, 0x0A, 0x42, 0xE9]);
+
+        for (code, found) in [(".equ GREETING \"Hi\"", 2), (".word hex \"\"", 0)] {
+            let diagnostics = failing(code);
+            assert_eq!(diagnostics.list.len(), 1, "{}", code);
+            assert_eq!(diagnostics.list[0].code, diagnostics::E_SYNTAX);
+            let message = format!("Expected a string of one character, found {} characters", found);
+            assert_eq!(diagnostics.list[0].message, message);
+        }
+    }
+
+    #[test]
+    fn shifts_stay_inside_a_number() {
+        assert_eq!(words(".words 1 << 15 0x8000 >> 15 (1 << 63) >> 63", 3), [0x8000, 1, 0xFFFF]);
+        for code in [".word 1 << 64", ".word 1 >> 64", ".word 1 << -1"] {
+            let diagnostics = failing(code);
+            assert_eq!(diagnostics.list.len(), 1, "{}", code);
+            assert_eq!(diagnostics.list[0].code, diagnostics::E_OUT_OF_RANGE);
+            assert_eq!(diagnostics.list[0].span.column, code.rfind(' ').unwrap() + 2);
+        }
+    }
+
+    #[test]
+    fn deeply_nested_values_are_reported() {
+        let nested = |depth: usize| format!(".word {}1{}\n", "(".repeat(depth), ")".repeat(depth));
+        let chain = |length: usize| format!(".word 0{}\n", " + 1".repeat(length));
+        assert_eq!(words(&nested(parser::MAX_NESTING - 1), 1), [1]);
+        assert_eq!(words(&chain(parser::MAX_NESTING - 1), 1), [parser::MAX_NESTING as u16 - 1]);
+        let deep = [
+            nested(parser::MAX_NESTING),
+            nested(5000),
+            chain(5000),
+            format!(".word {}1\n", "-~".repeat(2500)),
+        ];
+        for code in deep {
+            let diagnostics = failing(&code);
+            assert_eq!(diagnostics.list.len(), 1);
+            assert_eq!(diagnostics.list[0].code, diagnostics::E_SYNTAX);
+            assert_eq!(
+                diagnostics.list[0].message,
+                "Values can't nest more than 64 operators and parentheses"
+            );
+        }
+    }
+
+    #[test]
+    fn spaces_tell_values_apart() {
+        assert_eq!(words(".words 5 -1 5 - 1 5-1 5- 1", 5), [5, 0xFFFF, 4, 4, 4]);
+        let diagnostics = failing("routine: entry\n    load A num 1 -1\nend\n");
+        assert_eq!(diagnostics.list.len(), 1);
+        assert_eq!(diagnostics.list[0].code, diagnostics::E_SYNTAX);
+    }
+
+    #[test]
+    fn constants_and_labels_are_used_before_their_definition() {
+        let code = ".words num SIZE end - start\nstart: .fill SIZE 0\nend:\n.equ SIZE 0b11";
+        assert_eq!(words(code, 2), [3, 3]);
+    }
+
+    #[test]
+    fn org_places_what_follows_at_the_address() {
+        let (image, diagnostics) = assemble_text(
+            "routine: print\nrtor\nend\n.org 0x2000\nroutine: entry\njusr print\nend\n\
+            routine: after\nrtor\nend\n",
+        );
+        assert!(diagnostics.list.is_empty());
+        let memory = &image.unwrap().memory;
+        assert_eq!(memory[ENTRY_TRAMPOLINE..ENTRY_TRAMPOLINE + 2], [opcodes::JMP_TO_SR, 0x2000]);
+        assert_eq!(memory[CODE_START], opcodes::RET_TO_OR);
+        assert_eq!(memory[0x2000..0x2002], [opcodes::JMP_TO_SR, CODE_START as u16]);
+        assert_eq!(memory[0x2003], opcodes::RET_TO_OR);
+    }
+
+    #[test]
+    fn overlapping_code_is_reported() {
+        let (image, diagnostics) = assemble_text(
+            "routine: print\nload A num 1\nrtor\nend\n.org 0x1003\nroutine: entry\nrtor\nend\n",
+        );
+        assert!(image.is_none());
+        assert_eq!(diagnostics.list.len(), 1);
+        assert_eq!(diagnostics.list[0].code, diagnostics::E_LAYOUT);
+        assert_eq!(
+            diagnostics.list[0].message,
+            "Routine `entry` at 0x1003 - 0x1003 overlaps routine `print` at 0x1002 - 0x1004"
+        );
+
+        for address in ["0x02F4", "0xE000", "0xE500", "0xF100"] {
+            let (image, diagnostics) =
+                assemble_text(&format!(".org {}\nroutine: entry\nrtor\nend\n", address));
+            assert!(image.is_none(), "{}", address);
+            assert_eq!(diagnostics.list[0].code, diagnostics::E_LAYOUT);
+        }
+        let (_, diagnostics) = assemble_text("routine: entry\nrtor\nend\n.org 0xDFFF\n.words num 1 num 2\n");
+        assert_eq!(diagnostics.list[0].code, diagnostics::E_MEMORY_FULL);
+        let (image, diagnostics) = assemble_text(".org 0xE540\nroutine: entry\nrtor\nend\n");
+        assert!(diagnostics.list.is_empty());
+        assert_eq!(image.unwrap().memory[0xE540], opcodes::RET_TO_OR);
+
+        let (_, diagnostics) = assemble_text(".org 0x0F00\nroutine: entry\nrtor\nend\n");
+        assert_eq!(diagnostics.list[0].code, diagnostics::E_LAYOUT);
+        assert_eq!(diagnostics.list[0].message, "0x0F00 isn't inside a region code and data can go into");
+    }
+
+    #[test]
+    fn sections_continue_where_their_region_ended() {
+        let layout = Layout::read(
+            "region code 0x1002 0x7FFF\nregion stack 0x8000 0x8FFF reserved\nregion data 0x9000 0xDFFF\n",
+        )
+        .unwrap();
+        let (image, diagnostics) = assemble_into(
+            ".section data\nfirst: .word num 7\n.section code\nroutine: entry\nload A mem second\nend\n\
+            .section data\nsecond: .word num 8\n",
+            &layout,
+        );
+        assert!(diagnostics.list.is_empty());
+        let memory = &image.unwrap().memory;
+        assert_eq!(memory[0x9000..0x9002], [7, 8]);
+        assert_eq!(memory[CODE_START..CODE_START + 2], [opcodes::LOAD_AMEM, 0x9001]);
+
+        let (_, diagnostics) = assemble_into(".section stack\n.section dta\n", &layout);
+        let messages: Vec<&str> =
+            diagnostics.list.iter().map(|diagnostic| diagnostic.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Region `stack` is reserved, nothing can be placed into it",
+                "The layout has no region called `dta`"
+            ]
+        );
+        assert_eq!(diagnostics.list[1].notes[0].message, "did you mean `data`?");
+    }
+
+    /// Assembles code that must fail and prints its diagnostics, which must not panic
+    fn failing(code: &str) -> Diagnostics {
+        let diagnostics = crate::assemble(code, &Options::default()).err().expect("code must not assemble");
+        diagnostics.report();
+        diagnostics
+    }
+
+    #[test]
+    fn unsettled_filesystem_size_is_reported() {
+        let diagnostics = failing("filesys:\n    size = b\nend\nroutine: entry\n    halt\nend\nb: .word 5\n");
+        assert_eq!(diagnostics.list.len(), 1);
+        assert_eq!(diagnostics.list[0].code, diagnostics::E_CIRCULAR);
+        assert_eq!(diagnostics.list[0].span.line, 2);
+    }
+
+    #[test]
+    fn unsettled_org_is_reported() {
+        let diagnostics = failing("routine: entry\n    halt\nend\n.org b + 1\nb: .word 5\n");
+        assert_eq!(diagnostics.list.len(), 1);
+        assert_eq!(diagnostics.list[0].code, diagnostics::E_CIRCULAR);
+        assert_eq!(diagnostics.list[0].span.line, 4);
+    }
+
+    #[test]
+    fn items_after_an_overflow_are_placed() {
+        let diagnostics = failing(
+            "routine: entry\n    load A num b\n    halt\nend\nfilesys:\n    size = 0xF000\nend\nb: .word 5\n",
+        );
+        assert_eq!(diagnostics.list.len(), 1);
+        assert_eq!(diagnostics.list[0].code, diagnostics::E_MEMORY_FULL);
+        assert_eq!(diagnostics.list[0].span.line, 5);
+    }
+}
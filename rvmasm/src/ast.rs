@@ -0,0 +1,266 @@
+use crate::source::Span;
+
+/// A parsed .rvmasm file
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    pub items: Vec<Item>,
+}
+
+/// Everything that can appear at the top level of a file
+#[derive(Debug, Clone)]
+pub enum Item {
+    Routine(Routine),
+    FileSystem(FileSystem),
+    /// ```<name>:```, naming the address of the data after it
+    Label(Ident),
+    Data(Data),
+    /// ```.equ <name> <value>```
+    Constant(Constant),
+    /// ```.org <address>```, placing what follows at the address
+    Org(Org),
+    /// ```.section <region>```, placing what follows into a region of the layout
+    Section(Ident),
+}
+
+#[derive(Debug, Clone)]
+pub struct Org {
+    pub address: Value,
+    pub span: Span,
+}
+
+/// A name standing for a value
+#[derive(Debug, Clone)]
+pub struct Constant {
+    pub name: Ident,
+    pub value: Value,
+    pub span: Span,
+}
+
+/// ```routine: <name>``` up to its ```end```
+#[derive(Debug, Clone)]
+pub struct Routine {
+    pub name: Ident,
+    pub body: Vec<Statement>,
+    pub span: Span,
+}
+
+/// A line of a routine's body
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    /// ```.<name>:```, marking an address only the routine it is in can jump to
+    Label(Ident),
+    Instruction(Box<Instruction>),
+}
+
+/// ```filesys:``` up to its ```end```
+#[derive(Debug, Clone)]
+pub struct FileSystem {
+    pub size: Option<Value>,
+    pub span: Span,
+}
+
+/// A data directive placing constant words into the memory
+#[derive(Debug, Clone)]
+pub struct Data {
+    pub directive: Ident,
+    pub kind: DataKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum DataKind {
+    /// ```.word <value>``` and ```.words <value> <value> ...```
+    Words(Vec<Value>),
+    /// ```.string "<text>" [zero|len]```, followed by a 0 or preceded by its length
+    String { text: Text, length_prefixed: bool },
+    /// ```.fill <count> <value>```
+    Fill { count: Value, value: Value },
+    /// ```.align <value>```, padding with zeros up to the next multiple of the value
+    Align(Value),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+}
+
+/// A value with its type annotation, e.g. ```lit 0x0300```, ```num 42``` or ```hex A```, a
+/// plain number, a symbol, or a constant expression combining them like ```screen + 45*2```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Value {
+    pub kind: ValueKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueKind {
+    /// Hexadecimal number, with or without 0x
+    Lit(String),
+    /// Decimal number, or a plain number that can also be hexadecimal with 0x or binary with 0b
+    Num(String),
+    /// The character code of the first character
+    Hex(String),
+    /// The address of a routine or label, or the value of a constant
+    Symbol(String),
+    Unary(UnaryOp, Box<Value>),
+    Binary(BinaryOp, Box<Value>, Box<Value>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    /// ```-```
+    Negate,
+    /// ```~```
+    Not,
+    /// ```hi()```, the high byte
+    High,
+    /// ```lo()```, the low byte
+    Low,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Shl,
+    Shr,
+    And,
+    Or,
+    Xor,
+}
+
+/// The raw text following ```str```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Text {
+    pub value: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    A,
+    X,
+    Y,
+}
+
+impl Register {
+    /// The register's value as an operand, which is the character code of its name
+    pub fn code(self) -> u16 {
+        match self {
+            Register::A => 0x0041,
+            Register::X => 0x0058,
+            Register::Y => 0x0059,
+        }
+    }
+}
+
+/// The optional ```col```, ```bg```, ```bold```, ```inv``` and ```blink``` arguments
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Style {
+    pub color: Option<Ident>,
+    pub background: Option<Ident>,
+    pub attributes: u16,
+}
+
+/// An operand of ```comp```
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompOperand {
+    Register(Register),
+    Value(Value),
+}
+
+/// The optional arguments of ```sprt```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpriteFlags {
+    pub flip_h: bool,
+    pub flip_v: bool,
+    pub hidden: bool,
+    pub palette: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    pub mnemonic: Ident,
+    pub kind: InstructionKind,
+    pub span: Span,
+}
+
+/// The operands of an instruction. Instructions sharing a shape share a variant, their
+/// mnemonic tells them apart.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstructionKind {
+    /// ```load <register> [mem] <value>```
+    Load { register: Register, memory: bool, value: Value },
+    /// ```stor <register> <address>```
+    Stor { register: Register, address: Value },
+    /// ```radd```, ```rsub```, ```rmul``` and ```rdiv```
+    Arithmetic { register: Register, value: Value },
+    /// ```comp <operand> <operand>```
+    Comp { left: CompOperand, right: CompOperand },
+    /// ```jump```, ```jusr```, ```juie``` and ```juin```
+    Jump { target: Value },
+    /// ```rtor```
+    Return,
+    /// ```intr <routine>|off```
+    Interrupt { handler: Option<Value> },
+    /// ```rtin```
+    ReturnFromInterrupt,
+    /// ```setv <address> <value>```
+    SetValue { address: Value, value: Value },
+    /// ```noop```
+    Noop,
+    /// ```halt```
+    Halt,
+    /// ```ctrl gpu|cpu <action>```
+    Control { device: Ident, action: Ident },
+    /// ```draw str <text> [style]```
+    DrawStr { text: Text, style: Style },
+    /// ```draw val <value> [bin|dec|hex] [style]```
+    DrawVal { value: Value, base: u16, style: Style },
+    /// ```cmov up|do|le|ri|nl```
+    CursorMove { direction: Ident },
+    /// ```scrl up|do <lines>``` and ```scrl reg <top> <bottom>```
+    Scroll { direction: Ident, values: Vec<Value> },
+    /// ```vmod text|bmap|tile```
+    VideoMode { mode: Ident },
+    /// ```sprt <index> <x> <y> <tile> [fliph] [flipv] [hide] [pal <value>]```
+    Sprite { values: Vec<Value>, flags: SpriteFlags },
+    /// GPU commands taking a fixed number of values and an optional color: ```txtm```,
+    /// ```pixl```, ```line```, ```rect```, ```blit```, ```glyf```, ```font```, ```tile```,
+    /// ```spal``` and ```cpos```
+    Gpu { values: Vec<Value>, style: Style },
+}
+
+/// Every color name, in palette order
+pub const COLORS: [&str; 16] = [
+    "black", "darkgray", "gray", "orange", "yellow", "brown", "pink", "purple", "navy", "darkgreen",
+    "white", "red", "green", "blue", "cyan", "magenta",
+];
+
+/// Returns the palette index of a color name
+pub fn color_index(name: &str) -> Option<u16> {
+    let index = match name {
+        "black" => 0x00,
+        "darkgray" => 0x01,
+        "gray" => 0x02,
+        "orange" => 0x03,
+        "yellow" => 0x04,
+        "brown" => 0x05,
+        "pink" => 0x06,
+        "purple" => 0x07,
+        "navy" => 0x08,
+        "darkgreen" => 0x09,
+        "white" => 0x0A,
+        "red" => 0x0B,
+        "green" => 0x0C,
+        "blue" => 0x0D,
+        "cyan" => 0x0E,
+        "magenta" => 0x0F,
+        _ => return None,
+    };
+    Some(index)
+}
@@ -0,0 +1,98 @@
+use crate::assembler::{Image, Listed, Symbol, SymbolKind};
+use crate::source::{Expansion, Span};
+use std::fmt::Write;
+
+/// First line of every debug info file, followed by the format version
+const MAGIC: &str = "RVMD";
+const VERSION: u16 = 1;
+
+/// Where the words at an address were assembled from
+#[derive(Debug, Clone)]
+pub struct DebugEntry {
+    pub address: u16,
+    /// Number of words assembled from the code
+    pub len: usize,
+    /// The line of code written in the program. Code from a macro points at the call of the macro.
+    pub span: Span,
+    /// The routine the words are in
+    pub routine: Option<String>,
+    /// The last label of the routine before the words
+    pub label: Option<String>,
+}
+
+/// Returns where every word placed by an instruction or data directive was assembled from, ordered
+/// by address
+pub fn debug_info(listing: &[Listed], symbols: &[Symbol], expansions: &[Expansion]) -> Vec<DebugEntry> {
+    let mut entries: Vec<DebugEntry> = listing
+        .iter()
+        .filter(|listed| !listed.words.is_empty())
+        .map(|listed| {
+            let mut span = listed.span;
+            while let Some(expansion) = span.expansion.and_then(|index| expansions.get(index as usize)) {
+                span = expansion.call;
+            }
+            let address = listed.address;
+            let routine = symbols.iter().find(|symbol| {
+                symbol.kind == SymbolKind::Routine
+                    && symbol.value <= address
+                    && (address as usize) < symbol.value as usize + symbol.size.unwrap_or_default()
+            });
+            let label = routine.and_then(|routine| {
+                let labels = symbols.iter().filter(|symbol| symbol.routine.as_ref() == Some(&routine.name));
+                labels.filter(|label| label.value <= address).max_by_key(|label| label.value)
+            });
+            DebugEntry {
+                address,
+                len: listed.words.len(),
+                span,
+                routine: routine.map(|routine| routine.name.clone()),
+                label: label.map(|label| label.name.clone()),
+            }
+        })
+        .collect();
+    entries.sort_by_key(|entry| entry.address);
+    entries
+}
+
+/// Returns the text of the debug info file of an image, which the VM reads from ```ROM.dbg```
+/// next to the ROM. After the version, every source file is listed as ```file <index> <path>```,
+/// then every entry as ```<address> <words> <file index> <line> <column> <routine> <label>```,
+/// with ```-``` for a missing routine or label.
+pub fn write(image: &Image) -> String {
+    let mut text = format!("{} {}\n", MAGIC, VERSION);
+    for (index, source) in image.diagnostics.sources.iter().enumerate() {
+        _ = writeln!(text, "file {} {}", index, source.path);
+    }
+    for entry in &image.debug {
+        _ = writeln!(
+            text,
+            "{:#06X} {} {} {} {} {} {}",
+            entry.address,
+            entry.len,
+            entry.span.file,
+            entry.span.line,
+            entry.span.column,
+            entry.routine.as_deref().unwrap_or("-"),
+            entry.label.as_deref().unwrap_or("-")
+        );
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Options;
+
+    #[test]
+    fn words_point_at_their_routine_and_the_written_code() {
+        let code = "macro: spin\n.loop:\njump .loop\nend\n.word num 1\n\
+            routine: entry\nload A num 2\n.wait:\n    spin\nend\n";
+        let image = crate::assemble(code, &Options::default()).unwrap();
+        assert_eq!(
+            write(&image),
+            "RVMD 1\nfile 0 main.rvmasm\n\
+            0x1002 1 0 5 1 - -\n0x1003 2 0 7 1 entry -\n0x1005 2 0 9 5 entry .loop@0\n"
+        );
+    }
+}
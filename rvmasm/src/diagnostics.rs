@@ -0,0 +1,270 @@
+use crate::source::{Expansion, SourceFile, Span};
+use colored::Colorize;
+
+// --- Error codes ---
+/// Error code: A token that doesn't fit the syntax
+pub const E_SYNTAX: &str = "E001";
+/// Error code: A mnemonic no instruction has
+pub const E_UNKNOWN_INSTRUCTION: &str = "E002";
+/// Error code: A word that isn't one of the registers
+pub const E_UNKNOWN_REGISTER: &str = "E003";
+/// Error code: A word that isn't one of the colors
+pub const E_UNKNOWN_COLOR: &str = "E004";
+/// Error code: A routine or filesystem without ```end```
+pub const E_MISSING_END: &str = "E005";
+/// Error code: A value that isn't a valid number
+pub const E_INVALID_NUMBER: &str = "E006";
+/// Error code: A value outside of the range it's used in
+pub const E_OUT_OF_RANGE: &str = "E007";
+/// Error code: Code or data that doesn't fit into the memory
+pub const E_MEMORY_FULL: &str = "E008";
+/// Error code: A name that is defined more than once
+pub const E_DUPLICATE: &str = "E009";
+/// Error code: A name that is used but never defined
+pub const E_UNDEFINED: &str = "E010";
+/// Error code: A value or layout that depends on itself
+pub const E_CIRCULAR: &str = "E011";
+/// Error code: A macro that is defined or called wrongly, or nested too deeply
+pub const E_MACRO: &str = "E012";
+/// Error code: A file that can't be included, because it can't be found or read or includes itself
+pub const E_INCLUDE: &str = "E013";
+/// Error code: A value in an object file the linker can't adjust, or an object file it can't read
+pub const E_RELOCATION: &str = "E014";
+/// Error code: Code or data placed over something else or into a reserved region, or a layout
+/// that can't be read
+pub const E_LAYOUT: &str = "E015";
+/// Warning code: A jump into memory reserved for data
+pub const W_RESERVED_JUMP: &str = "W001";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Additional information attached to a diagnostic, optionally pointing at other code
+#[derive(Debug, Clone, PartialEq)]
+pub struct Note {
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+/// A problem found while assembling, pointing at the code that caused it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,
+    pub notes: Vec<Note>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: impl Into<String>, span: Span) -> Self {
+        Self {
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            span,
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn warning(code: &'static str, message: impl Into<String>, span: Span) -> Self {
+        Self { severity: Severity::Warning, ..Self::error(code, message, span) }
+    }
+
+    /// Adds a note shown below the diagnostic's source snippet
+    pub fn with_note(mut self, message: impl Into<String>) -> Self {
+        self.notes.push(Note { message: message.into(), span: None });
+        self
+    }
+
+    /// Adds a note that shows the code it points at
+    pub fn with_note_at(mut self, message: impl Into<String>, span: Span) -> Self {
+        self.notes.push(Note { message: message.into(), span: Some(span) });
+        self
+    }
+
+    /// Prints the diagnostic with the lines of code it points at, underlining the exact spot.
+    /// Code inside a macro is followed by the calls it was expanded from.
+    pub fn report(&self, sources: &[SourceFile], expansions: &[Expansion]) {
+        let title = match self.severity {
+            Severity::Error => format!("error[{}]", self.code).red().bold(),
+            Severity::Warning => format!("warning[{}]", self.code).yellow().bold(),
+        };
+        println!("{}: {}", title, self.message.bold());
+        print_snippet(sources, self.span);
+
+        let mut expansion = self.span.expansion.and_then(|index| expansions.get(index as usize));
+        while let Some(call) = expansion {
+            println!("{}: in the expansion of macro `{}`", "note".cyan().bold(), call.name);
+            print_snippet(sources, call.call);
+            expansion = call.call.expansion.and_then(|index| expansions.get(index as usize));
+        }
+
+        for note in &self.notes {
+            match note.span {
+                Some(span) => {
+                    println!("{}: {}", "note".cyan().bold(), note.message);
+                    print_snippet(sources, span);
+                }
+                None => println!("  {} {}: {}", "=".blue(), "note".bold(), note.message),
+            }
+        }
+        println!();
+    }
+}
+
+/// All diagnostics found in a run of the assembler
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    pub list: Vec<Diagnostic>,
+    /// Every macro call, which the spans of code copied from a macro refer to
+    pub expansions: Vec<Expansion>,
+    /// Every file read, which the file ids of spans refer to. Filled in once the files aren't
+    /// needed anymore for assembling.
+    pub sources: Vec<SourceFile>,
+    /// What was placed where, printed before the diagnostics
+    pub log: Vec<String>,
+}
+
+impl Diagnostics {
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.list.push(diagnostic);
+    }
+
+    pub fn extend(&mut self, other: Diagnostics) {
+        self.list.extend(other.list);
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.list.iter().filter(|diagnostic| diagnostic.severity == Severity::Error).count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.error_count() > 0
+    }
+
+    /// Prints the log and every diagnostic in the order they appear in the source code, followed
+    /// by a summary line if there were errors
+    pub fn report(&self) {
+        for line in &self.log {
+            println!("{}", line);
+        }
+        let mut list: Vec<&Diagnostic> = self.list.iter().collect();
+        list.sort_by_key(|diagnostic| (diagnostic.span.file, diagnostic.span.start));
+        // A value can be evaluated more than once, for example a constant at every use
+        list.dedup();
+        for diagnostic in list {
+            diagnostic.report(&self.sources, &self.expansions);
+        }
+
+        let errors = self.error_count();
+        if errors > 0 {
+            println!(
+                "{}",
+                format!("Could not assemble due to {} error{}", errors, if errors == 1 { "" } else { "s" })
+                    .red()
+                    .bold()
+            );
+        }
+    }
+}
+
+/// Prints the location of a span and the line it starts in with the span underlined
+fn print_snippet(sources: &[SourceFile], span: Span) {
+    // Spans of code that doesn't exist, like of an object file, start at line 0
+    let Some(source) = sources.get(span.file).filter(|_| span.line > 0) else {
+        return;
+    };
+    println!(" {} {}:{}:{}", "-->".blue(), source.path, span.line, span.column);
+
+    let line = source.text.lines().nth(span.line - 1).unwrap_or("");
+    let gutter = " ".repeat(span.line.to_string().len());
+    let line_start = source.text[..span.start].rfind('\n').map_or(0, |index| index + 1);
+    let line_end = line_start + line.len();
+    let width = source.text[span.start.min(line_end)..span.end.clamp(span.start, line_end)]
+        .chars()
+        .count()
+        .max(1);
+
+    println!("{} {}", gutter, "|".blue());
+    println!("{} {} {}", span.line.to_string().blue(), "|".blue(), line.replace('\t', " "));
+    println!(
+        "{} {} {}{}",
+        gutter,
+        "|".blue(),
+        " ".repeat(span.column - 1),
+        "^".repeat(width).red().bold()
+    );
+}
+
+/// Returns the option closest to a misspelled word, if any is close enough to be meant
+pub fn suggest<'a>(word: &str, options: &[&'a str]) -> Option<&'a str> {
+    options
+        .iter()
+        .map(|option| (edit_distance(&word.to_lowercase(), &option.to_lowercase()), *option))
+        .filter(|(distance, option)| *distance <= (option.len() / 3).max(1))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, option)| option)
+}
+
+/// Number of characters to insert, remove, replace or swap with their neighbour to turn one
+/// word into the other
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![(0..=b.len()).collect::<Vec<usize>>()];
+    for i in 1..=a.len() {
+        let mut current = vec![i];
+        for j in 1..=b.len() {
+            let previous = &rows[i - 1];
+            let mut distance = (previous[j - 1] + usize::from(a[i - 1] != b[j - 1]))
+                .min(previous[j] + 1)
+                .min(current[j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            current.push(distance);
+        }
+        rows.push(current);
+    }
+    rows[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn close_words_are_suggested() {
+        assert_eq!(suggest("laod", &["load", "stor"]), Some("load"));
+        assert_eq!(suggest("RED", &["red", "green"]), Some("red"));
+        assert_eq!(suggest("purple", &["red", "green"]), None);
+    }
+
+    #[test]
+    fn swapped_characters_count_once() {
+        assert_eq!(edit_distance("juin", "jiun"), 1);
+        assert_eq!(edit_distance("", "rtor"), 4);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn only_errors_are_counted() {
+        let mut diagnostics = Diagnostics::default();
+        diagnostics.push(Diagnostic::warning(W_RESERVED_JUMP, "warning", Span::default()));
+        assert!(!diagnostics.has_errors());
+        diagnostics.push(Diagnostic::error(E_SYNTAX, "error", Span::default()));
+        assert_eq!(diagnostics.error_count(), 1);
+    }
+
+    #[test]
+    fn diagnostics_without_code_are_printed() {
+        let mut diagnostics = Diagnostics::default();
+        diagnostics.sources.push(SourceFile { path: "main.rvmasm".to_string(), text: "halt\n".to_string() });
+        diagnostics.push(Diagnostic::error(E_RELOCATION, "No code to show", Span::default()));
+        diagnostics.report();
+    }
+}
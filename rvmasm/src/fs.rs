@@ -1,3 +1,6 @@
+// TODO: Files are not written into filesystems yet
+#![allow(dead_code)]
+
 pub struct FileSystem {
     pub startpoint: u16,
     pub content: Vec<File>,
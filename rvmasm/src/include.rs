@@ -0,0 +1,307 @@
+use crate::diagnostics::{self, Diagnostic, Diagnostics};
+use crate::lexer::{self, Token, TokenKind};
+use crate::source::{SourceFile, Span};
+use std::path::{Component, Path, PathBuf};
+
+/// Reads the file and every file it includes with ```include "<path>"```, returning all of them
+/// and the tokens of the program, with each include replaced by the tokens of the included file.
+/// The index of a file in the returned list is the file id in the spans of its tokens.
+/// A path is looked up next to the file including it first, then in every include directory in
+/// the order they were given. Every file is only included once, so a file can include the files
+/// it needs without caring if they were included before. A file including itself, directly or
+/// through other files, is an error.
+pub fn load(
+    path: &str,
+    include_dirs: &[PathBuf],
+    diagnostics: &mut Diagnostics,
+) -> std::io::Result<(Vec<SourceFile>, Vec<Token>)> {
+    let source = SourceFile::read(path)?;
+    let canonical = std::fs::canonicalize(path)?;
+    Ok(Loader::new(Files::Disk(include_dirs), diagnostics).run(source, canonical))
+}
+
+/// Like ```load```, but includes are looked up among the given files instead of on the disk, so
+/// nothing is read from it. A path is looked up next to the file including it first, then as it
+/// is written.
+pub fn load_memory(
+    source: SourceFile,
+    files: &[SourceFile],
+    diagnostics: &mut Diagnostics,
+) -> (Vec<SourceFile>, Vec<Token>) {
+    let canonical = normalize(Path::new(&source.path));
+    Loader::new(Files::Memory(files), diagnostics).run(source, canonical)
+}
+
+/// Where included files are read from
+enum Files<'a> {
+    /// The disk, searching the include directories
+    Disk(&'a [PathBuf]),
+    /// Files handed to the assembler
+    Memory(&'a [SourceFile]),
+}
+
+struct Loader<'a> {
+    files: Files<'a>,
+    sources: Vec<SourceFile>,
+    /// Canonical paths of every file read so far
+    included: Vec<PathBuf>,
+    /// Canonical paths and ids of the files being read, each with the include that led to it
+    stack: Vec<(PathBuf, usize, Option<Span>)>,
+    diagnostics: &'a mut Diagnostics,
+}
+
+impl<'a> Loader<'a> {
+    fn new(files: Files<'a>, diagnostics: &'a mut Diagnostics) -> Self {
+        Self { files, sources: Vec::new(), included: Vec::new(), stack: Vec::new(), diagnostics }
+    }
+
+    fn run(mut self, source: SourceFile, canonical: PathBuf) -> (Vec<SourceFile>, Vec<Token>) {
+        let tokens = self.file(source, canonical, None);
+        (self.sources, tokens)
+    }
+
+    /// Tokenizes a file and replaces its includes. The returned tokens end with an Eof token.
+    fn file(&mut self, source: SourceFile, canonical: PathBuf, include: Option<Span>) -> Vec<Token> {
+        let id = self.sources.len();
+        let tokens = lexer::tokenize(&source.text, id);
+        let path = PathBuf::from(&source.path);
+        self.sources.push(source);
+        self.included.push(canonical.clone());
+        self.stack.push((canonical, id, include));
+
+        let mut output = Vec::new();
+        for line in tokens.split_inclusive(|token| token.kind == TokenKind::Newline) {
+            let is_include = matches!(&line[0].kind, TokenKind::Ident(name) if name == "include")
+                && line.get(1).is_none_or(|token| token.kind != TokenKind::Punct(':'));
+            if !is_include {
+                output.extend_from_slice(line);
+                continue;
+            }
+            match self.include(line, &path) {
+                Ok(tokens) => output.extend(tokens),
+                Err(diagnostic) => self.diagnostics.push(diagnostic),
+            }
+            output.extend(line.last().filter(|token| token.kind == TokenKind::Newline).cloned());
+        }
+
+        self.stack.pop();
+        output
+    }
+
+    /// Returns the tokens of the file a line starting with ```include``` includes, without its
+    /// Eof token
+    fn include(&mut self, line: &[Token], from: &Path) -> Result<Vec<Token>, Diagnostic> {
+        let (raw, span) = match line.get(1).map(|token| (&token.kind, token.span)) {
+            Some((TokenKind::Str { raw, terminated: true }, span)) => (raw.as_str(), span),
+            found => {
+                let span = found.map_or(line[0].span, |(_, span)| span);
+                return Err(Diagnostic::error(
+                    diagnostics::E_SYNTAX,
+                    "Expected the path of the file to include in double quotes",
+                    span,
+                ));
+            }
+        };
+        let rest = line.get(2).filter(|token| !matches!(token.kind, TokenKind::Newline | TokenKind::Eof));
+        if let Some(token) = rest {
+            return Err(Diagnostic::error(
+                diagnostics::E_SYNTAX,
+                "Expected the end of the line after the path",
+                token.span,
+            ));
+        }
+        let span = line[0].span.to(span);
+
+        let Some(found) = self.find(raw, from) else {
+            let mut diagnostic = Diagnostic::error(
+                diagnostics::E_INCLUDE,
+                format!("Can't find the file `{}`", raw),
+                span,
+            );
+            diagnostic = diagnostic.with_note(match &self.files {
+                Files::Disk([]) => {
+                    "it was searched next to the file including it, add directories to search with `-I`"
+                        .to_string()
+                }
+                Files::Disk(include_dirs) => {
+                    let dirs: Vec<String> =
+                        include_dirs.iter().map(|dir| format!("`{}`", dir.display())).collect();
+                    format!("it was searched next to the file including it and in {}", dirs.join(", "))
+                }
+                Files::Memory(_) => "it was searched among the files handed to the assembler".to_string(),
+            });
+            return Err(diagnostic);
+        };
+        let error = |error: std::io::Error| {
+            Diagnostic::error(diagnostics::E_INCLUDE, format!("Can't read `{}`: {}", raw, error), span)
+        };
+        let canonical = match self.files {
+            Files::Disk(_) => std::fs::canonicalize(&found).map_err(error)?,
+            Files::Memory(_) => found.clone(),
+        };
+
+        if let Some(start) = self.stack.iter().position(|(file, _, _)| *file == canonical) {
+            let mut diagnostic = Diagnostic::error(
+                diagnostics::E_INCLUDE,
+                format!("Include cycle: `{}` ends up including itself", raw),
+                span,
+            );
+            for (_, id, include) in &self.stack[start + 1..] {
+                if let Some(include) = include {
+                    let (including, included) = (&self.sources[include.file].path, &self.sources[*id].path);
+                    let message = format!("`{}` includes `{}` here", including, included);
+                    diagnostic = diagnostic.with_note_at(message, *include);
+                }
+            }
+            return Err(diagnostic);
+        }
+        if self.included.contains(&canonical) {
+            return Ok(Vec::new());
+        }
+
+        let source = match self.files {
+            Files::Disk(_) => SourceFile::read(&found.to_string_lossy()).map_err(error)?,
+            Files::Memory(files) => {
+                let file = files.iter().find(|file| normalize(Path::new(&file.path)) == found);
+                file.cloned().ok_or_else(|| error(std::io::ErrorKind::NotFound.into()))?
+            }
+        };
+        let mut tokens = self.file(source, canonical, Some(span));
+        tokens.pop();
+        Ok(tokens)
+    }
+
+    /// Looks for a file next to the file including it, then in the include directories or among
+    /// the files handed to the assembler
+    fn find(&self, path: &str, from: &Path) -> Option<PathBuf> {
+        let next_to = from.parent().unwrap_or(Path::new("")).join(path);
+        match self.files {
+            Files::Disk(include_dirs) => std::iter::once(next_to)
+                .chain(include_dirs.iter().map(|dir| dir.join(path)))
+                .find(|candidate| candidate.is_file()),
+            Files::Memory(files) => [normalize(&next_to), normalize(Path::new(path))]
+                .into_iter()
+                .find(|candidate| files.iter().any(|file| normalize(Path::new(&file.path)) == *candidate)),
+        }
+    }
+}
+
+/// Removes ```.``` and resolves ```..``` in a path without looking at the disk
+fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if normal.file_name().is_some() => {
+                normal.pop();
+            }
+            component => normal.push(component),
+        }
+    }
+    normal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes the files to a new directory in the temporary directory and returns its path
+    fn directory(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rvmasm-{}-{}", name, std::process::id()));
+        for (path, text) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        }
+        dir
+    }
+
+    fn idents(tokens: &[Token]) -> Vec<&str> {
+        tokens
+            .iter()
+            .filter_map(|token| match &token.kind {
+                TokenKind::Ident(name) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn files_are_only_included_once() {
+        let dir = directory(
+            "once",
+            &[
+                ("main.rvmasm", "include \"a.rvmasm\"\ninclude \"lib.rvmasm\"\nhalt\n"),
+                ("a.rvmasm", "include \"lib.rvmasm\"\nnoop\n"),
+                ("lib/lib.rvmasm", "rtor\n"),
+            ],
+        );
+        let mut diagnostics = Diagnostics::default();
+        let main = dir.join("main.rvmasm");
+        let (sources, tokens) = load(&main.to_string_lossy(), &[dir.join("lib")], &mut diagnostics).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(diagnostics.list.is_empty(), "{:?}", diagnostics.list);
+        assert_eq!(sources.len(), 3);
+        assert_eq!(idents(&tokens), ["rtor", "noop", "halt"]);
+        assert_eq!(tokens[0].span.file, 2);
+        assert_eq!(tokens.iter().filter(|token| token.kind == TokenKind::Eof).count(), 1);
+    }
+
+    #[test]
+    fn include_cycles_are_reported() {
+        let dir = directory(
+            "cycle",
+            &[
+                ("main.rvmasm", "include \"a.rvmasm\"\n"),
+                ("a.rvmasm", "include \"b.rvmasm\"\n"),
+                ("b.rvmasm", "include \"a.rvmasm\"\n"),
+            ],
+        );
+        let mut diagnostics = Diagnostics::default();
+        load(&dir.join("main.rvmasm").to_string_lossy(), &[], &mut diagnostics).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let [diagnostic] = &diagnostics.list[..] else { panic!("{:?}", diagnostics.list) };
+        assert_eq!(diagnostic.code, diagnostics::E_INCLUDE);
+        assert_eq!(diagnostic.span.file, 2);
+        assert_eq!(diagnostic.notes.len(), 1);
+        assert_eq!(diagnostic.notes[0].span.map(|span| span.file), Some(1));
+    }
+
+    #[test]
+    fn missing_files_and_paths_are_reported() {
+        let dir = directory("missing", &[("main.rvmasm", "include \"nowhere.rvmasm\"\ninclude nowhere\n")]);
+        let mut diagnostics = Diagnostics::default();
+        load(&dir.join("main.rvmasm").to_string_lossy(), std::slice::from_ref(&dir), &mut diagnostics)
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let [missing, path] = &diagnostics.list[..] else { panic!("{:?}", diagnostics.list) };
+        assert_eq!(missing.message, "Can't find the file `nowhere.rvmasm`");
+        assert!(missing.notes[0].message.starts_with("it was searched next to the file including it and in"));
+        assert_eq!((path.code, path.span.line), (diagnostics::E_SYNTAX, 2));
+    }
+
+    #[test]
+    fn files_handed_to_the_assembler_are_included() {
+        let file = |path: &str, text: &str| SourceFile { path: path.to_string(), text: text.to_string() };
+        let files = [
+            file("lib/print.rvmasm", "include \"../util/./halt.rvmasm\"\nrtor\n"),
+            file("util/halt.rvmasm", "halt\n"),
+        ];
+        let mut diagnostics = Diagnostics::default();
+        let main = file(
+            "main.rvmasm",
+            "include \"lib/print.rvmasm\"\ninclude \"util/halt.rvmasm\"\ninclude \"x.rvmasm\"\n",
+        );
+        let (sources, tokens) = load_memory(main, &files, &mut diagnostics);
+
+        assert_eq!(sources.len(), 3);
+        assert_eq!(idents(&tokens), ["halt", "rtor"]);
+        let [missing] = &diagnostics.list[..] else { panic!("{:?}", diagnostics.list) };
+        assert_eq!(missing.message, "Can't find the file `x.rvmasm`");
+        assert_eq!(missing.notes[0].message, "it was searched among the files handed to the assembler");
+        assert_eq!(normalize(Path::new("./a/../../b/c.rvmasm")), Path::new("../b/c.rvmasm"));
+    }
+}
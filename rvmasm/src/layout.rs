@@ -0,0 +1,308 @@
+use crate::assembler::{
+    ASCII_TABLE, BITMAP, CODE_START, ENTRY_TRAMPOLINE, GPU_BUFFER, GPU_STATUS, TILE_MEMORY,
+};
+use crate::diagnostics::{self, Diagnostic, Diagnostics};
+use crate::source::Span;
+
+/// A named range of the memory that code and data can be placed into, or that is kept free
+#[derive(Debug, Clone)]
+pub struct Region {
+    pub name: String,
+    pub start: u16,
+    /// Last address of the region
+    pub end: u16,
+    /// Whether nothing may be placed into the region, like the stack or the memory of a device
+    pub reserved: bool,
+}
+
+impl Region {
+    fn new(name: &str, start: u16, end: u16, reserved: bool) -> Self {
+        Self { name: name.to_string(), start, end, reserved }
+    }
+
+    pub fn contains(&self, address: usize) -> bool {
+        (self.start as usize..=self.end as usize).contains(&address)
+    }
+}
+
+/// Where in the memory code and data go. Without a layout file, the memory after the jump to the
+/// entry routine up to the tiles is a region called "code", and the free memory between the tiles
+/// and the bitmap and after the bitmap are the regions "high" and "top".
+#[derive(Debug, Clone)]
+pub struct Layout {
+    pub regions: Vec<Region>,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        let mut regions = builtin();
+        regions.extend([
+            Region::new("code", CODE_START as u16, TILE_MEMORY.start - 1, false),
+            Region::new("high", TILE_MEMORY.end, BITMAP.start - 1, false),
+            Region::new("top", BITMAP.end, 0xFFFF, false),
+        ]);
+        Self { regions }
+    }
+}
+
+/// Returns the regions every ROM has, which the assembler or the GPU fill themselves
+fn builtin() -> Vec<Region> {
+    vec![
+        Region::new("chars", ASCII_TABLE.start, ASCII_TABLE.end - 1, true),
+        Region::new("status", GPU_STATUS.start, GPU_STATUS.end - 1, true),
+        Region::new("gpu", GPU_BUFFER.start, GPU_BUFFER.end - 1, true),
+        Region::new("entry", ENTRY_TRAMPOLINE as u16, ENTRY_TRAMPOLINE as u16 + 1, true),
+        Region::new("tiles", TILE_MEMORY.start, TILE_MEMORY.end - 1, true),
+        Region::new("bitmap", BITMAP.start, BITMAP.end - 1, true),
+    ]
+}
+
+impl Layout {
+    /// Reads a layout file. Every line is ```region <name> <start> <end> [reserved]```, where end is
+    /// the last address of the region, or a comment starting with ```#```. The regions for the
+    /// character codes, the GPU's status, command buffer, tiles and bitmap and the jump to the entry
+    /// routine are always there and can't be overlapped. Code is placed into the first region that
+    /// isn't reserved unless ```.section``` or ```.org``` say otherwise. The error tells which line
+    /// couldn't be read and why.
+    pub fn read(text: &str) -> Result<Layout, String> {
+        let mut layout = Layout { regions: builtin() };
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: &str| format!("line {}: {}", index + 1, message);
+            let line = line.split('#').next().unwrap_or_default();
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let address = |field: usize| -> Result<u16, String> {
+                let text = fields.get(field).ok_or_else(|| error("missing an address"))?;
+                let parsed = match text.strip_prefix("0x") {
+                    Some(hex) => u16::from_str_radix(hex, 16),
+                    None => text.parse(),
+                };
+                parsed.map_err(|_| error(&format!("`{}` is not an address between 0 and 0xFFFF", text)))
+            };
+
+            match fields.first() {
+                None => {}
+                Some(&"region") => {
+                    let name = fields.get(1).ok_or_else(|| error("missing a region name"))?;
+                    let (start, end) = (address(2)?, address(3)?);
+                    let reserved = match fields.get(4) {
+                        None => false,
+                        Some(&"reserved") => true,
+                        Some(other) => return Err(error(&format!("expected `reserved`, found `{}`", other))),
+                    };
+                    if fields.len() > 5 {
+                        return Err(error("expected the end of the line"));
+                    }
+                    if end < start {
+                        return Err(error("the region ends before it starts"));
+                    }
+                    if let Some(other) = layout.regions.iter().find(|region| region.name == *name) {
+                        return Err(error(&format!("there already is a region called `{}`", other.name)));
+                    }
+                    let region = Region::new(name, start, end, reserved);
+                    let mut regions = layout.regions.iter();
+                    if let Some(other) = regions.find(|other| other.start <= end && start <= other.end) {
+                        return Err(error(&format!(
+                            "region `{}` overlaps region `{}` at {:#06X} - {:#06X}",
+                            name, other.name, other.start, other.end
+                        )));
+                    }
+                    layout.regions.push(region);
+                }
+                Some(other) => return Err(error(&format!("unknown entry `{}`", other))),
+            }
+        }
+        if layout.regions.iter().all(|region| region.reserved) {
+            return Err("there is no region that isn't reserved to place code into".to_string());
+        }
+        Ok(layout)
+    }
+
+    /// Returns the index of the region code is placed into unless it's told otherwise
+    pub fn default_region(&self) -> usize {
+        self.regions.iter().position(|region| !region.reserved).unwrap_or_default()
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.regions.iter().position(|region| region.name == name)
+    }
+
+    /// Returns the index of the region that isn't reserved containing the address
+    pub fn containing(&self, address: usize) -> Option<usize> {
+        self.regions.iter().position(|region| !region.reserved && region.contains(address))
+    }
+
+    /// Reports everything placed into the memory that overlaps something else placed or a
+    /// reserved region
+    pub fn check_overlaps(&self, placed: &[Placement], diagnostics: &mut Diagnostics) {
+        let mut placed: Vec<&Placement> = placed.iter().filter(|placement| placement.len > 0).collect();
+        placed.sort_by_key(|placement| placement.start);
+
+        // The placement reaching the furthest so far, which the next one must start after
+        let mut furthest: Option<&Placement> = None;
+        for placement in placed {
+            for region in self.regions.iter().filter(|region| region.reserved) {
+                if placement.start <= region.end as usize && region.start as usize <= placement.end() {
+                    diagnostics.push(Diagnostic::error(
+                        diagnostics::E_LAYOUT,
+                        format!(
+                            "{} overlaps the reserved region `{}` at {:#06X} - {:#06X}",
+                            capitalized(&placement.to_string()),
+                            region.name,
+                            region.start,
+                            region.end
+                        ),
+                        placement.span,
+                    ));
+                }
+            }
+            match furthest {
+                Some(other) if other.end() >= placement.start => {
+                    let mut diagnostic = Diagnostic::error(
+                        diagnostics::E_LAYOUT,
+                        format!("{} overlaps {}", capitalized(&placement.to_string()), other),
+                        placement.span,
+                    );
+                    // Object files have no source code to point at
+                    if other.span != Span::default() {
+                        let note = format!("{} is placed here", other.what);
+                        diagnostic = diagnostic.with_note_at(note, other.span);
+                    }
+                    diagnostics.push(diagnostic);
+                    if placement.end() > other.end() {
+                        furthest = Some(placement);
+                    }
+                }
+                _ => furthest = Some(placement),
+            }
+        }
+    }
+}
+
+/// Something placed into the memory, like a routine
+pub struct Placement {
+    /// What was placed, like ```routine `entry````, starting in lowercase
+    pub what: String,
+    pub start: usize,
+    /// Number of addresses it takes up
+    pub len: usize,
+    /// Where it is defined
+    pub span: Span,
+}
+
+impl Placement {
+    /// Returns the last address it takes up
+    fn end(&self) -> usize {
+        self.start + self.len - 1
+    }
+}
+
+impl std::fmt::Display for Placement {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} at {:#06X} - {:#06X}", self.what, self.start, self.end())
+    }
+}
+
+/// Returns the text with its first letter in uppercase
+fn capitalized(text: &str) -> String {
+    let mut chars = text.chars();
+    chars.next().map_or(String::new(), |first| first.to_uppercase().chain(chars).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regions_are_read_after_the_builtin_ones() {
+        let layout = Layout::read(
+            "# The stack sits between code and data\n\
+            region code 0x1002 0x7FFF\nregion stack 0x8000 36863 reserved\nregion data 0x9000 0xDFFF\n",
+        )
+        .unwrap();
+        let names: Vec<&str> = layout.regions.iter().map(|region| region.name.as_str()).collect();
+        assert_eq!(names, ["chars", "status", "gpu", "entry", "tiles", "bitmap", "code", "stack", "data"]);
+        assert_eq!((layout.regions[7].start, layout.regions[7].end), (0x8000, 0x8FFF));
+        assert!(layout.regions[7].reserved);
+        assert_eq!(layout.regions[layout.default_region()].name, "code");
+        assert_eq!(layout.containing(0x8800), None);
+        assert_eq!(layout.containing(0x9000), Some(8));
+        assert_eq!(layout.find("data"), Some(8));
+    }
+
+    #[test]
+    fn the_memory_of_the_gpu_is_reserved() {
+        let layout = Layout::default();
+        for address in [0x02F0, 0x02F7, 0xE000, 0xE53F, 0xF000, 0xFE0F] {
+            assert_eq!(layout.containing(address), None, "{:#06X}", address);
+        }
+        let free = [(0x1002, "code"), (0xDFFF, "code"), (0xE540, "high"), (0xEFFF, "high"), (0xFE10, "top")];
+        for (address, name) in free {
+            assert_eq!(
+                layout.containing(address).map(|region| layout.regions[region].name.as_str()),
+                Some(name)
+            );
+        }
+        assert_eq!(
+            Layout::read("region code 0x1002 0xFFFF").unwrap_err(),
+            "line 1: region `code` overlaps region `tiles` at 0xE000 - 0xE53F"
+        );
+        assert_eq!(
+            Layout::read("region low 0x0250 0x02FF").unwrap_err(),
+            "line 1: region `low` overlaps region `chars` at 0x0200 - 0x0251"
+        );
+        assert!(Layout::read("region low 0x02F8 0x02FF").is_ok());
+        assert!(Layout::read("region low 0x02E0 0x02F0").is_err());
+    }
+
+    #[test]
+    fn broken_layouts_are_rejected() {
+        let error = |text: &str| Layout::read(text).unwrap_err();
+        assert_eq!(error("region code 0x2000 0x1FFF"), "line 1: the region ends before it starts");
+        assert_eq!(
+            error("region code 0x1002 0x7FFF\nregion code 0x8000 0xFFFF"),
+            "line 2: there already is a region called `code`"
+        );
+        assert_eq!(
+            error("region code 0x1000 0x7FFF"),
+            "line 1: region `code` overlaps region `entry` at 0x1000 - 0x1001"
+        );
+        assert_eq!(
+            error("region code 0x1002 0x10000"),
+            "line 1: `0x10000` is not an address between 0 and 0xFFFF"
+        );
+        assert_eq!(error("region code 0x1002 0x7FFF free"), "line 1: expected `reserved`, found `free`");
+        assert_eq!(error("segment code 0x1002 0x7FFF"), "line 1: unknown entry `segment`");
+        assert_eq!(
+            error("region stack 0x8000 0x8FFF reserved"),
+            "there is no region that isn't reserved to place code into"
+        );
+    }
+
+    #[test]
+    fn overlaps_are_reported_once_per_placement() {
+        let layout = Layout::default();
+        let placement =
+            |what: &str, start, len| Placement { what: what.to_string(), start, len, span: Span::default() };
+        let mut diagnostics = Diagnostics::default();
+        layout.check_overlaps(
+            &[
+                placement("routine `entry`", 0x1002, 4),
+                placement("data `table`", 0x1004, 2),
+                placement("data `empty`", 0x1004, 0),
+                placement("routine `print`", 0x1006, 2),
+                placement("routine `stray`", 0x1001, 1),
+            ],
+            &mut diagnostics,
+        );
+        let messages: Vec<&str> =
+            diagnostics.list.iter().map(|diagnostic| diagnostic.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Routine `stray` at 0x1001 - 0x1001 overlaps the reserved region `entry` at 0x1000 - 0x1001",
+                "Data `table` at 0x1004 - 0x1005 overlaps routine `entry` at 0x1002 - 0x1005",
+            ]
+        );
+        assert!(diagnostics.list.iter().all(|diagnostic| diagnostic.code == diagnostics::E_LAYOUT));
+    }
+}
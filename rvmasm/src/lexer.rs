@@ -0,0 +1,165 @@
+use crate::source::Span;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    /// A word starting with a letter, '_' or '.', like a mnemonic, keyword or routine name
+    Ident(String),
+    /// A word starting with a digit. It's kept as text since its base depends on the keyword
+    /// in front of it.
+    Number(String),
+    /// Text between double quotes, with its escapes not yet replaced. Unterminated if the line
+    /// ended before the closing quote.
+    Str { raw: String, terminated: bool },
+    /// Any other single character
+    Punct(char),
+    Newline,
+    Eof,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+/// Splits source code into tokens. Spaces and tabs only separate tokens, comments starting
+/// with ```#``` or ```//``` at the start of a word are skipped until the end of the line.
+/// The returned tokens always end with an Eof token.
+pub fn tokenize(text: &str, file: usize) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    let mut line = 1;
+    let mut line_start = 0;
+    let mut word_start = true;
+
+    while let Some((start, char)) = chars.next() {
+        let span = |end: usize| Span {
+            file,
+            start,
+            end,
+            line,
+            column: text[line_start..start].chars().count() + 1,
+            expansion: None,
+        };
+
+        let is_comment = char == '#' || (char == '/' && text[start..].starts_with("//"));
+        if word_start && is_comment {
+            while chars.next_if(|(_, next)| *next != '\n').is_some() {}
+            continue;
+        }
+
+        match char {
+            '\n' => {
+                tokens.push(Token { kind: TokenKind::Newline, span: span(start + 1) });
+                line += 1;
+                line_start = start + 1;
+                word_start = true;
+                continue;
+            }
+            ' ' | '\t' | '\r' => {
+                word_start = true;
+                continue;
+            }
+            _ => {}
+        }
+
+        let kind = if char == '"' {
+            let mut escaped = false;
+            let mut terminated = false;
+            while let Some((_, next)) = chars.next_if(|(_, next)| *next != '\n') {
+                if next == '"' && !escaped {
+                    terminated = true;
+                    break;
+                }
+                escaped = next == '\\' && !escaped;
+            }
+            let end = chars.peek().map_or(text.len(), |(index, _)| *index);
+            let raw = &text[start + 1..if terminated { end - 1 } else { end }];
+            TokenKind::Str { raw: raw.to_string(), terminated }
+        } else if char.is_ascii_alphanumeric() || char == '_' || char == '.' {
+            while chars
+                .next_if(|(_, next)| next.is_ascii_alphanumeric() || *next == '_' || *next == '.')
+                .is_some()
+            {}
+            let end = chars.peek().map_or(text.len(), |(index, _)| *index);
+            if char.is_ascii_digit() {
+                TokenKind::Number(text[start..end].to_string())
+            } else {
+                TokenKind::Ident(text[start..end].to_string())
+            }
+        } else {
+            TokenKind::Punct(char)
+        };
+
+        let end = chars.peek().map_or(text.len(), |(index, _)| *index);
+        tokens.push(Token { kind, span: span(end) });
+        word_start = false;
+    }
+
+    let end = text.len();
+    let column = text[line_start..].chars().count() + 1;
+    tokens.push(Token {
+        kind: TokenKind::Eof,
+        span: Span { file, start: end, end, line, column, expansion: None },
+    });
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(text: &str) -> Vec<TokenKind> {
+        tokenize(text, 0).into_iter().map(|token| token.kind).collect()
+    }
+
+    fn ident(name: &str) -> TokenKind {
+        TokenKind::Ident(name.to_string())
+    }
+
+    #[test]
+    fn words_and_punctuation_are_split() {
+        assert_eq!(
+            kinds("load A num .x+10\n"),
+            [
+                ident("load"),
+                ident("A"),
+                ident("num"),
+                ident(".x"),
+                TokenKind::Punct('+'),
+                TokenKind::Number("10".to_string()),
+                TokenKind::Newline,
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn comments_only_start_at_the_start_of_a_word() {
+        assert_eq!(
+            kinds("halt # stop\n// all\n"),
+            [ident("halt"), TokenKind::Newline, TokenKind::Newline, TokenKind::Eof]
+        );
+        assert_eq!(kinds("a#b"), [ident("a"), TokenKind::Punct('#'), ident("b"), TokenKind::Eof]);
+    }
+
+    #[test]
+    fn strings_keep_their_escapes() {
+        let string = |raw: &str, terminated| TokenKind::Str { raw: raw.to_string(), terminated };
+        assert_eq!(kinds(r#""a\"b" "\\""#), [string(r#"a\"b"#, true), string(r"\\", true), TokenKind::Eof]);
+        assert_eq!(
+            kinds("\"open\nhalt"),
+            [string("open", false), TokenKind::Newline, ident("halt"), TokenKind::Eof]
+        );
+    }
+
+    #[test]
+    fn spans_count_lines_and_columns() {
+        let tokens = tokenize("a\n  é b", 3);
+        let b = &tokens[3];
+        assert_eq!(b.kind, ident("b"));
+        assert_eq!((b.span.file, b.span.line, b.span.column), (3, 2, 5));
+        assert_eq!((b.span.start, b.span.end), (7, 8));
+        assert_eq!(tokens[4].span.column, 6);
+    }
+}
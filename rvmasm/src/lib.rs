@@ -0,0 +1,172 @@
+//! The RvmASM assembler. ```assemble``` turns code held in memory into a memory image without
+//! touching the disk, ```assemble_file``` reads the code and the files it includes from the disk.
+//! Both return every error and warning found, which can be printed with ```Diagnostics::report```.
+
+pub mod debug;
+pub mod diagnostics;
+pub mod fs;
+pub mod layout;
+pub mod listing;
+pub mod object;
+pub mod source;
+
+mod assembler;
+mod ast;
+mod include;
+mod lexer;
+mod linker;
+mod macros;
+mod opcodes;
+mod parser;
+
+pub use assembler::{Image, Listed, Symbol, SymbolKind};
+pub use diagnostics::{Diagnostic, Diagnostics};
+pub use layout::Layout;
+pub use object::Object;
+pub use source::{SourceFile, Span};
+
+use std::path::PathBuf;
+
+/// How to assemble a program
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// The path of the code given to ```assemble```, which diagnostics and the debug info show and
+    /// includes are looked up next to
+    pub path: String,
+    /// The files the code given to ```assemble``` can include
+    pub files: Vec<SourceFile>,
+    /// The directories ```assemble_file``` looks up includes in, after the directory of the file
+    /// including them
+    pub include_dirs: Vec<PathBuf>,
+    /// Where code and data go in the memory
+    pub layout: Layout,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            path: "main.rvmasm".to_string(),
+            files: Vec::new(),
+            include_dirs: Vec::new(),
+            layout: Layout::default(),
+        }
+    }
+}
+
+/// Assembles code into a memory image. Includes are looked up among the files of the options, so
+/// nothing is read from the disk. The image holds the warnings, and there is no image if there
+/// were any errors.
+pub fn assemble(source: &str, options: &Options) -> Result<Image, Diagnostics> {
+    let source = SourceFile { path: options.path.clone(), text: source.to_string() };
+    let mut diagnostics = Diagnostics::default();
+    let (sources, tokens) = include::load_memory(source, &options.files, &mut diagnostics);
+    rom(sources, &tokens, options, diagnostics)
+}
+
+/// Reads a file and the files it includes from the disk and assembles them into a memory image,
+/// like ```assemble```
+pub fn assemble_file(path: &str, options: &Options) -> Result<Image, Diagnostics> {
+    let (sources, tokens, diagnostics) = load(path, options)?;
+    rom(sources, &tokens, options, diagnostics)
+}
+
+/// Reads a file and the files it includes from the disk and assembles them into an object file
+/// for ```link```. The warnings are returned next to the object file.
+pub fn assemble_object_file(path: &str, options: &Options) -> Result<(Object, Diagnostics), Diagnostics> {
+    let (sources, tokens, mut diagnostics) = load(path, options)?;
+    let program = parser::parse(&tokens, &sources, &mut diagnostics);
+    let object = assembler::assemble_object(&program, &sources, &options.layout, &mut diagnostics);
+    diagnostics.sources = sources;
+    match object {
+        Some(object) if !diagnostics.has_errors() => Ok((object, diagnostics)),
+        _ => Err(diagnostics),
+    }
+}
+
+/// Links object files, each with the name diagnostics call it by, into a memory image. There is no
+/// image if there were any errors.
+pub fn link(objects: &[(String, Object)], layout: &Layout) -> Result<Image, Diagnostics> {
+    let mut diagnostics = Diagnostics::default();
+    let image = linker::link(objects, layout, &mut diagnostics);
+    match image {
+        Some(mut image) if !diagnostics.has_errors() => {
+            image.diagnostics = diagnostics;
+            Ok(image)
+        }
+        _ => Err(diagnostics),
+    }
+}
+
+/// Every file read, the tokens of the program and the diagnostics found while reading them
+type Loaded = (Vec<SourceFile>, Vec<lexer::Token>, Diagnostics);
+
+/// Reads a file and the files it includes from the disk, with the file that can't be read as an
+/// error
+fn load(path: &str, options: &Options) -> Result<Loaded, Diagnostics> {
+    let mut diagnostics = Diagnostics::default();
+    match include::load(path, &options.include_dirs, &mut diagnostics) {
+        Ok((sources, tokens)) => Ok((sources, tokens, diagnostics)),
+        Err(error) => {
+            diagnostics.push(Diagnostic::error(
+                diagnostics::E_INCLUDE,
+                format!("Can't read `{}`: {}", path, error),
+                Span::default(),
+            ));
+            Err(diagnostics)
+        }
+    }
+}
+
+/// Parses and assembles the tokens of a program into a memory image
+fn rom(
+    sources: Vec<SourceFile>,
+    tokens: &[lexer::Token],
+    options: &Options,
+    mut diagnostics: Diagnostics,
+) -> Result<Image, Diagnostics> {
+    let program = parser::parse(tokens, &sources, &mut diagnostics);
+    let image = assembler::assemble(&program, &options.layout, &mut diagnostics);
+    diagnostics.sources = sources;
+    match image {
+        Some(mut image) if !diagnostics.has_errors() => {
+            image.diagnostics = diagnostics;
+            Ok(image)
+        }
+        _ => Err(diagnostics),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_is_assembled_without_the_disk() {
+        let options = Options {
+            files: vec![SourceFile {
+                path: "lib/add.rvmasm".to_string(),
+                text: "routine: add\nradd A num 1\nrtor\nend\n".to_string(),
+            }],
+            ..Options::default()
+        };
+        let image =
+            assemble("include \"lib/add.rvmasm\"\nroutine: entry\nload A num 10\njusr add\nend\n", &options)
+                .unwrap();
+        assert!(image.diagnostics.list.is_empty());
+        assert_eq!(image.diagnostics.sources.len(), 2);
+        assert_eq!(image.memory[0x1000..0x1002], [opcodes::JMP_TO_SR, 0x1007]);
+        assert_eq!(image.memory[0x1007..0x100B], [opcodes::LOAD_AREG, 10, opcodes::JMP_TO_SR, 0x1002]);
+        let names: Vec<&str> = image.symbols.iter().map(|symbol| symbol.name.as_str()).collect();
+        assert_eq!(names, ["add", "entry"]);
+    }
+
+    #[test]
+    fn errors_are_returned_instead_of_an_image() {
+        let Err(diagnostics) = assemble("routine: entry\nload Q num 1\nend\n", &Options::default()) else {
+            panic!("the code has an unknown register")
+        };
+        assert!(diagnostics.has_errors());
+        assert_eq!(diagnostics.list[0].code, diagnostics::E_UNKNOWN_REGISTER);
+        assert_eq!(diagnostics.sources[0].path, "main.rvmasm");
+    }
+}
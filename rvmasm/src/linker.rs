@@ -0,0 +1,254 @@
+use crate::assembler::{self, Image};
+use crate::diagnostics::{self, Diagnostic, Diagnostics};
+use crate::layout::{Layout, Placement};
+use crate::object::{Object, SectionKind, SymbolValue, Target};
+use crate::source::Span;
+use colored::Colorize;
+use std::collections::{HashMap, HashSet};
+
+/// Places the sections of object files into one memory image and fills in the addresses they
+/// use. Sections placed with ```.org``` stay where they are. The others are placed into their
+/// region of the layout around them, filesystems first, then the routines and data of every object
+/// in the order the objects were given, each followed by what comes after it in its object.
+/// The jump at 0x1000 leads to the routine called "entry", or to the last routine if there is none.
+/// A symbol must be exported by exactly one object if any object uses it. Since object files have
+/// no source code to point at, errors tell the file and where in its source an address was used.
+/// There is no image if there were any errors.
+pub fn link(objects: &[(String, Object)], layout: &Layout, diagnostics: &mut Diagnostics) -> Option<Image> {
+    // Address of every section, by the index of its object and its index in the object
+    let mut addresses: Vec<Vec<u16>> = objects
+        .iter()
+        .map(|(_, object)| object.sections.iter().map(|section| section.address).collect())
+        .collect();
+    let sections = objects.iter().enumerate().flat_map(|(index, (_, object))| {
+        let sections = object.sections.iter().enumerate();
+        sections.map(move |(section_index, section)| (index, section_index, section))
+    });
+    let (fixed, movable): (Vec<_>, Vec<_>) = sections.partition(|(_, _, section)| section.fixed);
+    let (file_systems, code): (Vec<_>, Vec<_>) =
+        movable.into_iter().partition(|(_, _, section)| section.kind == SectionKind::FileSystem);
+
+    let mut pointers: Vec<usize> = layout.regions.iter().map(|region| region.start as usize).collect();
+    let mut placed = Vec::new();
+    let mut log = Vec::new();
+    for (index, section_index, section) in fixed.iter().chain(&file_systems).chain(&code) {
+        let path = &objects[*index].0;
+        let region = match layout.find(&section.region) {
+            Some(region) if !layout.regions[region].reserved => region,
+            _ => {
+                diagnostics.push(Diagnostic::error(
+                    diagnostics::E_LAYOUT,
+                    format!(
+                        "Section `{}` of `{}` goes into region `{}`, which the layout has no room for",
+                        section.name, path, section.region
+                    ),
+                    Span::default(),
+                ));
+                continue;
+            }
+        };
+
+        let address = match section.fixed {
+            true => section.address as usize,
+            false => {
+                // Sections placed with .org keep their addresses, the others go around them
+                let mut address = pointers[region];
+                while let Some(other) = fixed.iter().map(|(_, _, other)| other).find(|other| {
+                    let start = other.address as usize;
+                    start < address + section.size && address < start + other.size
+                }) {
+                    address = other.address as usize + other.size;
+                }
+                pointers[region] = address + section.size;
+                address
+            }
+        };
+        let region = &layout.regions[region];
+        if !region.contains(address) || address + section.used() > region.end as usize + 1 {
+            diagnostics.push(Diagnostic::error(
+                diagnostics::E_MEMORY_FULL,
+                format!("Section `{}` of `{}` doesn't fit into region `{}`", section.name, path, region.name),
+                Span::default(),
+            ));
+            continue;
+        }
+
+        let kind = match section.kind {
+            SectionKind::Routine => "Linking routine".green(),
+            SectionKind::FileSystem => "Linking filesystem".magenta(),
+            SectionKind::Data => "Linking data".blue(),
+        };
+        log.push((
+            address,
+            format!(
+                "{} \"{}\" from {} @ {}",
+                kind,
+                section.name.cyan(),
+                path,
+                format!("{:#06X}", address).yellow()
+            ),
+        ));
+        let what = match section.kind {
+            SectionKind::Routine => format!("routine `{}` of `{}`", section.name, path),
+            SectionKind::FileSystem => format!("filesystem of `{}`", path),
+            SectionKind::Data => format!("data `{}` of `{}`", section.name, path),
+        };
+        placed.push(Placement { what, start: address, len: section.used(), span: Span::default() });
+        addresses[*index][*section_index] = address as u16;
+    }
+
+    log.sort_by_key(|(address, _)| *address);
+    diagnostics.log.extend(log.into_iter().map(|(_, line)| line));
+    layout.check_overlaps(&placed, diagnostics);
+    if diagnostics.has_errors() {
+        return None;
+    }
+
+    // Names only need to be unique if something uses them, like the jump at 0x1000 uses "entry"
+    let sections = objects.iter().flat_map(|(_, object)| &object.sections);
+    let used: HashSet<&str> = sections
+        .flat_map(|section| &section.relocations)
+        .filter_map(|relocation| match &relocation.target {
+            Target::Symbol(name) => Some(name.as_str()),
+            Target::Section(_) => None,
+        })
+        .chain(["entry"])
+        .collect();
+
+    let mut exports = HashMap::<&str, (u16, &str)>::new();
+    for ((path, object), addresses) in objects.iter().zip(&addresses) {
+        for symbol in &object.symbols {
+            let value = match symbol.value {
+                SymbolValue::Section(section, offset) => addresses[section].wrapping_add(offset),
+                SymbolValue::Absolute(value) => value,
+            };
+            let first = exports.insert(&symbol.name, (value, path));
+            if let Some((_, first)) = first.filter(|_| used.contains(symbol.name.as_str())) {
+                diagnostics.push(Diagnostic::error(
+                    diagnostics::E_DUPLICATE,
+                    format!("Symbol `{}` is exported by both `{}` and `{}`", symbol.name, first, path),
+                    Span::default(),
+                ));
+            }
+        }
+    }
+
+    let mut sections = Vec::new();
+    let mut undefined = Vec::<(&str, Vec<&str>)>::new();
+    for ((_, object), addresses) in objects.iter().zip(&addresses) {
+        for (original, address) in object.sections.iter().zip(addresses) {
+            let mut section = original.clone();
+            for relocation in &original.relocations {
+                let value = match &relocation.target {
+                    Target::Section(index) => addresses[*index].wrapping_sub(object.sections[*index].address),
+                    Target::Symbol(name) => match exports.get(name.as_str()) {
+                        Some((value, _)) => *value,
+                        None => {
+                            match undefined.iter_mut().find(|(other, _)| other == name) {
+                                Some((_, uses)) => uses.push(&relocation.location),
+                                None => undefined.push((name, vec![&relocation.location])),
+                            }
+                            continue;
+                        }
+                    },
+                };
+                section.words[relocation.offset] = section.words[relocation.offset].wrapping_add(value);
+            }
+            section.address = *address;
+            sections.push(section);
+        }
+    }
+
+    let mut names: Vec<&str> = exports.keys().copied().collect();
+    names.sort_unstable();
+    for (name, uses) in undefined {
+        let mut diagnostic = Diagnostic::error(
+            diagnostics::E_UNDEFINED,
+            format!("Symbol `{}` is used but no object file exports it", name),
+            Span::default(),
+        );
+        for location in uses {
+            diagnostic = diagnostic.with_note(format!("used at {}", location));
+        }
+        if let Some(suggestion) = diagnostics::suggest(name, &names) {
+            diagnostic = diagnostic.with_note(format!("did you mean `{}`?", suggestion));
+        }
+        diagnostics.push(diagnostic);
+    }
+
+    if diagnostics.has_errors() {
+        return None;
+    }
+    Some(assembler::build_image(&sections))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::CODE_START;
+    use crate::source::SourceFile;
+    use crate::{lexer, parser};
+
+    /// Assembles code held in memory into an object file
+    fn object(path: &str, code: &str) -> (String, Object) {
+        let sources = vec![SourceFile { path: path.to_string(), text: code.to_string() }];
+        let mut diagnostics = Diagnostics::default();
+        let program = parser::parse(&lexer::tokenize(code, 0), &sources, &mut diagnostics);
+        let object = assembler::assemble_object(&program, &sources, &Layout::default(), &mut diagnostics);
+        assert!(!diagnostics.has_errors(), "{} must assemble", path);
+        (path.to_string(), object.unwrap())
+    }
+
+    #[test]
+    fn addresses_are_relocated() {
+        let objects = [
+            object("helper.rvmasm", "routine: helper\n    halt\nend\n"),
+            object("main.rvmasm", "routine: entry\n    halt\nend\ntable: .words helper table + 1 lo(7)\n"),
+        ];
+        let mut diagnostics = Diagnostics::default();
+        let image = link(&objects, &Layout::default(), &mut diagnostics).unwrap();
+        assert!(diagnostics.list.is_empty());
+        // helper takes 0x1002 - 0x1003, entry 0x1004 - 0x1005 and the table starts at 0x1006
+        let table = CODE_START + 4;
+        assert_eq!(image.memory[table..table + 3], [CODE_START as u16, table as u16 + 1, 7]);
+        assert_eq!(image.memory[CODE_START - 1], CODE_START as u16 + 2);
+    }
+
+    #[test]
+    fn symbols_must_be_exported_once() {
+        let main = object("main.rvmasm", "routine: entry\n    halt\nend\ntable: .word helpr\n");
+        let helper = object("helper.rvmasm", "routine: helper\n    halt\nend\n");
+        let mut diagnostics = Diagnostics::default();
+        assert!(link(&[main.clone(), helper.clone()], &Layout::default(), &mut diagnostics).is_none());
+        assert_eq!(diagnostics.list.len(), 1);
+        assert_eq!(diagnostics.list[0].code, diagnostics::E_UNDEFINED);
+
+        let mut diagnostics = Diagnostics::default();
+        let main = object("main.rvmasm", "routine: entry\n    jusr helper\nend\n");
+        let objects = [main, helper.clone(), ("other.rvmasm".to_string(), helper.1)];
+        assert!(link(&objects, &Layout::default(), &mut diagnostics).is_none());
+        let [duplicate] = &diagnostics.list[..] else { panic!("{:?}", diagnostics.list) };
+        assert_eq!(duplicate.code, diagnostics::E_DUPLICATE);
+        assert_eq!(
+            duplicate.message,
+            "Symbol `helper` is exported by both `helper.rvmasm` and `other.rvmasm`"
+        );
+    }
+
+    #[test]
+    fn unused_names_can_be_shared() {
+        let objects = [
+            object(
+                "main.rvmasm",
+                ".equ WIDTH 80\nroutine: entry\n    jusr print\nend\nroutine: wait\n    rtor\nend\n",
+            ),
+            object(
+                "print.rvmasm",
+                ".equ WIDTH 40\nroutine: print\n    jusr wait\nend\nroutine: wait\n    rtor\nend\n",
+            ),
+        ];
+        let mut diagnostics = Diagnostics::default();
+        link(&objects, &Layout::default(), &mut diagnostics).unwrap();
+        assert!(diagnostics.list.is_empty(), "{:?}", diagnostics.list);
+    }
+}
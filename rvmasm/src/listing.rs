@@ -0,0 +1,207 @@
+use crate::assembler::{Image, SymbolKind};
+use crate::source::{SourceFile, Span};
+use std::fmt::Write;
+
+/// Number of words shown per line of the listing
+const WORDS_PER_LINE: usize = 4;
+/// Width of the column with the words
+const WORDS_WIDTH: usize = WORDS_PER_LINE * 5;
+
+/// Returns the listing of an image: the address and words of every instruction and data directive
+/// next to the line of code they were assembled from, in the order they are written. Instructions
+/// that stand for many words, like ```draw str```, continue on the lines below. Code from a macro
+/// shows the line in the macro with the name of the macro.
+pub fn listing(image: &Image) -> String {
+    let (sources, expansions) = (&image.diagnostics.sources, &image.diagnostics.expansions);
+    let locations: Vec<String> =
+        image.listing.iter().map(|listed| location(sources, listed.span)).collect();
+    let width = locations.iter().map(|location| location.len()).max().unwrap_or_default();
+
+    let mut text = String::new();
+    _ = writeln!(text, "{:<6}{:<WORDS_WIDTH$}  {:<width$}  Source", "Addr", "Words", "Line");
+    for (listed, location) in image.listing.iter().zip(locations) {
+        let mut rows = listed.words.chunks(WORDS_PER_LINE).map(|words| {
+            words.iter().map(|word| format!("{:04X}", word)).collect::<Vec<_>>().join(" ")
+        });
+        let line = source_line(sources, listed.span);
+        let expansion = listed.span.expansion.and_then(|index| expansions.get(index as usize));
+        let line = match expansion {
+            Some(expansion) => format!("{}  ; in macro `{}`", line, expansion.name),
+            None => line.to_string(),
+        };
+        _ = writeln!(
+            text,
+            "{:04X}  {:<WORDS_WIDTH$}  {:<width$}  {}",
+            listed.address,
+            rows.next().unwrap_or_default(),
+            location,
+            line,
+        );
+        for row in rows {
+            _ = writeln!(text, "      {}", row);
+        }
+    }
+    text
+}
+
+/// Returns the map of an image: every routine, label and constant with its address or value and
+/// the number of addresses it takes up, ordered by address with the constants and their values last
+pub fn map(image: &Image) -> String {
+    let sources = &image.diagnostics.sources;
+    let mut symbols: Vec<_> = image.symbols.iter().collect();
+    symbols.sort_by_key(|symbol| (symbol.kind == SymbolKind::Constant, symbol.value));
+    let width = symbols.iter().map(|symbol| display_name(symbol).len()).max().unwrap_or_default().max(4);
+
+    let mut text = String::new();
+    _ = writeln!(text, "{:<8}{:<8}{:<10}{:<width$}  Defined at", "Address", "Size", "Kind", "Name");
+    for symbol in symbols {
+        // The address column holds the value of constants
+        let size = symbol.size.map(|size| size.to_string()).unwrap_or_default();
+        _ = writeln!(
+            text,
+            "{:<8}{:<8}{:<10}{:<width$}  {}",
+            format!("{:#06X}", symbol.value),
+            size,
+            symbol.kind.name(),
+            display_name(symbol),
+            location(sources, symbol.span),
+        );
+    }
+    text
+}
+
+/// Returns the symbols of an image as JSON for the VM's debugger. Every symbol has its name, kind,
+/// its address (or value for constants), its size if it has one, the routine it belongs to if it
+/// is a local label, and the file and line it is defined in.
+pub fn symbols_json(image: &Image) -> String {
+    let sources = &image.diagnostics.sources;
+    let mut text = String::from("{\n  \"symbols\": [");
+    for (index, symbol) in image.symbols.iter().enumerate() {
+        let mut fields = vec![
+            format!("\"name\": {}", json_string(&symbol.name)),
+            format!("\"kind\": \"{}\"", symbol.kind.name()),
+        ];
+        fields.push(match symbol.kind {
+            SymbolKind::Constant => format!("\"value\": {}", symbol.value),
+            _ => format!("\"address\": {}", symbol.value),
+        });
+        fields.extend(symbol.size.map(|size| format!("\"size\": {}", size)));
+        if let Some(routine) = &symbol.routine {
+            fields.push(format!("\"routine\": {}", json_string(routine)));
+        }
+        if let Some(source) = sources.get(symbol.span.file) {
+            fields.push(format!("\"file\": {}", json_string(&source.path)));
+            fields.push(format!("\"line\": {}", symbol.span.line));
+        }
+        let separator = if index == 0 { "" } else { "," };
+        _ = write!(text, "{}\n    {{ {} }}", separator, fields.join(", "));
+    }
+    text.push_str("\n  ]\n}\n");
+    text
+}
+
+/// Returns the name of a symbol, with the routine in front of local labels
+fn display_name(symbol: &crate::assembler::Symbol) -> String {
+    match &symbol.routine {
+        Some(routine) => format!("{}{}", routine, symbol.name),
+        None => symbol.name.clone(),
+    }
+}
+
+/// Returns the file and line a span starts in, like ```main.rvmasm:4```
+fn location(sources: &[SourceFile], span: Span) -> String {
+    match sources.get(span.file) {
+        Some(source) => format!("{}:{}", source.path, span.line),
+        None => String::new(),
+    }
+}
+
+/// Returns the line a span starts in without its indentation
+fn source_line(sources: &[SourceFile], span: Span) -> &str {
+    sources
+        .get(span.file)
+        .and_then(|source| source.text.lines().nth(span.line.saturating_sub(1)))
+        .unwrap_or_default()
+        .trim()
+}
+
+/// Returns a string in double quotes with the characters JSON doesn't allow in strings escaped
+fn json_string(text: &str) -> String {
+    let mut escaped = String::from("\"");
+    for char in text.chars() {
+        match char {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            char if (char as u32) < 0x20 => _ = write!(escaped, "\\u{:04x}", char as u32),
+            char => escaped.push(char),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Options;
+
+    const CODE: &str = ".equ LIMIT 3\ntable: .words num 1 num 2\n\
+        routine: entry\n    load A num LIMIT\n.loop:\n    jump .loop\nend\n";
+
+    fn assembled() -> Image {
+        crate::assemble(CODE, &Options::default()).unwrap()
+    }
+
+    #[test]
+    fn words_are_listed_next_to_their_line() {
+        let image = assembled();
+        let listing = listing(&image);
+        let lines: Vec<&str> = listing.lines().map(str::trim_end).collect();
+        assert_eq!(
+            lines,
+            [
+                "Addr  Words                 Line           Source",
+                "1002                        main.rvmasm:2  table: .words num 1 num 2",
+                "1002  0001 0002             main.rvmasm:2  table: .words num 1 num 2",
+                "1004                        main.rvmasm:3  routine: entry",
+                "1004  00A1 0003             main.rvmasm:4  load A num LIMIT",
+                "1006                        main.rvmasm:5  .loop:",
+                "1006  00C0 1006             main.rvmasm:6  jump .loop",
+            ]
+        );
+    }
+
+    #[test]
+    fn symbols_are_mapped_by_address() {
+        let image = assembled();
+        let map = map(&image);
+        let lines: Vec<&str> = map.lines().map(str::trim_end).collect();
+        assert_eq!(
+            lines,
+            [
+                "Address Size    Kind      Name        Defined at",
+                "0x1002  2       data      table       main.rvmasm:2",
+                "0x1004  4       routine   entry       main.rvmasm:3",
+                "0x1006          label     entry.loop  main.rvmasm:5",
+                "0x0003          constant  LIMIT       main.rvmasm:1",
+            ]
+        );
+    }
+
+    #[test]
+    fn symbols_are_written_as_json() {
+        let image = assembled();
+        let json = symbols_json(&image);
+        assert!(json.starts_with(
+            "{\n  \"symbols\": [\n    { \"name\": \"LIMIT\", \"kind\": \"constant\", \"value\": 3"
+        ));
+        assert!(json.contains(
+            "{ \"name\": \".loop\", \"kind\": \"label\", \"address\": 4102, \"routine\": \"entry\", \
+            \"file\": \"main.rvmasm\", \"line\": 5 }"
+        ));
+        assert!(json.ends_with("}\n  ]\n}\n"));
+        assert_eq!(json_string("a \"b\"\\\n\u{1}"), "\"a \\\"b\\\"\\\\\\n\\u0001\"");
+    }
+}
@@ -0,0 +1,409 @@
+use crate::diagnostics::{self, Diagnostic, Diagnostics};
+use crate::lexer::{Token, TokenKind};
+use crate::parser::MNEMONICS;
+use crate::source::{Expansion, Span};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// Number of macro calls that may be nested inside each other, which stops a macro that calls
+/// itself from expanding forever
+pub const MAX_DEPTH: usize = 32;
+
+/// Words that start something else and can't be the name of a macro
+const KEYWORDS: [&str; 4] = ["macro", "routine", "filesys", "end"];
+
+/// ```macro: <name> <parameter>, ...``` up to its ```end```
+struct Macro {
+    name: String,
+    parameters: Vec<String>,
+    body: Vec<Part>,
+    /// Local labels defined in the body, which get a new name in every expansion
+    labels: HashSet<String>,
+    /// The line with the macro's name and parameters
+    span: Span,
+}
+
+/// A token of a macro's body, or the place one of its arguments is put
+enum Part {
+    Token(Token),
+    Parameter(usize),
+}
+
+/// Removes the macro definitions from the tokens and replaces every call with the body of the
+/// macro. A call is the name of a macro at the start of a line or after a label, followed by
+/// its arguments separated by commas. Macros may be called before they are defined and may
+/// call other macros.
+/// The tokens copied from a body keep pointing at the macro's definition. They are marked with
+/// the expansion they belong to, which is added to the diagnostics and points at the call.
+pub fn expand(tokens: &[Token], diagnostics: &mut Diagnostics) -> Vec<Token> {
+    let Some((eof, tokens)) = tokens.split_last() else {
+        return Vec::new();
+    };
+    let mut expander = Expander { macros: HashMap::new(), diagnostics, too_deep: false };
+    let rest = expander.define_all(tokens);
+    let mut output = Vec::new();
+    expander.expand(&rest, 0, None, &mut output);
+    output.push(eof.clone());
+    output
+}
+
+struct Expander<'a> {
+    macros: HashMap<String, Rc<Macro>>,
+    diagnostics: &'a mut Diagnostics,
+    /// Set once the calls were nested too deeply, after which nothing is expanded anymore
+    too_deep: bool,
+}
+
+/// Splits tokens into lines, each ending with its newline token unless it is the last one
+fn lines(tokens: &[Token]) -> impl Iterator<Item = &[Token]> {
+    tokens.split_inclusive(|token| token.kind == TokenKind::Newline)
+}
+
+fn ident(token: Option<&Token>) -> Option<&str> {
+    match token.map(|token| &token.kind) {
+        Some(TokenKind::Ident(name)) => Some(name),
+        _ => None,
+    }
+}
+
+fn is_punct(token: Option<&Token>, char: char) -> bool {
+    token.is_some_and(|token| token.kind == TokenKind::Punct(char))
+}
+
+/// Returns true if the line starts a macro, a routine or a filesystem
+fn starts_item(line: &[Token]) -> bool {
+    ident(line.first()).is_some_and(|name| matches!(name, "macro" | "routine" | "filesys"))
+        && is_punct(line.get(1), ':')
+}
+
+impl Expander<'_> {
+    /// Collects every macro definition and returns the tokens outside of them
+    fn define_all(&mut self, tokens: &[Token]) -> Vec<Token> {
+        let lines: Vec<&[Token]> = lines(tokens).collect();
+        let mut rest = Vec::new();
+        let mut index = 0;
+        while index < lines.len() {
+            let header = lines[index];
+            index += 1;
+            if ident(header.first()) != Some("macro") || !is_punct(header.get(1), ':') {
+                rest.extend_from_slice(header);
+                continue;
+            }
+
+            let start = index;
+            let is_end = |line: &[Token]| ident(line.first()) == Some("end") || starts_item(line);
+            while index < lines.len() && !is_end(lines[index]) {
+                index += 1;
+            }
+            let body = &lines[start..index];
+            let span = header[0].span.to(header[header.len() - 1].span);
+            let parsed = self.header(header);
+            match lines.get(index) {
+                Some(end) if ident(end.first()) == Some("end") => {
+                    index += 1;
+                    if end.len() > 1 && end[1].kind != TokenKind::Newline {
+                        self.diagnostics.push(Diagnostic::error(
+                            diagnostics::E_SYNTAX,
+                            "Expected the end of the line after `end`",
+                            end[1].span,
+                        ));
+                    }
+                }
+                found => {
+                    let found = found.map_or(tokens.last(), |line| line.first());
+                    let found = found.map_or(span, |token| token.span);
+                    let name = parsed.as_ref().ok().and_then(|(name, _)| ident(Some(name)));
+                    let name = name.unwrap_or_default();
+                    self.diagnostics.push(
+                        Diagnostic::error(
+                            diagnostics::E_MISSING_END,
+                            format!("Macro `{}` is missing its `end`", name),
+                            found,
+                        )
+                        .with_note_at("it starts here", header[0].span),
+                    );
+                }
+            }
+            match parsed {
+                Ok((name, parameters)) => self.define(name, parameters, body, span),
+                Err(diagnostic) => self.diagnostics.push(diagnostic),
+            }
+        }
+        rest
+    }
+
+    /// Parses the name and parameters of ```macro: <name> <parameter>, ...```
+    fn header(&self, header: &[Token]) -> Result<(Token, Vec<Token>), Diagnostic> {
+        let mut tokens = header[2..].iter().filter(|token| token.kind != TokenKind::Newline).peekable();
+        let unexpected = |token: Option<&Token>, expected: &str| {
+            let span = token.map_or(header[header.len() - 1].span, |token| token.span);
+            let message = format!("Expected {} in the macro definition", expected);
+            Diagnostic::error(diagnostics::E_SYNTAX, message, span)
+        };
+
+        let name = tokens.next().filter(|token| ident(Some(token)).is_some());
+        let name = name.ok_or_else(|| unexpected(header.get(2), "a macro name"))?;
+        let mut parameters = Vec::new();
+        while let Some(token) = tokens.next() {
+            if ident(Some(token)).is_none() {
+                return Err(unexpected(Some(token), "a parameter name"));
+            }
+            parameters.push(token.clone());
+            match tokens.next() {
+                None => break,
+                Some(token) if is_punct(Some(token), ',') => {
+                    if tokens.peek().is_none() {
+                        return Err(unexpected(None, "a parameter name after `,`"));
+                    }
+                }
+                token => return Err(unexpected(token, "`,` between the parameters")),
+            }
+        }
+        Ok((name.clone(), parameters))
+    }
+
+    fn define(&mut self, name: Token, parameters: Vec<Token>, lines: &[&[Token]], span: Span) {
+        let name_text = ident(Some(&name)).unwrap_or_default().to_string();
+        if MNEMONICS.contains(&name_text.as_str()) || KEYWORDS.contains(&name_text.as_str()) {
+            self.diagnostics.push(Diagnostic::error(
+                diagnostics::E_MACRO,
+                format!("`{}` is a keyword or instruction and can't be the name of a macro", name_text),
+                name.span,
+            ));
+            return;
+        }
+        if let Some(first) = self.macros.get(&name_text) {
+            self.diagnostics.push(
+                Diagnostic::error(
+                    diagnostics::E_DUPLICATE,
+                    format!("Macro `{}` is defined more than once", name_text),
+                    name.span,
+                )
+                .with_note_at(format!("`{}` first defined here", name_text), first.span),
+            );
+            return;
+        }
+
+        let parameter_names: Vec<&str> = parameters.iter().filter_map(|token| ident(Some(token))).collect();
+        let mut body = Vec::new();
+        let mut labels = HashSet::new();
+        for line in lines {
+            let label = ident(line.first()).filter(|name| name.starts_with('.'));
+            let label = label.filter(|_| is_punct(line.get(1), ':'));
+            labels.extend(label.map(str::to_string));
+
+            let mut tokens = line.iter().peekable();
+            while let Some(token) = tokens.next() {
+                // ```\<parameter>``` is replaced with the argument given for the parameter
+                let parameter = tokens
+                    .next_if(|next| is_punct(Some(token), '\\') && token.span.touches(next.span))
+                    .and_then(|next| Some((next, ident(Some(next))?)));
+                let Some((next, parameter)) = parameter else {
+                    body.push(Part::Token(token.clone()));
+                    continue;
+                };
+                match parameter_names.iter().position(|name| *name == parameter) {
+                    Some(index) => body.push(Part::Parameter(index)),
+                    None => {
+                        let mut diagnostic = Diagnostic::error(
+                            diagnostics::E_MACRO,
+                            format!("Macro `{}` has no parameter `{}`", name_text, parameter),
+                            token.span.to(next.span),
+                        );
+                        if let Some(suggestion) = diagnostics::suggest(parameter, &parameter_names) {
+                            diagnostic = diagnostic.with_note(format!("did you mean `\\{}`?", suggestion));
+                        }
+                        self.diagnostics.push(diagnostic);
+                    }
+                }
+            }
+        }
+
+        let parameters = parameter_names.iter().map(|name| name.to_string()).collect();
+        let definition = Macro { name: name_text.clone(), parameters, body, labels, span };
+        self.macros.insert(name_text, Rc::new(definition));
+    }
+
+    /// Copies the tokens to the output, replacing every macro call with the macro's body. The
+    /// root is the call in the source code that the calls being expanded come from.
+    fn expand(&mut self, tokens: &[Token], depth: usize, root: Option<Span>, output: &mut Vec<Token>) {
+        for line in lines(tokens) {
+            // A label in front of a call stays in front of the macro's first line
+            let start = match ident(line.first()) {
+                Some(name) if is_punct(line.get(1), ':') && !matches!(name, "routine" | "filesys") => 2,
+                _ => 0,
+            };
+            let definition = ident(line.get(start))
+                .filter(|_| !is_punct(line.get(start + 1), ':'))
+                .and_then(|name| self.macros.get(name))
+                .cloned();
+            let Some(definition) = definition else {
+                output.extend_from_slice(line);
+                continue;
+            };
+
+            output.extend_from_slice(&line[..start]);
+            let (call, newline) = match line.split_last() {
+                Some((last, call)) if last.kind == TokenKind::Newline => (&call[start..], Some(last)),
+                _ => (&line[start..], None),
+            };
+            if !self.too_deep {
+                self.call(&definition, call, depth, root, output);
+            }
+            output.extend(newline.cloned());
+        }
+    }
+
+    fn call(
+        &mut self,
+        definition: &Macro,
+        call: &[Token],
+        depth: usize,
+        root: Option<Span>,
+        output: &mut Vec<Token>,
+    ) {
+        let span = call[0].span.to(call[call.len() - 1].span);
+        let mut arguments: Vec<&[Token]> =
+            call[1..].split(|token| token.kind == TokenKind::Punct(',')).collect();
+        if call.len() == 1 {
+            arguments.clear();
+        }
+        if let Some(empty) = arguments.iter().position(|argument| argument.is_empty()) {
+            let found = arguments[..empty].iter().map(|argument| argument.len() + 1).sum::<usize>();
+            let span = call.get(found).map_or(span, |token| token.span);
+            self.diagnostics.push(Diagnostic::error(
+                diagnostics::E_SYNTAX,
+                "Expected an argument next to `,`",
+                span,
+            ));
+            return;
+        }
+        if arguments.len() != definition.parameters.len() {
+            let given = match arguments.len() {
+                1 => "1 was".to_string(),
+                count => format!("{} were", count),
+            };
+            self.diagnostics.push(
+                Diagnostic::error(
+                    diagnostics::E_MACRO,
+                    format!(
+                        "Macro `{}` takes {} argument{}, but {} given",
+                        definition.name,
+                        definition.parameters.len(),
+                        if definition.parameters.len() == 1 { "" } else { "s" },
+                        given
+                    ),
+                    span,
+                )
+                .with_note_at(format!("`{}` is defined here", definition.name), definition.span),
+            );
+            return;
+        }
+        if depth == MAX_DEPTH {
+            self.too_deep = true;
+            self.diagnostics.push(
+                Diagnostic::error(
+                    diagnostics::E_MACRO,
+                    format!("Macro calls are nested more than {} levels deep", MAX_DEPTH),
+                    root.unwrap_or(span),
+                )
+                .with_note_at(
+                    format!("`{}` is still called at that depth, maybe it calls itself", definition.name),
+                    definition.span,
+                ),
+            );
+            return;
+        }
+
+        let expansion = self.diagnostics.expansions.len();
+        self.diagnostics.expansions.push(Expansion { name: definition.name.clone(), call: span });
+        let mut body = Vec::new();
+        for part in &definition.body {
+            match part {
+                Part::Token(token) => {
+                    let mut token = token.clone();
+                    token.span.expansion = Some(expansion as u32);
+                    match &mut token.kind {
+                        TokenKind::Ident(name) if definition.labels.contains(name.as_str()) => {
+                            *name = format!("{}@{}", name, expansion);
+                        }
+                        _ => {}
+                    }
+                    body.push(token);
+                }
+                Part::Parameter(index) => body.extend_from_slice(arguments[*index]),
+            }
+        }
+        self.expand(&body, depth + 1, Some(root.unwrap_or(span)), output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+
+    /// Expands the macros of the code, returning the kinds of the tokens left
+    fn expanded(code: &str) -> (Vec<TokenKind>, Diagnostics) {
+        let mut diagnostics = Diagnostics::default();
+        let tokens = expand(&tokenize(code, 0), &mut diagnostics);
+        (tokens.into_iter().map(|token| token.kind).collect(), diagnostics)
+    }
+
+    fn kinds(code: &str) -> Vec<TokenKind> {
+        tokenize(code, 0).into_iter().map(|token| token.kind).collect()
+    }
+
+    #[test]
+    fn arguments_replace_their_parameters() {
+        let (tokens, diagnostics) = expanded(
+            "macro: put value, register\nload \\register \\value\nend\nput num 1 + 2, A\nput 3, X\n",
+        );
+        assert!(diagnostics.list.is_empty());
+        assert_eq!(tokens, kinds("load A num 1 + 2\n\nload X 3\n\n"));
+    }
+
+    #[test]
+    fn macros_pass_arguments_on() {
+        let code = "macro: twice text\nshow \\text\nshow \\text\nend\n\
+            macro: show text\ndraw str \\text\nend\n.x: twice \"a, b\"\n";
+        let (tokens, diagnostics) = expanded(code);
+        assert!(diagnostics.list.is_empty());
+        assert_eq!(tokens, kinds(".x: draw str \"a, b\"\n\ndraw str \"a, b\"\n\n\n"));
+    }
+
+    #[test]
+    fn labels_are_renamed_in_every_call() {
+        let (tokens, diagnostics) = expanded("macro: spin\n.loop:\njump .loop\nend\nspin\nspin\n");
+        assert!(diagnostics.list.is_empty());
+        let labels: Vec<&TokenKind> = tokens
+            .iter()
+            .filter(|kind| matches!(kind, TokenKind::Ident(name) if name.starts_with('.')))
+            .collect();
+        assert_eq!(labels.len(), 4);
+        assert_eq!(labels[0], labels[1]);
+        assert_eq!(labels[2], labels[3]);
+        assert_ne!(labels[0], labels[2]);
+    }
+
+    #[test]
+    fn wrong_arguments_are_reported() {
+        let definition = "macro: put value, register\nload \\register \\value\nend\n";
+        for call in ["put 1", "put 1, A, X", "put 1,", "put"] {
+            let (_, diagnostics) = expanded(&format!("{}{}\n", definition, call));
+            assert_eq!(diagnostics.list.len(), 1, "{}", call);
+            assert_eq!(diagnostics.list[0].span.line, 4, "{}", call);
+        }
+        let (_, diagnostics) = expanded("macro: put value\nload A \\valeu\nend\n");
+        assert_eq!(diagnostics.list.len(), 1);
+        assert_eq!(diagnostics.list[0].code, diagnostics::E_MACRO);
+    }
+
+    #[test]
+    fn recursion_stops_at_the_depth_limit() {
+        let (_, diagnostics) = expanded("macro: again\nagain\nend\nagain\n");
+        assert_eq!(diagnostics.list.len(), 1);
+        assert_eq!(diagnostics.list[0].code, diagnostics::E_MACRO);
+        assert_eq!(diagnostics.list[0].span.line, 4);
+    }
+}
@@ -0,0 +1,333 @@
+use std::fmt::Write;
+
+/// First line of every object file, followed by the format version
+const MAGIC: &str = "RVMO";
+const VERSION: u16 = 1;
+
+/// Number of words written per ```words``` line
+const WORDS_PER_LINE: usize = 16;
+
+/// A separately assembled part of a program, placed into a ROM by the linker. Object files are
+/// text, so they can be looked at and versioned like the code they were made from.
+#[derive(Debug, Clone, Default)]
+pub struct Object {
+    pub sections: Vec<Section>,
+    /// Routines, data labels and constants other objects can use
+    pub symbols: Vec<Symbol>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionKind {
+    Routine,
+    FileSystem,
+    Data,
+}
+
+impl SectionKind {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Routine => "routine",
+            Self::FileSystem => "filesys",
+            Self::Data => "data",
+        }
+    }
+}
+
+/// A routine, a filesystem or the data between them, which the linker moves as a whole
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub kind: SectionKind,
+    pub name: String,
+    /// Address the section was assembled at
+    pub address: u16,
+    pub words: Vec<u16>,
+    /// Number of addresses the section takes up, including the unused address after routines and
+    /// filesystems and the space preallocated for filesystems
+    pub size: usize,
+    /// Name of the region of the layout the section is placed into
+    pub region: String,
+    /// Whether the section was placed with ```.org```, so the linker must not move it
+    pub fixed: bool,
+    pub relocations: Vec<Relocation>,
+}
+
+impl Section {
+    /// Returns the number of addresses the words and the preallocated space take up
+    pub fn used(&self) -> usize {
+        match self.kind {
+            SectionKind::Data => self.size,
+            SectionKind::Routine | SectionKind::FileSystem => self.size - 1,
+        }
+    }
+}
+
+/// A word holding an address that is only known once the sections are placed
+#[derive(Debug, Clone)]
+pub struct Relocation {
+    /// Index of the word in its section
+    pub offset: usize,
+    pub target: Target,
+    /// Where the address is used, like ```main.rvmasm:4:6```
+    pub location: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// The word is an address inside the section with the index, in this object. The distance
+    /// the section is moved is added to it.
+    Section(usize),
+    /// The word is added to the address of a symbol of another object
+    Symbol(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub value: SymbolValue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolValue {
+    /// An address in the section with the index, counted from the section's start
+    Section(usize, u16),
+    /// A constant that doesn't depend on any address
+    Absolute(u16),
+}
+
+impl Object {
+    /// Returns the text of the object file
+    pub fn write(&self) -> String {
+        let mut text = format!("{} {}\n", MAGIC, VERSION);
+        for section in &self.sections {
+            _ = writeln!(
+                text,
+                "section {} {} {:#06X} {} {}{}",
+                section.kind.name(),
+                section.name,
+                section.address,
+                section.size,
+                section.region,
+                if section.fixed { " fixed" } else { "" }
+            );
+            for words in section.words.chunks(WORDS_PER_LINE) {
+                let words: Vec<String> = words.iter().map(|word| format!("{:04X}", word)).collect();
+                _ = writeln!(text, "words {}", words.join(" "));
+            }
+            for relocation in &section.relocations {
+                let target = match &relocation.target {
+                    Target::Section(index) => format!("section {}", index),
+                    Target::Symbol(name) => format!("symbol {}", name),
+                };
+                _ = writeln!(text, "reloc {} {} {}", relocation.offset, target, relocation.location);
+            }
+        }
+        for symbol in &self.symbols {
+            let value = match symbol.value {
+                SymbolValue::Section(index, offset) => format!("section {} {}", index, offset),
+                SymbolValue::Absolute(value) => format!("absolute {}", value),
+            };
+            _ = writeln!(text, "symbol {} {}", symbol.name, value);
+        }
+        text
+    }
+
+    /// Reads the text of an object file. The error tells which line couldn't be read and why.
+    pub fn read(text: &str) -> Result<Object, String> {
+        let mut lines = text.lines().enumerate();
+        match lines.next().map(|(_, line)| line.split_whitespace().collect::<Vec<_>>()) {
+            Some(header) if header.first() == Some(&MAGIC) => {
+                if header.get(1) != Some(&VERSION.to_string().as_str()) {
+                    return Err(format!("unsupported version, expected {} {}", MAGIC, VERSION));
+                }
+            }
+            _ => return Err(format!("not an object file, it doesn't start with `{}`", MAGIC)),
+        }
+
+        let mut object = Object::default();
+        for (index, line) in lines {
+            let error = |message: &str| format!("line {}: {}", index + 1, message);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let number = |field: usize| -> Result<usize, String> {
+                let text = fields.get(field).ok_or_else(|| error("missing a number"))?;
+                let parsed = match text.strip_prefix("0x") {
+                    Some(hex) => usize::from_str_radix(hex, 16),
+                    None => text.parse(),
+                };
+                parsed.map_err(|_| error(&format!("`{}` is not a number", text)))
+            };
+            let word = |field: usize| number(field).and_then(|number| {
+                u16::try_from(number).map_err(|_| error(&format!("{} doesn't fit into a word", number)))
+            });
+            let name = |field: usize| {
+                fields.get(field).map(|name| name.to_string()).ok_or_else(|| error("missing a name"))
+            };
+
+            match fields.first() {
+                None => {}
+                Some(&"section") => {
+                    let kind = match fields.get(1) {
+                        Some(&"routine") => SectionKind::Routine,
+                        Some(&"filesys") => SectionKind::FileSystem,
+                        Some(&"data") => SectionKind::Data,
+                        _ => return Err(error("unknown section kind")),
+                    };
+                    object.sections.push(Section {
+                        kind,
+                        name: name(2)?,
+                        address: word(3)?,
+                        words: Vec::new(),
+                        size: number(4)?,
+                        region: name(5)?,
+                        fixed: match fields.get(6) {
+                            None => false,
+                            Some(&"fixed") => true,
+                            Some(other) => return Err(error(&format!("expected `fixed`, found `{}`", other))),
+                        },
+                        relocations: Vec::new(),
+                    });
+                }
+                Some(&"words" | &"reloc") if object.sections.is_empty() => {
+                    return Err(error("words and relocations must follow a section"));
+                }
+                Some(&"words") => {
+                    let words = (1..fields.len()).map(|field| {
+                        u16::from_str_radix(fields[field], 16)
+                            .map_err(|_| error(&format!("`{}` is not a hexadecimal word", fields[field])))
+                    });
+                    let words = words.collect::<Result<Vec<u16>, String>>()?;
+                    object.sections.last_mut().unwrap().words.extend(words);
+                }
+                Some(&"reloc") => {
+                    let target = match fields.get(2) {
+                        Some(&"section") => Target::Section(number(3)?),
+                        Some(&"symbol") => Target::Symbol(name(3)?),
+                        _ => return Err(error("unknown relocation target")),
+                    };
+                    // The location ends the line, since the path in it may contain spaces
+                    let location = rest(line, 4).ok_or_else(|| error("missing a location"))?.to_string();
+                    let relocation = Relocation { offset: number(1)?, target, location };
+                    object.sections.last_mut().unwrap().relocations.push(relocation);
+                }
+                Some(&"symbol") => {
+                    let value = match fields.get(2) {
+                        Some(&"section") => SymbolValue::Section(number(3)?, word(4)?),
+                        Some(&"absolute") => SymbolValue::Absolute(word(3)?),
+                        _ => return Err(error("unknown symbol value")),
+                    };
+                    object.symbols.push(Symbol { name: name(1)?, value });
+                }
+                Some(other) => return Err(error(&format!("unknown entry `{}`", other))),
+            }
+        }
+
+        // Relocations may refer to sections that come after them
+        let sections = object.sections.len();
+        let relocations = object.sections.iter().flat_map(|section| &section.relocations);
+        let targets = relocations.filter_map(|relocation| match relocation.target {
+            Target::Section(index) => Some(index),
+            Target::Symbol(_) => None,
+        });
+        let symbols = object.symbols.iter().filter_map(|symbol| match symbol.value {
+            SymbolValue::Section(index, _) => Some(index),
+            SymbolValue::Absolute(_) => None,
+        });
+        if let Some(index) = targets.chain(symbols).find(|index| *index >= sections) {
+            return Err(format!("there is no section {}", index));
+        }
+        for (index, section) in object.sections.iter().enumerate() {
+            if section.kind != SectionKind::Data && section.size < 1 {
+                // Routines and filesystems are followed by an unused address
+                return Err(format!("section {} is a {} with a size of 0", index, section.kind.name()));
+            }
+            if section.words.len() > section.size {
+                return Err(format!("section {} has more words than its size", index));
+            }
+            let mut offsets = section.relocations.iter().map(|relocation| relocation.offset);
+            if let Some(offset) = offsets.find(|offset| *offset >= section.words.len()) {
+                return Err(format!("section {} has a relocation at {} after its words", index, offset));
+            }
+        }
+        Ok(object)
+    }
+}
+
+/// Returns what follows a number of fields of a line, spaces included
+fn rest(line: &str, fields: usize) -> Option<&str> {
+    let mut rest = line.trim();
+    for _ in 0..fields {
+        rest = rest.split_once(char::is_whitespace)?.1.trim_start();
+    }
+    Some(rest).filter(|rest| !rest.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object() -> Object {
+        let section = |kind, name: &str, address, words: Vec<u16>, size, fixed| Section {
+            kind,
+            name: name.to_string(),
+            address,
+            words,
+            size,
+            region: "code".to_string(),
+            fixed,
+            relocations: Vec::new(),
+        };
+        let mut routine = section(SectionKind::Routine, "entry", 0x1002, vec![0x0001; 20], 21, false);
+        routine.relocations = vec![
+            Relocation { offset: 1, target: Target::Section(2), location: "main.rvmasm:2:5".to_string() },
+            Relocation {
+                offset: 17,
+                target: Target::Symbol("print".to_string()),
+                location: "my programs/main file.rvmasm:9:12".to_string(),
+            },
+        ];
+        Object {
+            sections: vec![
+                routine,
+                section(SectionKind::FileSystem, "filesystem", 0x1017, Vec::new(), 65, false),
+                section(SectionKind::Data, "table", 0x2000, vec![0xFFFF, 0x1002], 3, true),
+            ],
+            symbols: vec![
+                Symbol { name: "entry".to_string(), value: SymbolValue::Section(0, 0) },
+                Symbol { name: "WIDTH".to_string(), value: SymbolValue::Absolute(80) },
+            ],
+        }
+    }
+
+    #[test]
+    fn objects_are_read_as_written() {
+        let text = object().write();
+        let read = Object::read(&text).unwrap();
+        assert_eq!(read.write(), text);
+
+        let routine = &read.sections[0];
+        assert_eq!((routine.kind, routine.address, routine.size), (SectionKind::Routine, 0x1002, 21));
+        assert_eq!(routine.words, [0x0001; 20]);
+        assert_eq!(routine.relocations[1].target, Target::Symbol("print".to_string()));
+        assert_eq!(routine.relocations[1].location, "my programs/main file.rvmasm:9:12");
+        assert!(read.sections[2].fixed);
+        assert_eq!(read.symbols[1].value, SymbolValue::Absolute(80));
+    }
+
+    #[test]
+    fn broken_objects_are_rejected() {
+        let text = object().write();
+        let broken = [
+            text.replacen("RVMO 1", "RVMO 2", 1),
+            text.replacen("section data table 0x2000 3", "section data table 0x2000 1", 1),
+            text.replacen("section routine entry 0x1002 21", "section routine entry 0x1002 0", 1),
+            text.replacen("section data", "section text", 1),
+            text.replacen("reloc 1 section 2", "reloc 1 section 3", 1),
+            text.replacen("reloc 17", "reloc 20", 1),
+            text.replacen("reloc 1 section 2 main.rvmasm:2:5", "reloc 1 section 2", 1),
+            text.replacen("words FFFF", "words FFFFF", 1),
+            "RVMO 1\nwords 0001\n".to_string(),
+        ];
+        for text in broken {
+            assert!(Object::read(&text).is_err(), "{}", text);
+        }
+    }
+}
@@ -11,27 +11,35 @@ pub const HALT_LOOP: u16 = 0x00AF;
 // --- OpCodes: Load into Register ---
 /// OpCode: Loads the following value into A register
 pub const LOAD_AREG: u16 = 0x00A1;
-/// OpCode: Loads the following value into B register
-pub const LOAD_BREG: u16 = 0x00A2;
-/// OpCode: Loads the following value into C register
-pub const LOAD_CREG: u16 = 0x00A3;
-/// OpCode: Loads the following value into D register
-pub const LOAD_DREG: u16 = 0x00A4;
+/// OpCode: Loads the following value into X register
+pub const LOAD_XREG: u16 = 0x00A2;
+/// OpCode: Loads the following value into Y register
+pub const LOAD_YREG: u16 = 0x00A3;
 /// OpCode: Loads the following value into G register
 pub const LOAD_GREG: u16 = 0x00A5;
 
+// --- OpCodes: Load from Address into Register ---
+/// OpCode: Loads the value at the following address into A register
+pub const LOAD_AMEM: u16 = 0x00A6;
+/// OpCode: Loads the value at the following address into X register
+pub const LOAD_XMEM: u16 = 0x00A7;
+/// OpCode: Loads the value at the following address into Y register
+pub const LOAD_YMEM: u16 = 0x00A8;
+
 // --- OpCodes: Load into Register ---
 /// OpCode: Stores the A register's value to the following address
 pub const STOR_AREG: u16 = 0x00B1;
-/// OpCode: Stores the B register's value to the following address
-pub const STOR_BREG: u16 = 0x00B2;
-/// OpCode: Stores the C register's value to the following address
-pub const STOR_CREG: u16 = 0x00B3;
-/// OpCode: Stores the D register's value to the following address
-pub const STOR_DREG: u16 = 0x00B4;
-/// OpCode: Stores the G register's value to the following address
+/// OpCode: Stores the X register's value to the following address
+pub const STOR_XREG: u16 = 0x00B2;
+/// OpCode: Stores the Y register's value to the following address
+pub const STOR_YREG: u16 = 0x00B3;
+/// OpCode: Pushes the G register's value to the GPU's command ring. Waits while the ring is full.
 pub const STOR_GREG: u16 = 0x00B5;
 
+// --- OpCodes: Store Value ---
+/// OpCode: Stores the second value after the opcode to the address given by the first one
+pub const STOR_IMMV: u16 = 0x00B6;
+
 // --- OpCodes: Jump to Subroutine ---
 /// OpCode: Sets the instruction pointer to the value of the following address, jumping there.
 ///         This also pushes the previous value to the stack, allowing to return to where the
@@ -79,6 +87,20 @@ pub const MUL_REG_V: u16 = 0x00D4;
 ///          specified in the second address after the opcode
 pub const DIV_REG_V: u16 = 0x00D5;
 
+// --- OpCodes: Set interrupt handler ---
+/// OpCode: Sets the address of the routine that is called after the GPU finished a frame (vblank
+///         interrupt) to the following value. An address of 0x0000 disables the interrupt.
+pub const SET_INT_V: u16 = 0x00E0;
+
+// --- OpCodes: Return from interrupt ---
+/// OpCode: Fetches the value the interrupt pushed to the stack and sets the instruction pointer
+///         to it, returning to where the program was interrupted.
+pub const RET_FR_INT: u16 = 0x00E1;
+
+// --- OpCodes: Ring the GPU's doorbell ---
+/// OpCode: Makes the GPU execute everything that was pushed to its command ring so far
+pub const RING_DBELL: u16 = 0x00E2;
+
 // NOTE: GPU OPCODES
 // TODO:
 
@@ -92,14 +114,21 @@ pub const GPU_NO_OPERAT: u16 = 0xA000;
 ///             a medium shade ('▒') character.
 pub const GPU_DRAW_TEXT: u16 = 0xA001;
 
+// --- OpCodes: Draw Character ---
+/// GPU OpCode: Draws the following value as a single character with the color in its high byte,
+///             automatically moving the cursor. Unlike in draw mode, this can draw a '`'.
+pub const GPU_DRAW_CHAR: u16 = 0xA004;
+
 // --- OpCodes: Draw Value ---
-/// GPU OpCode: Treats the following value as an integer, attempts to convert it to ASCII and draw it to the
-///             screen, automatically moving the cursor. If the value is invalid, it will output
-///             a medium shade ('▒') character.
+/// GPU OpCode: Reads the following two values as a format and an integer and draws the integer to
+///             the screen, automatically moving the cursor. The format holds the color in its high
+///             byte and the base in its low byte: 2 (binary), 10 (decimal) or 16 (hexadecimal).
 pub const GPU_DRAW_VALU: u16 = 0xA003;
 
 // --- OpCodes: Reset Buf Ptr ---
-/// GPU OpCode: Resets the GPU's buf_ptr to the beginning of the GPU buffer.
+/// GPU OpCode: Moves the command ring's tail up to the doorbell, discarding the commands that
+///             were handed to the GPU after this one. Commands pushed after the doorbell was rung
+///             are kept.
 pub const GPU_RESET_PTR: u16 = 0xA0A2;
 
 // --- OpCodes: Update GPU ---
@@ -130,3 +159,87 @@ pub const GPU_MV_C_RIGH: u16 = 0xA0B3;
 /// GPU OpCode: This moves inserts a new line (moves the GPU's cursor down and to the leftmost position)
 pub const GPU_NEW_LINE: u16 = 0xA0B4;
 
+// --- OpCodes: Set the cursor collumn ---
+/// GPU OpCode: This moves the GPU's cursor to the collumn specified by the following value
+pub const GPU_SET_C_X: u16 = 0xA0B5;
+
+// --- OpCodes: Set the cursor line ---
+/// GPU OpCode: This moves the GPU's cursor to the line specified by the following value
+pub const GPU_SET_C_Y: u16 = 0xA0B6;
+
+// --- OpCodes: Set character attributes ---
+/// GPU OpCode: Applies the attributes in the following value to all characters drawn after it.
+///             Bit 0 makes them bold, bit 1 swaps their colors and bit 2 makes them blink.
+pub const GPU_SET_ATTR: u16 = 0xA0C0;
+
+// --- OpCodes: Set palette entry ---
+/// GPU OpCode: Sets the palette entry at the index in the following value to the RGB565 color
+///             in the value after that
+pub const GPU_SET_PALT: u16 = 0xA0C1;
+
+// --- OpCodes: Set scroll region ---
+/// GPU OpCode: Limits scrolling to the lines between the following two values (inclusive)
+pub const GPU_SET_SCRL_R: u16 = 0xA0D0;
+
+// --- OpCodes: Scroll up ---
+/// GPU OpCode: Scrolls the scroll region up by the number of lines in the following value
+pub const GPU_SCROLL_UP: u16 = 0xA0D1;
+
+// --- OpCodes: Scroll down ---
+/// GPU OpCode: Scrolls the scroll region down by the number of lines in the following value
+pub const GPU_SCROLL_DN: u16 = 0xA0D2;
+
+// --- OpCodes: Set video mode ---
+/// GPU OpCode: Switches to the video mode in the following value: 0 for text, 1 for the bitmap
+///             and 2 for tiles and sprites
+pub const GPU_SET_MODE: u16 = 0xA0E0;
+
+// --- OpCodes: Set text mode ---
+/// GPU OpCode: Resizes the text screen to the collumns and lines in the following two values,
+///             the cell width and height in the next two and the scale factor in the fifth value.
+///             A value of 0 keeps the current setting. Clears the frame buffer.
+pub const GPU_SET_TEXT: u16 = 0xA0E7;
+
+// --- OpCodes: Set pixel ---
+/// GPU OpCode: Sets the pixel at the x and y position in the following two values to the
+///             palette index in the value after that
+pub const GPU_SET_PIXL: u16 = 0xA0E1;
+
+// --- OpCodes: Draw line ---
+/// GPU OpCode: Draws a line from the x and y position in the following two values to the x and y
+///             position in the two values after that, using the palette index in the fifth value
+pub const GPU_DRAW_LINE: u16 = 0xA0E2;
+
+// --- OpCodes: Fill rectangle ---
+/// GPU OpCode: Fills a rectangle with its top left corner at the x and y position in the
+///             following two values, the width and height in the two values after that and the
+///             palette index in the fifth value
+pub const GPU_FILL_RECT: u16 = 0xA0E3;
+
+// --- OpCodes: Blit bitmap ---
+/// GPU OpCode: Copies the image at the address in the following value into the bitmap, placing
+///             it at the x and y position in the next two values. The width and height of the
+///             image are in the fourth and fifth value. Palette index 0 is transparent.
+pub const GPU_BLIT_BMAP: u16 = 0xA0E4;
+
+// --- OpCodes: Set sprite ---
+/// GPU OpCode: Writes the sprite at the index in the following value to the sprite attribute
+///             table. The next four values are its x and y position, tile index and flags.
+pub const GPU_SET_SPRT: u16 = 0xA0E5;
+
+// --- OpCodes: Set tile ---
+/// GPU OpCode: Sets the tile map entry at the x and y position in the following two values to
+///             the tile index in the value after that
+pub const GPU_SET_TILE: u16 = 0xA0E6;
+
+// --- OpCodes: Define glyph ---
+/// GPU OpCode: Replaces the glyph of the character byte in the following value with the six
+///             values after that. Each value holds two rows of 8 pixels, the upper row in the
+///             high byte.
+pub const GPU_DEF_GLYPH: u16 = 0xA0F0;
+
+// --- OpCodes: Load glyphs ---
+/// GPU OpCode: Replaces glyphs with the ones stored at the address in the following value,
+///             starting at the character byte in the next value. The third value is the number
+///             of glyphs, each taking up six addresses.
+pub const GPU_LOAD_FONT: u16 = 0xA0F1;
@@ -0,0 +1,969 @@
+use crate::ast::*;
+use crate::diagnostics::{self, Diagnostic, Diagnostics};
+use crate::lexer::{Token, TokenKind};
+use crate::macros;
+use crate::source::{SourceFile, Span};
+
+type Result<T> = std::result::Result<T, Diagnostic>;
+
+/// Number of operators and parentheses that may be nested inside each other in a value, which
+/// keeps deeply nested values from overflowing the stack. Every operator of a chain like
+/// ```1 + 2 + 3``` nests the value before it one level deeper.
+pub const MAX_NESTING: usize = 64;
+
+/// Number of values taken by the GPU commands sharing the ```InstructionKind::Gpu``` shape and
+/// whether they take a color
+const GPU_COMMANDS: [(&str, usize, bool); 10] = [
+    ("txtm", 5, false),
+    ("pixl", 2, true),
+    ("line", 4, true),
+    ("rect", 4, true),
+    ("blit", 5, false),
+    ("glyf", 7, false),
+    ("font", 3, false),
+    ("tile", 3, false),
+    ("spal", 2, false),
+    ("cpos", 2, false),
+];
+
+/// Every mnemonic, used to suggest one for unknown instructions
+pub const MNEMONICS: [&str; 33] = [
+    "load", "stor", "radd", "rsub", "rmul", "rdiv", "comp", "jump", "jusr", "juie", "juin", "rtor",
+    "rtin", "intr", "setv", "noop", "halt", "ctrl", "draw", "cmov", "scrl", "vmod", "sprt", "txtm",
+    "pixl", "line", "rect", "blit", "glyf", "font", "tile", "spal", "cpos",
+];
+
+/// Parses the tokens of a program into its routines, filesystems and data after expanding its
+/// macros. The sources are the files the tokens came from, used to get the raw text of ```str```
+/// and ```hex``` operands.
+/// A line with an error is skipped after reporting it, so all errors of a program are found at
+/// once. The program holds everything that could be parsed.
+pub fn parse(tokens: &[Token], sources: &[SourceFile], diagnostics: &mut Diagnostics) -> Program {
+    let tokens = macros::expand(tokens, diagnostics);
+    let mut parser = Parser { tokens: &tokens, sources, pos: 0, nesting: 0, diagnostics };
+    let mut program = Program::default();
+    loop {
+        parser.skip_newlines();
+        if parser.peek().kind == TokenKind::Eof {
+            return program;
+        }
+        if let Some(label) = parser.label(false) {
+            program.items.push(Item::Label(label));
+            if parser.at_line_end() {
+                continue;
+            }
+        }
+        match parser.item() {
+            Ok(item) => program.items.push(item),
+            Err(diagnostic) => {
+                parser.diagnostics.push(diagnostic);
+                parser.skip_line();
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    sources: &'a [SourceFile],
+    pos: usize,
+    /// Number of values being parsed inside each other
+    nesting: usize,
+    diagnostics: &'a mut Diagnostics,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if token.kind != TokenKind::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+
+    /// Returns the code a span covers
+    fn source_text(&self, span: Span) -> &str {
+        &self.sources[span.file].text[span.start..span.end]
+    }
+
+    fn peek_ident(&self) -> Option<&str> {
+        match &self.peek().kind {
+            TokenKind::Ident(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Consumes the next token if it is the given identifier
+    fn eat_ident(&mut self, name: &str) -> Option<Ident> {
+        if self.peek_ident() == Some(name) {
+            let token = self.next();
+            return Some(Ident { name: name.to_string(), span: token.span });
+        }
+        None
+    }
+
+    /// Skips everything up to and including the end of the line
+    fn skip_line(&mut self) {
+        while !self.at_line_end() {
+            self.next();
+        }
+        self.next();
+    }
+
+    /// Parses the rest of a line with the given function, reporting an error and skipping the
+    /// line if it fails
+    fn recover<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Option<T> {
+        match parse(self) {
+            Ok(value) => Some(value),
+            Err(diagnostic) => {
+                self.diagnostics.push(diagnostic);
+                self.skip_line();
+                None
+            }
+        }
+    }
+
+    /// Returns true if the next tokens start a routine, a filesystem or a data label
+    fn at_item_start(&self) -> bool {
+        self.peek_ident().is_some_and(|name| !name.starts_with('.'))
+            && self.tokens.get(self.pos + 1).is_some_and(|token| token.kind == TokenKind::Punct(':'))
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek().kind == TokenKind::Newline {
+            self.next();
+        }
+    }
+
+    fn at_line_end(&self) -> bool {
+        matches!(self.peek().kind, TokenKind::Newline | TokenKind::Eof)
+    }
+
+    fn describe(token: &Token) -> String {
+        match &token.kind {
+            TokenKind::Ident(name) | TokenKind::Number(name) => format!("`{}`", name),
+            TokenKind::Str { .. } => "a string".to_string(),
+            TokenKind::Punct(char) => format!("`{}`", char),
+            TokenKind::Newline => "the end of the line".to_string(),
+            TokenKind::Eof => "the end of the file".to_string(),
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> Diagnostic {
+        let token = self.peek();
+        Diagnostic::error(
+            diagnostics::E_SYNTAX,
+            format!("Expected {}, found {}", expected, Self::describe(token)),
+            token.span,
+        )
+    }
+
+    fn expect_line_end(&mut self) -> Result<()> {
+        if !self.at_line_end() {
+            return Err(self.unexpected("the end of the line"));
+        }
+        self.next();
+        Ok(())
+    }
+
+    fn expect_punct(&mut self, char: char) -> Result<Span> {
+        if self.peek().kind != TokenKind::Punct(char) {
+            return Err(self.unexpected(&format!("`{}`", char)));
+        }
+        Ok(self.next().span)
+    }
+
+    fn ident(&mut self, what: &str) -> Result<Ident> {
+        match self.peek().kind.clone() {
+            TokenKind::Ident(name) => Ok(Ident { name, span: self.next().span }),
+            _ => Err(self.unexpected(what)),
+        }
+    }
+
+    /// Parses one of the given words
+    fn keyword(&mut self, options: &[&str]) -> Result<Ident> {
+        match self.peek_ident() {
+            Some(name) if options.contains(&name) => self.ident(""),
+            name => {
+                let list: Vec<String> = options.iter().map(|option| format!("`{}`", option)).collect();
+                let mut diagnostic = self.unexpected(&format!("one of {}", list.join(", ")));
+                if let Some(suggestion) = name.and_then(|name| diagnostics::suggest(name, options)) {
+                    diagnostic = diagnostic.with_note(format!("did you mean `{}`?", suggestion));
+                }
+                Err(diagnostic)
+            }
+        }
+    }
+
+    /// Parses a word made of all tokens up to the next space, no matter what they are
+    fn raw_word(&mut self, what: &str) -> Result<Text> {
+        if self.at_line_end() {
+            return Err(self.unexpected(what));
+        }
+        let first = self.next().span;
+        let mut span = first;
+        while !self.at_line_end() && span.touches(self.peek().span) {
+            span = first.to(self.next().span);
+        }
+        Ok(Text { value: self.source_text(span).to_string(), span })
+    }
+
+    /// Parses a double-quoted string, replacing its escapes
+    fn string(&mut self) -> Result<Text> {
+        let token = self.next();
+        let TokenKind::Str { raw, terminated } = token.kind else {
+            self.pos -= 1;
+            return Err(self.unexpected("a string"));
+        };
+        if !terminated {
+            return Err(Diagnostic::error(
+                diagnostics::E_SYNTAX,
+                "String is missing its closing `\"`",
+                token.span,
+            ));
+        }
+
+        // Span of the characters from the byte index start to end inside the quotes
+        let span_of = |start: usize, end: usize| Span {
+            start: token.span.start + 1 + start,
+            end: token.span.start + 1 + end,
+            column: token.span.column + 1 + raw[..start].chars().count(),
+            ..token.span
+        };
+
+        let mut value = String::new();
+        let mut chars = raw.char_indices().peekable();
+        while let Some((start, char)) = chars.next() {
+            if char != '\\' {
+                value.push(char);
+                continue;
+            }
+            let escaped = match chars.next().map(|(_, escape)| escape) {
+                Some('n') => Some('\n'),
+                Some('t') => Some('\t'),
+                Some('"') => Some('"'),
+                Some('\\') => Some('\\'),
+                Some('x') => {
+                    let digits: String = (0..2)
+                        .filter_map(|_| chars.next_if(|(_, c)| c.is_ascii_hexdigit()))
+                        .map(|(_, c)| c)
+                        .collect();
+                    (digits.len() == 2)
+                        .then(|| u32::from_str_radix(&digits, 16).ok())
+                        .flatten()
+                        .and_then(char::from_u32)
+                }
+                Some('u') if chars.next_if(|(_, c)| *c == '{').is_some() => {
+                    let mut digits = String::new();
+                    while let Some((_, digit)) = chars.next_if(|(_, c)| c.is_ascii_hexdigit()) {
+                        digits.push(digit);
+                    }
+                    let closed = chars.next_if(|(_, c)| *c == '}').is_some();
+                    (closed && (1..=6).contains(&digits.len()))
+                        .then(|| u32::from_str_radix(&digits, 16).ok())
+                        .flatten()
+                        .and_then(char::from_u32)
+                }
+                _ => None,
+            };
+            let end = chars.peek().map_or(raw.len(), |(index, _)| *index);
+            match escaped {
+                Some(escaped) => value.push(escaped),
+                None => {
+                    return Err(Diagnostic::error(
+                        diagnostics::E_SYNTAX,
+                        format!("Invalid escape `{}`", &raw[start..end]),
+                        span_of(start, end),
+                    )
+                    .with_note("escapes are `\\n`, `\\t`, `\\\"`, `\\\\`, `\\x41` and `\\u{E9}`"));
+                }
+            }
+        }
+        Ok(Text { value, span: token.span })
+    }
+
+    /// Parses the text of ```draw str```, either a string or the raw text up to the next space
+    /// with every ```^``` standing for a space
+    fn text(&mut self) -> Result<Text> {
+        if matches!(self.peek().kind, TokenKind::Str { .. }) {
+            return self.string();
+        }
+        let text = self.raw_word("the text to draw")?;
+        Ok(Text { value: text.value.replace('^', " "), span: text.span })
+    }
+
+    // --- Items ---
+
+    fn item(&mut self) -> Result<Item> {
+        if let Some(keyword) = self.eat_ident("routine") {
+            let name = self
+                .recover(|parser| {
+                    parser.expect_punct(':')?;
+                    let name = parser.ident("a routine name")?;
+                    parser.expect_line_end()?;
+                    Ok(name)
+                })
+                .unwrap_or(Ident { name: String::new(), span: keyword.span });
+
+            let mut body = Vec::new();
+            loop {
+                self.skip_newlines();
+                if let Some(end) = self.eat_ident("end") {
+                    self.recover(Self::expect_line_end);
+                    return Ok(Item::Routine(Routine { name, body, span: keyword.span.to(end.span) }));
+                }
+                if self.peek().kind == TokenKind::Eof || self.at_item_start() {
+                    self.diagnostics.push(self.missing_end(&format!("Routine `{}`", name.name), keyword.span));
+                    let span = keyword.span.to(self.tokens[self.pos.saturating_sub(1)].span);
+                    return Ok(Item::Routine(Routine { name, body, span }));
+                }
+                if let Some(label) = self.label(true) {
+                    body.push(Statement::Label(label));
+                    if self.at_line_end() {
+                        continue;
+                    }
+                }
+                if let Some(instruction) = self.recover(Self::instruction) {
+                    body.push(Statement::Instruction(Box::new(instruction)));
+                }
+            }
+        }
+
+        if let Some(keyword) = self.eat_ident("filesys") {
+            self.recover(|parser| {
+                parser.expect_punct(':')?;
+                parser.expect_line_end()
+            });
+
+            let mut size = None;
+            loop {
+                self.skip_newlines();
+                if let Some(end) = self.eat_ident("end") {
+                    self.recover(Self::expect_line_end);
+                    return Ok(Item::FileSystem(FileSystem { size, span: keyword.span.to(end.span) }));
+                }
+                if self.peek().kind == TokenKind::Eof || self.at_item_start() {
+                    self.diagnostics.push(self.missing_end("Filesystem", keyword.span));
+                    return Ok(Item::FileSystem(FileSystem { size, span: keyword.span }));
+                }
+                let parsed = self.recover(|parser| {
+                    if parser.eat_ident("size").is_none() {
+                        return Err(parser.unexpected("`size` or `end`"));
+                    }
+                    parser.expect_punct('=')?;
+                    let size = parser.value()?;
+                    parser.expect_line_end()?;
+                    Ok(size)
+                });
+                if parsed.is_some() {
+                    size = parsed;
+                }
+            }
+        }
+
+        if let Some(keyword) = self.eat_ident(".equ") {
+            let name = self.ident("a constant name")?;
+            let value = self.value()?;
+            let span = keyword.span.to(value.span);
+            self.expect_line_end()?;
+            return Ok(Item::Constant(Constant { name, value, span }));
+        }
+
+        if let Some(keyword) = self.eat_ident(".org") {
+            let address = self.value()?;
+            let span = keyword.span.to(address.span);
+            self.expect_line_end()?;
+            return Ok(Item::Org(Org { address, span }));
+        }
+
+        if self.eat_ident(".section").is_some() {
+            let region = self.ident("a region name")?;
+            self.expect_line_end()?;
+            return Ok(Item::Section(region));
+        }
+
+        if let Some(directive) = self.peek_ident().filter(|name| name.starts_with('.')) {
+            let directive = directive.to_string();
+            return self.data(&directive).map(Item::Data);
+        }
+
+        Err(self.unexpected("`routine:`, `filesys:`, a label or a data directive"))
+    }
+
+    fn data(&mut self, directive: &str) -> Result<Data> {
+        let start = self.peek().span;
+        let kind = match directive {
+            ".word" => {
+                self.next();
+                DataKind::Words(vec![self.value()?])
+            }
+            ".words" => {
+                self.next();
+                let mut values = vec![self.value()?];
+                while !self.at_line_end() {
+                    values.push(self.value()?);
+                }
+                DataKind::Words(values)
+            }
+            ".string" => {
+                self.next();
+                let text = self.string()?;
+                let length_prefixed = match self.at_line_end() {
+                    true => false,
+                    false => self.keyword(&["zero", "len"])?.name == "len",
+                };
+                DataKind::String { text, length_prefixed }
+            }
+            ".fill" => {
+                self.next();
+                DataKind::Fill { count: self.value()?, value: self.value()? }
+            }
+            ".align" => {
+                self.next();
+                DataKind::Align(self.value()?)
+            }
+            _ => {
+                let mut diagnostic = self.unexpected("a data directive");
+                let options = [".word", ".words", ".string", ".fill", ".align", ".equ", ".org", ".section"];
+                if let Some(suggestion) = diagnostics::suggest(directive, &options) {
+                    diagnostic = diagnostic.with_note(format!("did you mean `{}`?", suggestion));
+                }
+                return Err(diagnostic);
+            }
+        };
+        let directive = Ident { name: directive.to_string(), span: start };
+        let span = start.to(self.tokens[self.pos - 1].span);
+        self.expect_line_end()?;
+        Ok(Data { directive, kind, span })
+    }
+
+    /// Parses a label if there is one. Local labels start with a dot and are inside routines,
+    /// data labels are at the top level.
+    fn label(&mut self, local: bool) -> Option<Ident> {
+        let is_label = self
+            .peek_ident()
+            .is_some_and(|name| name.starts_with('.') == local && !matches!(name, "routine" | "filesys"))
+            && self.tokens.get(self.pos + 1).is_some_and(|token| token.kind == TokenKind::Punct(':'));
+        if !is_label {
+            return None;
+        }
+        let label = self.ident("").ok()?;
+        self.next();
+        Some(label)
+    }
+
+    fn missing_end(&self, what: &str, start: Span) -> Diagnostic {
+        let found = self.peek();
+        Diagnostic::error(
+            diagnostics::E_MISSING_END,
+            format!("{} is missing its `end`", what),
+            found.span,
+        )
+        .with_note_at("it starts here", start)
+    }
+
+    // --- Operands ---
+
+    /// Parses a value, which can be an expression. Only operators continue a value, so values
+    /// following each other are told apart by the spaces between them. A ```-``` with a space
+    /// before it and none after it starts a negative value instead of subtracting.
+    fn value(&mut self) -> Result<Value> {
+        self.binary(1)
+    }
+
+    /// Parses operators binding at least as tight as the precedence, and everything they bind
+    fn binary(&mut self, min_precedence: u8) -> Result<Value> {
+        let nesting = self.nesting;
+        let value = self.operators(min_precedence);
+        self.nesting = nesting;
+        value
+    }
+
+    fn operators(&mut self, min_precedence: u8) -> Result<Value> {
+        let mut left = self.unary()?;
+        while let Some((op, precedence, length)) =
+            self.operator().filter(|(_, precedence, _)| *precedence >= min_precedence)
+        {
+            self.pos += length;
+            self.nest()?;
+            let right = self.binary(precedence + 1)?;
+            let span = left.span.to(right.span);
+            left = Value { kind: ValueKind::Binary(op, Box::new(left), Box::new(right)), span };
+        }
+        Ok(left)
+    }
+
+    /// Returns the binary operator at the current position with its precedence and the number
+    /// of tokens it takes up
+    fn operator(&self) -> Option<(BinaryOp, u8, usize)> {
+        let TokenKind::Punct(char) = self.peek().kind else {
+            return None;
+        };
+        let next = self
+            .tokens
+            .get(self.pos + 1)
+            .filter(|next| self.peek().span.touches(next.span))
+            .map(|next| &next.kind);
+        let previous = self.pos.checked_sub(1).map(|pos| self.tokens[pos].span);
+        let spaced = previous.is_some_and(|previous| !previous.touches(self.peek().span));
+        let (op, precedence) = match (char, next) {
+            ('-', Some(_)) if spaced => return None,
+            ('<', Some(TokenKind::Punct('<'))) => return Some((BinaryOp::Shl, 4, 2)),
+            ('>', Some(TokenKind::Punct('>'))) => return Some((BinaryOp::Shr, 4, 2)),
+            ('|', _) => (BinaryOp::Or, 1),
+            ('^', _) => (BinaryOp::Xor, 2),
+            ('&', _) => (BinaryOp::And, 3),
+            ('+', _) => (BinaryOp::Add, 5),
+            ('-', _) => (BinaryOp::Sub, 5),
+            ('*', _) => (BinaryOp::Mul, 6),
+            ('/', _) => (BinaryOp::Div, 6),
+            ('%', _) => (BinaryOp::Rem, 6),
+            _ => return None,
+        };
+        Some((op, precedence, 1))
+    }
+
+    fn unary(&mut self) -> Result<Value> {
+        let nesting = self.nesting;
+        self.nest()?;
+        let value = self.nested_unary();
+        self.nesting = nesting;
+        value
+    }
+
+    /// Goes one level deeper into a value, reporting values nested too deeply
+    fn nest(&mut self) -> Result<()> {
+        if self.nesting == MAX_NESTING {
+            return Err(Diagnostic::error(
+                diagnostics::E_SYNTAX,
+                format!("Values can't nest more than {} operators and parentheses", MAX_NESTING),
+                self.peek().span,
+            )
+            .with_note("split the value up with `.equ` constants"));
+        }
+        self.nesting += 1;
+        Ok(())
+    }
+
+    fn nested_unary(&mut self) -> Result<Value> {
+        let start = self.peek().span;
+        let is_call = self.tokens.get(self.pos + 1).is_some_and(|next| next.kind == TokenKind::Punct('('));
+        let op = match (&self.peek().kind, is_call) {
+            (TokenKind::Punct('-'), _) => UnaryOp::Negate,
+            (TokenKind::Punct('~'), _) => UnaryOp::Not,
+            (TokenKind::Ident(name), true) if name == "hi" => UnaryOp::High,
+            (TokenKind::Ident(name), true) if name == "lo" => UnaryOp::Low,
+            _ => return self.primary(),
+        };
+        self.next();
+        // hi() and lo() take the value in their parentheses
+        let operand = match op {
+            UnaryOp::High | UnaryOp::Low => self.primary()?,
+            _ => self.unary()?,
+        };
+        let span = start.to(operand.span);
+        Ok(Value { kind: ValueKind::Unary(op, Box::new(operand)), span })
+    }
+
+    fn primary(&mut self) -> Result<Value> {
+        let token = self.peek().clone();
+        let kind = match &token.kind {
+            TokenKind::Punct('(') => {
+                self.next();
+                let inner = self.value()?;
+                let end = self.expect_punct(')')?;
+                return Ok(Value { kind: inner.kind, span: token.span.to(end) });
+            }
+            TokenKind::Number(text) => ValueKind::Num(text.clone()),
+            TokenKind::Str { .. } => return self.character(token.span),
+            TokenKind::Ident(annotation) if matches!(annotation.as_str(), "lit" | "num" | "hex") => {
+                self.next();
+                let word = self.peek().clone();
+                let text = match &word.kind {
+                    TokenKind::Number(text) => text.clone(),
+                    TokenKind::Ident(text) if annotation != "num" => text.clone(),
+                    // hex takes the first character of anything
+                    TokenKind::Punct(_) if annotation == "hex" => self.source_text(word.span).to_string(),
+                    TokenKind::Str { .. } if annotation == "hex" => return self.character(token.span),
+                    // Anything else is an expression, like `num WIDTH` or `lit (BASE + 2)`
+                    TokenKind::Ident(_) | TokenKind::Punct('(' | '-' | '~') => {
+                        let value = self.unary()?;
+                        return Ok(Value { span: token.span.to(value.span), ..value });
+                    }
+                    _ => return Err(self.unexpected(&format!("the value after `{}`", annotation))),
+                };
+                let kind = match annotation.as_str() {
+                    "lit" => ValueKind::Lit(text),
+                    "num" => ValueKind::Num(text),
+                    _ => ValueKind::Hex(text),
+                };
+                self.next();
+                return Ok(Value { kind, span: token.span.to(word.span) });
+            }
+            TokenKind::Ident(name) => ValueKind::Symbol(name.clone()),
+            _ => return Err(self.unexpected("a value")),
+        };
+        self.next();
+        Ok(Value { kind, span: token.span })
+    }
+
+    /// Parses a string of one character standing for its character code, like ```"A"``` or
+    /// ```hex "\n"```. Constants hold a single word, so longer strings are only allowed in
+    /// ```.string``` and ```draw str```.
+    fn character(&mut self, start: Span) -> Result<Value> {
+        let text = self.string()?;
+        let span = start.to(text.span);
+        let mut chars = text.value.chars();
+        match (chars.next(), chars.next()) {
+            (Some(char), None) => Ok(Value { kind: ValueKind::Hex(char.to_string()), span }),
+            _ => Err(Diagnostic::error(
+                diagnostics::E_SYNTAX,
+                format!(
+                    "Expected a string of one character, found {} characters",
+                    text.value.chars().count()
+                ),
+                text.span,
+            )
+            .with_note("use `.string` with a label to place longer text in the memory")),
+        }
+    }
+
+    fn values(&mut self, count: usize) -> Result<Vec<Value>> {
+        (0..count).map(|_| self.value()).collect()
+    }
+
+    fn register(&mut self) -> Result<Register> {
+        let register = match self.peek_ident() {
+            Some("A") => Register::A,
+            Some("X") => Register::X,
+            Some("Y") => Register::Y,
+            name => {
+                let mut diagnostic = self.unexpected("a register (`A`, `X` or `Y`)");
+                diagnostic.code = diagnostics::E_UNKNOWN_REGISTER;
+                if let Some(name) = name.filter(|name| ["a", "x", "y"].contains(name)) {
+                    diagnostic = diagnostic.with_note(format!("did you mean register {}?", name.to_uppercase()));
+                }
+                return Err(diagnostic);
+            }
+        };
+        self.next();
+        Ok(register)
+    }
+
+    fn color(&mut self) -> Result<Ident> {
+        let color = self.ident("a color")?;
+        if color_index(&color.name).is_none() {
+            let mut diagnostic = Diagnostic::error(
+                diagnostics::E_UNKNOWN_COLOR,
+                format!("Unknown color `{}`", color.name),
+                color.span,
+            );
+            if let Some(suggestion) = diagnostics::suggest(&color.name, &COLORS) {
+                diagnostic = diagnostic.with_note(format!("did you mean `{}`?", suggestion));
+            }
+            return Err(diagnostic);
+        }
+        Ok(color)
+    }
+
+    /// Parses one style argument into the style. Returns false if the next token isn't one.
+    fn style_argument(&mut self, style: &mut Style) -> Result<bool> {
+        match self.peek_ident() {
+            Some("col") => {
+                self.next();
+                style.color = Some(self.color()?);
+            }
+            Some("bg") => {
+                self.next();
+                style.background = Some(self.color()?);
+            }
+            Some(attribute @ ("bold" | "inv" | "blink")) => {
+                style.attributes |= match attribute {
+                    "bold" => 0b0001,
+                    "inv" => 0b0010,
+                    _ => 0b0100,
+                };
+                self.next();
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Parses style arguments up to the end of the line
+    fn style(&mut self, colors_only: bool) -> Result<Style> {
+        let mut style = Style::default();
+        while !self.at_line_end() {
+            if !self.style_argument(&mut style)? {
+                return Err(self.unexpected("a style argument (`col`, `bg`, `bold`, `inv` or `blink`)"));
+            }
+            if colors_only && style.attributes != 0 {
+                return Err(Diagnostic::error(
+                    diagnostics::E_SYNTAX,
+                    "Only `col` is allowed here",
+                    self.tokens[self.pos - 1].span,
+                ));
+            }
+        }
+        Ok(style)
+    }
+
+    // --- Instructions ---
+
+    fn instruction(&mut self) -> Result<Instruction> {
+        let mnemonic = self.ident("an instruction")?;
+        let kind = match mnemonic.name.as_str() {
+            "load" => {
+                let register = self.register()?;
+                let memory = self.eat_ident("mem").is_some();
+                InstructionKind::Load { register, memory, value: self.value()? }
+            }
+            "stor" => InstructionKind::Stor { register: self.register()?, address: self.value()? },
+            "radd" | "rsub" | "rmul" | "rdiv" => {
+                InstructionKind::Arithmetic { register: self.register()?, value: self.value()? }
+            }
+            "comp" => InstructionKind::Comp { left: self.comp_operand()?, right: self.comp_operand()? },
+            "jump" | "jusr" | "juie" | "juin" => InstructionKind::Jump { target: self.value()? },
+            "rtor" => InstructionKind::Return,
+            "rtin" => InstructionKind::ReturnFromInterrupt,
+            "setv" => InstructionKind::SetValue { address: self.value()?, value: self.value()? },
+            "noop" => InstructionKind::Noop,
+            "halt" => InstructionKind::Halt,
+            "intr" => {
+                let handler = match self.eat_ident("off") {
+                    Some(_) => None,
+                    None => Some(self.value()?),
+                };
+                InstructionKind::Interrupt { handler }
+            }
+            "ctrl" => {
+                let device = self.keyword(&["gpu", "cpu"])?;
+                let action = if device.name == "gpu" {
+                    self.keyword(&["clear", "reset", "update"])?
+                } else {
+                    self.keyword(&["reset", "halt"])?
+                };
+                InstructionKind::Control { device, action }
+            }
+            "draw" => {
+                let kind = self.keyword(&["str", "val"])?;
+                if kind.name == "str" {
+                    let text = self.text()?;
+                    InstructionKind::DrawStr { text, style: self.style(false)? }
+                } else {
+                    let value = self.value()?;
+                    let mut base = 10;
+                    let mut style = Style::default();
+                    while !self.at_line_end() {
+                        if let Some(name) = self.peek_ident().filter(|name| ["bin", "dec", "hex"].contains(name)) {
+                            base = match name {
+                                "bin" => 2,
+                                "hex" => 16,
+                                _ => 10,
+                            };
+                            self.next();
+                        } else if !self.style_argument(&mut style)? {
+                            return Err(self.unexpected("a base (`bin`, `dec` or `hex`) or a style argument"));
+                        }
+                    }
+                    InstructionKind::DrawVal { value, base, style }
+                }
+            }
+            "cmov" => InstructionKind::CursorMove { direction: self.keyword(&["up", "do", "le", "ri", "nl"])? },
+            "scrl" => {
+                let direction = self.keyword(&["up", "do", "reg"])?;
+                let values = self.values(if direction.name == "reg" { 2 } else { 1 })?;
+                InstructionKind::Scroll { direction, values }
+            }
+            "vmod" => InstructionKind::VideoMode { mode: self.keyword(&["text", "bmap", "tile"])? },
+            "sprt" => {
+                let values = self.values(4)?;
+                let mut flags = SpriteFlags::default();
+                while !self.at_line_end() {
+                    match self.keyword(&["fliph", "flipv", "hide", "pal"])?.name.as_str() {
+                        "fliph" => flags.flip_h = true,
+                        "flipv" => flags.flip_v = true,
+                        "hide" => flags.hidden = true,
+                        _ => flags.palette = Some(self.value()?),
+                    }
+                }
+                InstructionKind::Sprite { values, flags }
+            }
+            name => match GPU_COMMANDS.iter().find(|(command, _, _)| *command == name) {
+                Some((_, count, takes_color)) => {
+                    let values = self.values(*count)?;
+                    let style = if *takes_color { self.style(true)? } else { Style::default() };
+                    InstructionKind::Gpu { values, style }
+                }
+                None => {
+                    let mut diagnostic = Diagnostic::error(
+                        diagnostics::E_UNKNOWN_INSTRUCTION,
+                        format!("Unknown instruction `{}`", mnemonic.name),
+                        mnemonic.span,
+                    );
+                    if let Some(suggestion) = diagnostics::suggest(name, &MNEMONICS) {
+                        diagnostic = diagnostic.with_note(format!("did you mean `{}`?", suggestion));
+                    }
+                    return Err(diagnostic);
+                }
+            },
+        };
+
+        let span = mnemonic.span.to(self.tokens[self.pos.saturating_sub(1)].span);
+        self.expect_line_end()?;
+        Ok(Instruction { mnemonic, kind, span })
+    }
+
+    fn comp_operand(&mut self) -> Result<CompOperand> {
+        if self.eat_ident("reg").is_some() {
+            Ok(CompOperand::Register(self.register()?))
+        } else {
+            Ok(CompOperand::Value(self.value()?))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+
+    fn parse_text(text: &str) -> (Program, Diagnostics) {
+        let sources = [SourceFile { path: "main.rvmasm".to_string(), text: text.to_string() }];
+        let mut diagnostics = Diagnostics::default();
+        let program = parse(&tokenize(text, 0), &sources, &mut diagnostics);
+        (program, diagnostics)
+    }
+
+    fn codes(diagnostics: &Diagnostics) -> Vec<&str> {
+        diagnostics.list.iter().map(|diagnostic| diagnostic.code).collect()
+    }
+
+    #[test]
+    fn routines_hold_their_instructions() {
+        let (program, diagnostics) = parse_text("routine: entry\n\nload A mem lit 0x0300\nrtor\nend\n");
+        assert!(diagnostics.list.is_empty());
+        let [Item::Routine(routine)] = &program.items[..] else { panic!("{:?}", program.items) };
+        assert_eq!(routine.name.name, "entry");
+        assert_eq!((routine.span.start, routine.span.end), (0, 46));
+        let [Statement::Instruction(load), Statement::Instruction(ret)] = &routine.body[..] else {
+            panic!("{:?}", routine.body)
+        };
+        assert!(matches!(
+            &load.kind,
+            InstructionKind::Load { register: Register::A, memory: true, value }
+                if value.kind == ValueKind::Lit("0x0300".to_string())
+        ));
+        assert_eq!(ret.kind, InstructionKind::Return);
+    }
+
+    #[test]
+    fn every_bad_line_is_reported() {
+        let (program, diagnostics) = parse_text("routine: entry\nload a num 1\nlaod A num 1\nrtor\nend\n");
+        assert_eq!(
+            codes(&diagnostics),
+            [diagnostics::E_UNKNOWN_REGISTER, diagnostics::E_UNKNOWN_INSTRUCTION]
+        );
+        let [register, mnemonic] = &diagnostics.list[..] else { unreachable!() };
+        assert_eq!((register.span.line, register.span.column), (2, 6));
+        assert_eq!(register.notes[0].message, "did you mean register A?");
+        assert_eq!(mnemonic.notes[0].message, "did you mean `load`?");
+
+        let (_, diagnostics) = parse_text("routine: entry\nload B num 1\nstor y lit 0x2000\nend\n");
+        assert_eq!(codes(&diagnostics), [diagnostics::E_UNKNOWN_REGISTER; 2]);
+        assert_eq!(diagnostics.list[0].message, "Expected a register (`A`, `X` or `Y`), found `B`");
+        assert!(diagnostics.list[0].notes.is_empty());
+        assert_eq!(diagnostics.list[1].notes[0].message, "did you mean register Y?");
+
+        let [Item::Routine(routine)] = &program.items[..] else { panic!("{:?}", program.items) };
+        let [Statement::Instruction(ret)] = &routine.body[..] else { panic!("{:?}", routine.body) };
+        assert_eq!(ret.kind, InstructionKind::Return);
+    }
+
+    #[test]
+    fn a_missing_end_stops_at_the_next_routine() {
+        let (program, diagnostics) = parse_text("routine: first\nrtor\nroutine: second\nrtor\nend\n");
+        assert_eq!(codes(&diagnostics), [diagnostics::E_MISSING_END]);
+        let diagnostic = &diagnostics.list[0];
+        assert_eq!(diagnostic.message, "Routine `first` is missing its `end`");
+        assert_eq!(diagnostic.span.line, 3);
+        assert_eq!(diagnostic.notes[0].span.map(|span| span.line), Some(1));
+        assert_eq!(program.items.len(), 2);
+    }
+
+    #[test]
+    fn labels_can_share_a_line_with_an_instruction() {
+        let (program, diagnostics) = parse_text("routine: entry\n.start:\n.loop: jump .loop\nend\n");
+        assert!(diagnostics.list.is_empty());
+        let [Item::Routine(routine)] = &program.items[..] else { panic!("{:?}", program.items) };
+        let [Statement::Label(start), Statement::Label(the_loop), Statement::Instruction(jump)] =
+            &routine.body[..]
+        else {
+            panic!("{:?}", routine.body)
+        };
+        assert_eq!((start.name.as_str(), the_loop.name.as_str()), (".start", ".loop"));
+        assert!(matches!(&jump.kind, InstructionKind::Jump { target }
+                if target.kind == ValueKind::Symbol(".loop".to_string())));
+    }
+
+    #[test]
+    fn jumps_take_values_as_addresses() {
+        let (program, diagnostics) = parse_text("routine: entry\njump lit 0x56FA\njusr hex\nend\n");
+        let [Item::Routine(routine)] = &program.items[..] else { panic!("{:?}", program.items) };
+        let [Statement::Instruction(jump)] = &routine.body[..] else { panic!("{:?}", routine.body) };
+        assert!(matches!(&jump.kind, InstructionKind::Jump { target }
+                if target.kind == ValueKind::Lit("0x56FA".to_string())));
+        assert_eq!(diagnostics.list[0].message, "Expected the value after `hex`, found the end of the line");
+    }
+
+    /// Parses a ```draw str``` with the given string and returns its text or its error
+    fn drawn(string: &str) -> std::result::Result<String, Diagnostic> {
+        let (program, mut diagnostics) = parse_text(&format!("routine: entry\ndraw str {}\nend\n", string));
+        if let Some(diagnostic) = diagnostics.list.pop() {
+            return Err(diagnostic);
+        }
+        let [Item::Routine(routine)] = &program.items[..] else { panic!("{:?}", program.items) };
+        match &routine.body[..] {
+            [Statement::Instruction(instruction)] => match &instruction.kind {
+                InstructionKind::DrawStr { text, .. } => Ok(text.value.clone()),
+                kind => panic!("{:?}", kind),
+            },
+            body => panic!("{:?}", body),
+        }
+    }
+
+    #[test]
+    fn string_escapes_are_replaced() {
+        assert_eq!(drawn(r#""\n\t\"\\\x41\u{E9}""#).unwrap(), "\n\t\"\\A\u{E9}");
+        assert_eq!(drawn(r#""a b^""#).unwrap(), "a b^");
+        assert_eq!(drawn("a^b").unwrap(), "a b");
+    }
+
+    #[test]
+    fn invalid_escapes_are_reported() {
+        for escape in [r"\q", r"\x4", r"\u{}", r"\u{E9", r"\u{D800}"] {
+            let diagnostic = drawn(&format!("\"a{}\"", escape)).unwrap_err();
+            assert_eq!(diagnostic.code, diagnostics::E_SYNTAX);
+            assert_eq!((diagnostic.span.line, diagnostic.span.column), (2, 12), "{}", escape);
+        }
+    }
+
+    #[test]
+    fn unterminated_strings_are_reported() {
+        let diagnostic = drawn("\"open").unwrap_err();
+        assert_eq!(diagnostic.message, "String is missing its closing `\"`");
+    }
+
+    #[test]
+    fn unknown_directives_are_suggested() {
+        let (program, diagnostics) = parse_text("label:\n.strng \"a\"\n.string \"a\" none\n");
+        assert!(matches!(&program.items[..], [Item::Label(label)] if label.name == "label"));
+        let [directive, ending] = &diagnostics.list[..] else { panic!("{:?}", diagnostics.list) };
+        assert_eq!(directive.notes[0].message, "did you mean `.string`?");
+        assert_eq!(ending.span.line, 3);
+    }
+}
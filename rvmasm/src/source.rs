@@ -0,0 +1,51 @@
+/// A source file handed to the assembler
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub path: String,
+    pub text: String,
+}
+
+impl SourceFile {
+    pub fn read(path: &str) -> std::io::Result<Self> {
+        Ok(Self {
+            path: path.to_string(),
+            text: std::fs::read_to_string(path)?,
+        })
+    }
+}
+
+/// Location of a piece of source code. Start and end are byte offsets into the file, line and
+/// column (both starting at 1) belong to the start. Code copied from a macro points at the
+/// macro's definition and holds the index of the expansion it was copied to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub file: usize,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+    pub expansion: Option<u32>,
+}
+
+impl Span {
+    /// Returns a span reaching from the start of this span to the end of the other one. If the
+    /// other one is somewhere else, like in the arguments of a macro call, this span is returned.
+    pub fn to(self, other: Span) -> Span {
+        if other.file != self.file || other.expansion != self.expansion || other.end < self.start {
+            return self;
+        }
+        Span { end: other.end, ..self }
+    }
+
+    /// Returns true if the other span starts right where this one ends, without a space
+    pub fn touches(self, other: Span) -> bool {
+        other.file == self.file && other.expansion == self.expansion && other.start == self.end
+    }
+}
+
+/// A macro call the body of the macro was copied to
+#[derive(Debug, Clone)]
+pub struct Expansion {
+    pub name: String,
+    pub call: Span,
+}
@@ -1,5 +1,7 @@
 use crate::opcodes::*;
 use std::default::Default;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
 
 #[derive(Debug)]
 pub struct CPU {
@@ -16,14 +18,21 @@ pub struct CPU {
 
     pub halt_flag: bool,
     pub eq_flag: bool,
+    pub int_flag: bool,
+
+    pub int_vector: u16,
 
     pub clock_speed: usize, // in Hz
 
     pub memory: crate::memory::Memory,
+    pub gpu_status: Arc<gpu::status::Status>,
+
+    /// Where the code in the ROM was assembled from, if rvmasm wrote it
+    pub debug_info: Option<crate::debug::DebugInfo>,
 }
 
 impl CPU {
-    pub fn init() -> Self {
+    pub fn init(gpu_status: Arc<gpu::status::Status>) -> Self {
         Self {
             name: String::from("OwO CPU"),
 
@@ -38,10 +47,28 @@ impl CPU {
 
             halt_flag: false,
             eq_flag: false,
+            int_flag: false,
+
+            int_vector: 0x0000,
 
             clock_speed: 10_000_000, // in Hz
 
             memory: crate::memory::Memory::init(),
+            gpu_status,
+
+            debug_info: crate::debug::DebugInfo::load(
+                &format!("{}/ROM.dbg", env!("CARGO_MANIFEST_DIR"))
+            ),
+        }
+    }
+
+    /// Describes an address with the code it was assembled from, like
+    /// ```0x1004 in routine `divide` (.loop) at arithmetics.rvmasm:26:5```
+    pub fn describe(&self, address: u16) -> String {
+        let location = self.debug_info.as_ref().and_then(|info| info.locate(address));
+        match location {
+            Some(location) => format!("{:#06X} in {}", address, location),
+            None => format!("{:#06X}", address),
         }
     }
 
@@ -79,14 +106,42 @@ impl CPU {
         instruction
     }
 
+    /// Reads the value at the given address. The GPU status is read from the GPU itself.
     pub fn read_at(&mut self, address: u16) -> u16 {
-        let instruction = self
-            .memory
-            .rom[address as usize];
-        instruction
+        match self.gpu_status.read(address) {
+            Some(value) => value,
+            None => self.memory.rom[address as usize],
+        }
+    }
+
+    /// Writes a value to the given address. Writes to the GPU status go to the GPU itself.
+    pub fn write_at(&mut self, address: u16, value: u16) {
+        if !self.gpu_status.write(address, value) {
+            self.memory.rom[address as usize] = value;
+            self.memory.update(address);
+        }
+    }
+
+    /// Jumps to the interrupt handler if the GPU finished a frame. The return address is pushed
+    /// to the stack, just like when jumping to a subroutine.
+    pub fn handle_interrupts(&mut self) {
+        if self.int_vector == 0x0000 || self.int_flag {
+            return;
+        }
+        if self.gpu_status.vblank.swap(false, Ordering::Acquire) {
+            self.memory.ram[self.stack_ptr as usize] = self.instr_ptr;
+            self.increase_stack_ptr();
+            self.instr_ptr = self.int_vector;
+            self.int_flag = true;
+            #[cfg(debug_assertions)]
+            crate::debug!("VBlank interrupt: Jumping to: ", crate::hex!(self.int_vector));
+        }
     }
 
     pub fn update(&mut self) {
+        self.handle_interrupts();
+        // The address of the instruction, since the instruction pointer wraps around while reading
+        let address = self.instr_ptr;
         let instruction = self.read_word();
         #[cfg(debug_assertions)]
         crate::debug!("Read value: ", crate::hex!(instruction));
@@ -118,34 +173,72 @@ impl CPU {
                 crate::debug!("Loaded value into G Register: ", crate::hex!(self.g_reg));
             }
 
+            // --- Load the value at the following address into one of the registers ---
+            LOAD_AMEM => {
+                let addr = self.read_word();
+                self.a_reg = self.read_at(addr);
+                #[cfg(debug_assertions)]
+                crate::debug!("Loaded value into A Register: ", crate::hex!(self.a_reg));
+            }
+            LOAD_XMEM => {
+                let addr = self.read_word();
+                self.x_reg = self.read_at(addr);
+                #[cfg(debug_assertions)]
+                crate::debug!("Loaded value into X Register: ", crate::hex!(self.x_reg));
+            }
+            LOAD_YMEM => {
+                let addr = self.read_word();
+                self.y_reg = self.read_at(addr);
+                #[cfg(debug_assertions)]
+                crate::debug!("Loaded value into Y Register: ", crate::hex!(self.y_reg));
+            }
+
             // --- Store a register's value to the following address. This copies the value and doesn't move it ---
             STOR_AREG => {
                 let addr = self.read_word();
-                self.memory.rom[addr as usize] = self.a_reg;
+                self.write_at(addr, self.a_reg);
                 #[cfg(debug_assertions)]
                 crate::debug!("Storing A Register to : ", crate::hex!(addr));
-                self.memory.update(addr);
             }
             STOR_XREG => {
                 let addr = self.read_word();
-                self.memory.rom[addr as usize] = self.x_reg;
+                self.write_at(addr, self.x_reg);
                 #[cfg(debug_assertions)]
                 crate::debug!("Storing X Register to : ", crate::hex!(addr));
-                self.memory.update(addr);
             }
             STOR_YREG => {
                 let addr = self.read_word();
-                self.memory.rom[addr as usize] = self.y_reg;
+                self.write_at(addr, self.y_reg);
                 #[cfg(debug_assertions)]
                 crate::debug!("Storing Y Register to : ", crate::hex!(addr));
-                self.memory.update(addr);
             }
             STOR_GREG => {
+                if self.gpu_status.ring_full() {
+                    // --- Hand everything over to the GPU and try again once it made room ---
+                    self.gpu_status.ring_doorbell();
+                    self.instr_ptr = address;
+                    #[cfg(debug_assertions)]
+                    crate::debug!("GPU command ring full, waiting");
+                } else {
+                    let head = self.gpu_status.ring_head.load(Ordering::Acquire);
+                    self.memory.rom[head as usize] = self.g_reg;
+                    self.memory.update(head);
+                    self.gpu_status.ring_head.store(gpu::status::next_in_ring(head), Ordering::Release);
+                    #[cfg(debug_assertions)]
+                    crate::debug!("Pushing G Register to GPU command ring at: ", crate::hex!(head));
+                }
+            }
+            STOR_IMMV => {
                 let addr = self.read_word();
-                self.memory.rom[addr as usize] = self.g_reg;
+                let value = self.read_word();
+                self.write_at(addr, value);
+                #[cfg(debug_assertions)]
+                crate::debug!("Storing value to : ", crate::hex!(addr));
+            }
+            RING_DBELL => {
+                self.gpu_status.ring_doorbell();
                 #[cfg(debug_assertions)]
-                crate::debug!("Storing G Register to : ", crate::hex!(addr));
-                self.memory.update(addr);
+                crate::debug!("Ringing GPU doorbell");
             }
 
             // --- Subroutine Things ---
@@ -169,6 +262,19 @@ impl CPU {
                 crate::debug!("Returning to:", crate::hex!(self.instr_ptr));
                 self.increase_instr_ptr();
             }
+            // --- Interrupts ---
+            SET_INT_V => {
+                self.int_vector = self.read_word();
+                #[cfg(debug_assertions)]
+                crate::debug!("Setting interrupt handler: ", crate::hex!(self.int_vector));
+            }
+            RET_FR_INT => {
+                self.decrease_stack_ptr();
+                self.instr_ptr = self.memory.ram[self.stack_ptr as usize];
+                self.int_flag = false;
+                #[cfg(debug_assertions)]
+                crate::debug!("Returning from interrupt to:", crate::hex!(self.instr_ptr));
+            }
             COMP_REGS => {
                 let text_1;
                 let text_2;
@@ -207,11 +313,7 @@ impl CPU {
                     _ => text_2 = format!("Value {:#06X}", val_2)
                 };
 
-                if val_1 == val_2 {
-                    self.eq_flag = true;
-                } else {
-                    self.eq_flag = false;
-                }
+                self.eq_flag = val_1 == val_2;
 
                 #[cfg(debug_assertions)]
                 crate::debug!(
@@ -355,11 +457,20 @@ impl CPU {
             }
             HALT_LOOP => {
                 self.halt_flag = true;
-                std::process::exit(0);
                 #[cfg(debug_assertions)]
                 crate::debug!("HALT: ", self.halt_flag);
+                std::process::exit(0);
+            }
+            _ => {
+                use colored::Colorize;
+                println!(
+                    "{}: Unknown instruction {:#06X} at {}",
+                    "[FAULT]".red(),
+                    instruction,
+                    self.describe(address)
+                );
+                self.halt_flag = true;
             }
-            _ => {}
         }
 
         /*
@@ -369,3 +480,95 @@ impl CPU {
         */
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A CPU running the words given at 0x1000, without reading the ROM from the disk
+    fn cpu(code: &[u16]) -> CPU {
+        let mut rom = vec![NO_OPERAT; 0x10000];
+        rom[0x1000..0x1000 + code.len()].copy_from_slice(code);
+        CPU {
+            name: String::from("Test CPU"),
+            instr_ptr: 0x1000,
+            stack_ptr: 0x0000,
+            a_reg: 0,
+            x_reg: 0,
+            y_reg: 0,
+            g_reg: 0,
+            halt_flag: false,
+            eq_flag: false,
+            int_flag: false,
+            int_vector: 0x0000,
+            clock_speed: 10_000_000,
+            memory: crate::memory::Memory { rom, ram: [NO_OPERAT; 1024] },
+            gpu_status: Arc::new(gpu::status::Status::default()),
+            debug_info: None,
+        }
+    }
+
+    #[test]
+    fn vblank_interrupts_the_cpu_once() {
+        let mut cpu = cpu(&[SET_INT_V, 0x2000, NO_OPERAT, NO_OPERAT]);
+        cpu.memory.rom[0x2000] = NO_OPERAT;
+        cpu.memory.rom[0x2001] = RET_FR_INT;
+        cpu.update();
+        assert_eq!(cpu.int_vector, 0x2000);
+
+        cpu.gpu_status.vblank.store(true, Ordering::Release);
+        cpu.update();
+        assert_eq!((cpu.instr_ptr, cpu.int_flag, cpu.stack_ptr), (0x2001, true, 1));
+
+        // Another frame doesn't interrupt the handler
+        cpu.gpu_status.vblank.store(true, Ordering::Release);
+        cpu.update();
+        assert_eq!((cpu.instr_ptr, cpu.int_flag, cpu.stack_ptr), (0x1002, false, 0));
+        cpu.update();
+        assert_eq!((cpu.instr_ptr, cpu.int_flag), (0x2001, true));
+    }
+
+    #[test]
+    fn there_is_no_interrupt_without_a_handler() {
+        let mut cpu = cpu(&[NO_OPERAT, NO_OPERAT]);
+        cpu.gpu_status.vblank.store(true, Ordering::Release);
+        cpu.update();
+        assert_eq!((cpu.instr_ptr, cpu.int_flag), (0x1001, false));
+    }
+
+    #[test]
+    fn the_gpu_status_is_read_from_the_gpu() {
+        let mut cpu = cpu(&[LOAD_AMEM, gpu::status::GPU_FRAMES, LOAD_XMEM, 0x1000]);
+        cpu.gpu_status.frame_counter.store(42, Ordering::Relaxed);
+        cpu.update();
+        cpu.update();
+        assert_eq!((cpu.a_reg, cpu.x_reg), (42, LOAD_AMEM));
+    }
+
+    #[test]
+    fn the_cpu_rings_the_doorbell() {
+        let mut cpu = cpu(&[LOAD_AREG, 0x0400, STOR_AREG, gpu::status::RING_HEAD, RING_DBELL]);
+        for _ in 0..3 {
+            cpu.update();
+        }
+        assert_eq!(cpu.gpu_status.read(gpu::status::RING_DOORBELL), Some(0x0400));
+        assert_eq!(cpu.memory.rom[gpu::status::RING_HEAD as usize], NO_OPERAT);
+    }
+
+    #[test]
+    fn values_are_stored_without_a_register() {
+        let mut cpu = cpu(&[STOR_IMMV, gpu::status::RING_HEAD, 0x0420]);
+        cpu.update();
+        assert_eq!(cpu.gpu_status.read(gpu::status::RING_HEAD), Some(0x0420));
+        assert_eq!((cpu.instr_ptr, cpu.a_reg), (0x1003, 0));
+    }
+
+    #[test]
+    fn addresses_are_described_with_their_code() {
+        let mut cpu = cpu(&[]);
+        assert_eq!(cpu.describe(0x1004), "0x1004");
+        let info = "RVMD 1\nfile 0 main.rvmasm\n0x1004 2 0 7 5 divide .loop\n";
+        cpu.debug_info = Some(crate::debug::DebugInfo::read(info).unwrap());
+        assert_eq!(cpu.describe(0x1005), "0x1005 in routine `divide` (.loop) at main.rvmasm:7:5");
+    }
+}
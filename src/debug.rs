@@ -0,0 +1,169 @@
+use std::fmt;
+
+/// First line of every debug info file, followed by the format version
+const MAGIC: &str = "RVMD";
+const VERSION: &str = "1";
+
+/// Where the words of the ROM were assembled from, read from the ```ROM.dbg``` file rvmasm
+/// writes with ```--debug```
+#[derive(Debug, Default)]
+pub struct DebugInfo {
+    files: Vec<String>,
+    /// Ordered by address
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    address: u16,
+    len: u16,
+    file: usize,
+    line: usize,
+    column: usize,
+    routine: Option<String>,
+    label: Option<String>,
+}
+
+/// The code an address was assembled from
+#[derive(Debug)]
+pub struct Location<'a> {
+    pub file: &'a str,
+    pub line: usize,
+    pub column: usize,
+    pub routine: Option<&'a str>,
+    pub label: Option<&'a str>,
+}
+
+impl fmt::Display for Location<'_> {
+    /// Formats the location like ```routine `divide` (.loop) at arithmetics.rvmasm:26:5```
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(routine) = self.routine {
+            write!(f, "routine `{}` ", routine)?;
+        }
+        if let Some(label) = self.label {
+            write!(f, "({}) ", label)?;
+        }
+        write!(f, "at {}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+impl DebugInfo {
+    /// Reads the debug info file, returning nothing if there is none. A file that can't be read
+    /// is reported and ignored.
+    pub fn load(path: &str) -> Option<Self> {
+        let text = std::fs::read_to_string(path).ok()?;
+        match Self::read(&text) {
+            Ok(info) => Some(info),
+            Err(error) => {
+                use colored::Colorize;
+                println!("{}: Ignoring the debug info in {}: {}", "[WARNING]".yellow(), path, error);
+                None
+            }
+        }
+    }
+
+    /// Reads the text of a debug info file. The error tells which line couldn't be read and why.
+    pub fn read(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate();
+        let header: Vec<&str> =
+            lines.next().map(|(_, line)| line.split_whitespace().collect()).unwrap_or_default();
+        if header != [MAGIC, VERSION] {
+            return Err(format!("it doesn't start with `{} {}`", MAGIC, VERSION));
+        }
+
+        let mut info = Self::default();
+        for (index, line) in lines {
+            let error = |message: &str| format!("line {}: {}", index + 1, message);
+            if let Some(rest) = line.strip_prefix("file ") {
+                let (_, path) = rest.split_once(' ').ok_or_else(|| error("missing a path"))?;
+                info.files.push(path.to_string());
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.is_empty() {
+                continue;
+            }
+            if fields.len() != 7 {
+                return Err(error(
+                    "expected an address, a length, a file, a line, a column, a routine and a label",
+                ));
+            }
+            let number = |field: usize| -> Result<usize, String> {
+                let text = fields[field];
+                let parsed = match text.strip_prefix("0x") {
+                    Some(hex) => usize::from_str_radix(hex, 16),
+                    None => text.parse(),
+                };
+                parsed.map_err(|_| error(&format!("`{}` is not a number", text)))
+            };
+            let name = |field: usize| Some(fields[field].to_string()).filter(|name| name != "-");
+            info.entries.push(Entry {
+                address: number(0)? as u16,
+                len: number(1)? as u16,
+                file: number(2)?,
+                line: number(3)?,
+                column: number(4)?,
+                routine: name(5),
+                label: name(6),
+            });
+        }
+        info.entries.sort_by_key(|entry| entry.address);
+        Ok(info)
+    }
+
+    /// Returns the code the word at the address was assembled from
+    pub fn locate(&self, address: u16) -> Option<Location<'_>> {
+        let index = self.entries.partition_point(|entry| entry.address <= address).checked_sub(1)?;
+        let entry = &self.entries[index];
+        if address as usize >= entry.address as usize + entry.len as usize {
+            return None;
+        }
+        Some(Location {
+            file: self.files.get(entry.file).map_or("?", |file| file.as_str()),
+            line: entry.line,
+            column: entry.column,
+            routine: entry.routine.as_deref(),
+            label: entry.label.as_deref(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INFO: &str = "RVMD 1\nfile 0 main.rvmasm\nfile 1 lib/my print.rvmasm\n\
+        0x1008 1 1 3 1 - -\n0x1002 2 0 4 5 entry -\n0x1004 3 0 6 9 entry .loop\n";
+
+    #[test]
+    fn addresses_are_located_in_their_entry() {
+        let info = DebugInfo::read(INFO).unwrap();
+        let location = info.locate(0x1006).unwrap();
+        assert_eq!((location.file, location.line, location.column), ("main.rvmasm", 6, 9));
+        assert_eq!((location.routine, location.label), (Some("entry"), Some(".loop")));
+        assert_eq!(location.to_string(), "routine `entry` (.loop) at main.rvmasm:6:9");
+        assert_eq!(info.locate(0x1002).unwrap().to_string(), "routine `entry` at main.rvmasm:4:5");
+        assert_eq!(info.locate(0x1008).unwrap().to_string(), "at lib/my print.rvmasm:3:1");
+        // Addresses between entries and outside of every entry have no code
+        assert!(info.locate(0x1007).is_none());
+        assert!(info.locate(0x1001).is_none());
+        assert!(info.locate(0xFFFF).is_none());
+    }
+
+    #[test]
+    fn malformed_debug_info_is_rejected() {
+        let error = |text: &str| DebugInfo::read(text).unwrap_err();
+        assert_eq!(error(""), "it doesn't start with `RVMD 1`");
+        assert_eq!(error("RVMD 2\n"), "it doesn't start with `RVMD 1`");
+        assert_eq!(error("RVMD 1\nfile 0\n"), "line 2: missing a path");
+        assert_eq!(
+            error("RVMD 1\n0x1002 2 0 4 5 entry\n"),
+            "line 2: expected an address, a length, a file, a line, a column, a routine and a label"
+        );
+        assert_eq!(error("RVMD 1\n0x1002 two 0 4 5 entry -\n"), "line 2: `two` is not a number");
+        // A file index without a file is shown as unknown instead of being rejected
+        let info = DebugInfo::read("RVMD 1\n\n0x1002 2 3 4 5 - -\n").unwrap();
+        assert_eq!(info.locate(0x1003).unwrap().to_string(), "at ?:4:5");
+    }
+}
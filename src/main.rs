@@ -1,10 +1,9 @@
-use gpu;
-
 pub mod cpu;
+pub mod debug;
 pub mod memory;
 pub mod opcodes;
 
-pub const FONT_SIZE: f32 = 12.0;
+use std::sync::Arc;
 
 // NOTE: MEMORY LAYOUT
 // 0x0000 - 0x01FF | INPUT BUFFER
@@ -15,7 +14,10 @@ pub const FONT_SIZE: f32 = 12.0;
 // 0x0240 - 0x0249 | 0 - 9
 // 0x024A - 0x024F | : _ | & ? @
 // 0x0250          | EMPTY CHAR (0x0020)
-// 0x0300 - 0x0FFF | GPU BUFFER (3328 16-bit / 6656B)
+// 0x02F0 - 0x02F7 | GPU STATUS, COMMAND RING POINTERS (MAPPED TO THE GPU)
+// 0x0300 - 0x0FFF | GPU COMMAND RING (3328 16-bit / 6656B)
+// 0xE000 - 0xE53F | TILE PATTERNS, TILE MAP, SPRITE ATTRIBUTES
+// 0xF000 - 0xFE0F | BITMAP (160x90 pixels, 4 per 16-bit address)
 
 #[tokio::main]
 async fn main() {
@@ -23,22 +25,33 @@ async fn main() {
     println!("Sucks to be on windows");
 
 
+    // --- Text screen size, scaling and host font from the command line ---
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let gpu_config = gpu::config::GpuConfig::from_args(&args).unwrap_or_else(|error| {
+        use colored::Colorize;
+        println!("{}: {}", "[ERROR]".red(), error);
+        std::process::exit(1);
+    });
+
+    let gpu_status = Arc::new(gpu::status::Status::default());
+
+    let cpu_status = gpu_status.clone();
     let cpu_task = tokio::spawn( async {
-        let mut cpu = cpu::CPU::init();
+        let mut cpu = cpu::CPU::init(cpu_status);
 
         loop {
-            if cpu.halt_flag == false {
+            if !cpu.halt_flag {
                 #[cfg(debug_assertions)]
                 debug!(
                     "CPU instruction pointer: ",
-                    format!("{:#06X}", cpu.instr_ptr)
+                    cpu.describe(cpu.instr_ptr)
                 );
                 cpu.update();
             }
         }
     });
 
-    gpu::main();
+    gpu::main(gpu_status, gpu_config);
     cpu_task.await.unwrap();
 }
 
@@ -40,6 +40,6 @@ impl Memory {
             .unwrap();
 
         let file = img;
-        _ = file.write_at(&self.rom[instr_ptr as usize].to_be_bytes(), instr_ptr as u64 * 16);
+        _ = file.write_at(&self.rom[instr_ptr as usize].to_be_bytes(), instr_ptr as u64 * 2);
     }
 }
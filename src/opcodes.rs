@@ -11,26 +11,34 @@ pub const HALT_LOOP: u16 = 0x00AF;
 // --- OpCodes: Load into Register ---
 /// OpCode: Loads the following value into A register
 pub const LOAD_AREG: u16 = 0x00A1;
-/// OpCode: Loads the following value into B register
-pub const LOAD_BREG: u16 = 0x00A2;
-/// OpCode: Loads the following value into C register
-pub const LOAD_CREG: u16 = 0x00A3;
-/// OpCode: Loads the following value into D register
-pub const LOAD_DREG: u16 = 0x00A4;
+/// OpCode: Loads the following value into X register
+pub const LOAD_XREG: u16 = 0x00A2;
+/// OpCode: Loads the following value into Y register
+pub const LOAD_YREG: u16 = 0x00A3;
 /// OpCode: Loads the following value into G register
-pub const LOAD_GREG: u16 = 0x00AF;
+pub const LOAD_GREG: u16 = 0x00A5;
+
+// --- OpCodes: Load from Address into Register ---
+/// OpCode: Loads the value at the following address into A register
+pub const LOAD_AMEM: u16 = 0x00A6;
+/// OpCode: Loads the value at the following address into X register
+pub const LOAD_XMEM: u16 = 0x00A7;
+/// OpCode: Loads the value at the following address into Y register
+pub const LOAD_YMEM: u16 = 0x00A8;
 
 // --- OpCodes: Load into Register ---
 /// OpCode: Stores the A register's value to the following address
 pub const STOR_AREG: u16 = 0x00B1;
-/// OpCode: Stores the B register's value to the following address
-pub const STOR_BREG: u16 = 0x00B2;
-/// OpCode: Stores the C register's value to the following address
-pub const STOR_CREG: u16 = 0x00B3;
-/// OpCode: Stores the D register's value to the following address
-pub const STOR_DREG: u16 = 0x00B3;
-/// OpCode: Stores the G register's value to the following address
-pub const STOR_GREG: u16 = 0x00BF;
+/// OpCode: Stores the X register's value to the following address
+pub const STOR_XREG: u16 = 0x00B2;
+/// OpCode: Stores the Y register's value to the following address
+pub const STOR_YREG: u16 = 0x00B3;
+/// OpCode: Pushes the G register's value to the GPU's command ring. Waits while the ring is full.
+pub const STOR_GREG: u16 = 0x00B5;
+
+// --- OpCodes: Store Value ---
+/// OpCode: Stores the second value after the opcode to the address given by the first one
+pub const STOR_IMMV: u16 = 0x00B6;
 
 // --- OpCodes: Jump to Subroutine ---
 /// OpCode: Sets the instruction pointer to the value of the following address, jumping there.
@@ -79,6 +87,20 @@ pub const MUL_REG_V: u16 = 0x00D4;
 ///          specified in the second address after the opcode
 pub const DIV_REG_V: u16 = 0x00D5;
 
+// --- OpCodes: Set interrupt handler ---
+/// OpCode: Sets the address of the routine that is called after the GPU finished a frame (vblank
+///         interrupt) to the following value. An address of 0x0000 disables the interrupt.
+pub const SET_INT_V: u16 = 0x00E0;
+
+// --- OpCodes: Return from interrupt ---
+/// OpCode: Fetches the value the interrupt pushed to the stack and sets the instruction pointer
+///         to it, returning to where the program was interrupted.
+pub const RET_FR_INT: u16 = 0x00E1;
+
+// --- OpCodes: Ring the GPU's doorbell ---
+/// OpCode: Makes the GPU execute everything that was pushed to its command ring so far
+pub const RING_DBELL: u16 = 0x00E2;
+
 // NOTE: GPU OPCODES
 // TODO:
 
@@ -93,7 +115,9 @@ pub const GPU_NO_OPERAT: u16 = 0xA000;
 pub const GPU_DRAW_LETT: u16 = 0xA001;
 
 // --- OpCodes: Reset Buf Ptr ---
-/// GPU OpCode: Resets the GPU's buf_ptr to the beginning of the GPU buffer.
+/// GPU OpCode: Moves the command ring's tail up to the doorbell, discarding the commands that
+///             were handed to the GPU after this one. Commands pushed after the doorbell was rung
+///             are kept.
 pub const GPU_RESET_PTR: u16 = 0xA0A2;
 
 // --- OpCodes: Update GPU ---